@@ -72,16 +72,20 @@ if ! command -v cargo &>/dev/null; then
 fi
 echo -e "${GREEN}✓ Rust found${NC}"
 
-# Docker
-if ! command -v docker &>/dev/null; then
-    echo -e "${RED}Error: Docker is not installed.${NC}"
-    exit 1
-fi
-if ! docker info &>/dev/null; then
-    echo -e "${RED}Error: Docker daemon is not running.${NC}"
+# Container runtime: Docker, or Podman
+if command -v docker &>/dev/null; then
+    if ! docker info &>/dev/null; then
+        echo -e "${RED}Error: Docker daemon is not running.${NC}"
+        exit 1
+    fi
+    echo -e "${GREEN}✓ Docker running${NC}"
+elif command -v podman &>/dev/null; then
+    echo -e "${GREEN}✓ Podman found${NC}"
+else
+    echo -e "${RED}Error: neither Docker nor Podman is installed.${NC}"
+    echo "To run without a sandbox instead, set sandbox = \"none\" in ~/.config/agerus/config.toml."
     exit 1
 fi
-echo -e "${GREEN}✓ Docker running${NC}"
 
 # Ollama
 if ! command -v ollama &>/dev/null; then
@@ -1,75 +1,67 @@
-use crate::app::{AppEvent, MessageRole};
+use crate::app::{AppEvent, ChatMessage, MessageRole};
 use crate::audio::AudioPlayer; // Import
-use crate::config::Config;
-use crate::mcp::{McpRequest, ToolDefinition};
+use crate::config::{Config, SandboxMode, ToolMode};
+use crate::mcp::{self, McpRequest, ToolDefinition};
 use anyhow::Result;
-use futures_util::StreamExt;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use serde_json::json;
+use std::collections::HashSet;
 use tokio::sync::{mpsc, oneshot};
 
-const MAX_LOOPS: usize = 10;
-
-// ... (Keep existing structs and SYSTEM_PROMPT) ...
-// (Retain ChatResponse, Message, ToolCall, ToolFunction structs)
-#[derive(Deserialize, Debug)]
-struct ChatResponse {
-    message: Option<Message>,
-    #[serde(default)]
-    done: bool,
-    #[serde(default)]
-    error: Option<String>,
-}
-
-#[derive(Deserialize, Debug)]
-struct Message {
-    content: Option<String>,
-    thinking: Option<String>,
-    reasoning_content: Option<String>,
-    tool_calls: Option<Vec<ToolCall>>,
-}
+mod anthropic;
+pub mod context;
+#[cfg(test)]
+mod mock_http;
+mod ollama;
+mod openai;
+pub mod provider;
+mod text_tools;
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
-struct ToolCall {
-    function: ToolFunction,
-}
+use provider::{
+    build_provider, ChatProvider, ChatRequest, ProviderMessage, ProviderRole, ToolCall,
+    ToolsRejected, TurnSink,
+};
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
-struct ToolFunction {
-    name: String,
-    arguments: serde_json::Value,
-}
+const MAX_LOOPS: usize = 10;
 
 const AGENT_SYSTEM_PROMPT: &str = r#"
-You are Agerus, an expert software development agent running in a secure Docker sandbox.
+You are Agerus, an expert software development agent{environment_intro}.
 # CRITICAL OPERATIONAL RULES:
 1. **CONTEXT IS KING**: 
    - You have NO magic knowledge of the user's files.
    - ALWAYS run `list_files` to explore the directory structure first.
    - ALWAYS run `read_file` to see file content before editing.
    - ALWAYS run `consult_documentation` to see the documentation for the language.
-2. **SANDBOXED ENVIRONMENT**:
-   - You are running inside a Docker container.
-   - You can safely run destructive commands (rm, etc) if necessary.
-   - You cannot open GUI applications.
+{environment}
 3. **THINK BEFORE ACTING**:
    - Before calling a tool, briefly explain your plan.
    - If a tool fails, analyze the error and try a different approach.
 4. **FORMATTING**:
-   - When writing code, return the full file content if the file is small.
+   - To change an existing file, use `edit_file` with an exact snippet copied from `read_file` (without the line-number prefix).
+   - For changes spanning several places or files, use `apply_patch` with a unified diff.
+   - Use `write_file` with the full content only for new or small files.
    - For large files, ensure you have read them first to avoid overwriting content blindly.
 "#;
 
+/// Rule 2 of the prompt for `sandbox = "container"`
+const SANDBOX_ENVIRONMENT: &str = r#"2. **SANDBOXED ENVIRONMENT**:
+   - You are running inside a Docker container.
+   - You can safely run destructive commands (rm, etc) if necessary.
+   - You cannot open GUI applications.
+   - `run_command` blocks until the command ends. Start servers and watchers with `start_process` and follow them with `process_output`."#;
+
+/// Rule 2 of the prompt for `sandbox = "none"`
+const HOST_ENVIRONMENT: &str = r#"2. **NO SANDBOX**:
+   - Your commands run directly on the user's machine, as the user. Nothing contains them.
+   - Never run destructive or system-wide commands (rm -rf, package installs, git push, ...) unless the user asked for them.
+   - The user may have to approve tool calls. If one is declined, ask how to proceed instead of retrying.
+   - You cannot open GUI applications.
+   - `run_command` blocks until the command ends. Start servers and watchers with `start_process` and follow them with `process_output`."#;
+
 pub async fn run_agent_loop(
     config: Config,
-    history: Vec<crate::app::ChatMessage>,
+    history: Vec<ChatMessage>,
     app_tx: mpsc::Sender<AppEvent>,
     mcp_tx: mpsc::Sender<McpRequest>,
 ) -> Result<()> {
-    // --- SETUP AUDIO ---
-    let audio = AudioPlayer::new(config.voice_server_url.clone(), config.voice_enabled);
-
     // 1. Fetch Tools from MCP Server
     let (tx, rx) = oneshot::channel();
     if let Err(e) = mcp_tx.send(McpRequest::ListTools(tx)).await {
@@ -89,41 +81,19 @@ pub async fn run_agent_loop(
         }
     };
 
-    let ollama_tools: Vec<serde_json::Value> = tools
-        .iter()
-        .map(|t| {
-            json!({
-                "type": "function",
-                "function": {
-                    "name": t.name,
-                    "description": t.description,
-                    "parameters": t.input_schema
-                }
-            })
-        })
-        .collect();
+    let provider = build_provider(&config);
+    let window = config.context_window();
 
     // 2. CONSTRUCT MESSAGE HISTORY
-    let mut messages = vec![json!({
-        "role": "system",
-        "content": AGENT_SYSTEM_PROMPT
-    })];
-
-    let history_json: Vec<serde_json::Value> = history
-        .iter()
-        .map(|msg| {
-            let role = match msg.role {
-                MessageRole::User => "user",
-                MessageRole::Assistant | MessageRole::Thinking => "assistant",
-                MessageRole::System | MessageRole::Error => "system",
-            };
-            json!({ "role": role, "content": msg.content })
-        })
-        .collect();
-
-    messages.extend(history_json);
-
-    let client = Client::new();
+    // `history` follows the turn as it grows, so it can be compacted between tool rounds
+    let mut history = history;
+    let mut messages = history_to_messages(&history);
+
+    let mut native_tools = config.tool_mode != ToolMode::Prompt;
+    let mut prompt_tools = config.tool_mode == ToolMode::Prompt;
+    // Estimated exactly as sent, tool docs and all
+    let mut system = system_prompt(&config, prompt_tools, &tools);
+    let mut can_compact = true;
     let mut loops = 0;
 
     loop {
@@ -132,207 +102,370 @@ pub async fn run_agent_loop(
         }
         loops += 1;
 
-        // Try with tools first
-        let mut body = json!({
-            "model": config.model,
-            "messages": messages,
-            "tools": ollama_tools,
-            "stream": true
-        });
+        // 3. COMPACT HISTORY IF IT NO LONGER FITS (again after every tool round)
+        if can_compact {
+            let offered = if native_tools { &tools[..] } else { &[] };
+            match compact_if_needed(provider.as_ref(), &config, &system, offered, &mut history).await {
+                Ok(true) => {
+                    messages = history_to_messages(&history);
+                    app_tx
+                        .send(AppEvent::HistoryCompacted(history.clone()))
+                        .await?;
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    // Don't pay for a failing summary on every round
+                    can_compact = false;
+                    app_tx
+                        .send(AppEvent::Thinking(format!("Compaction failed: {}", e)))
+                        .await?;
+                }
+            }
+        }
 
-        let mut res = client.post(&config.ollama_url).json(&body).send().await;
+        let mut sink = TurnSink::new(app_tx.clone());
+        let mut result = {
+            let wire = wire_messages(&messages, prompt_tools);
+            let request = ChatRequest {
+                model: &config.model,
+                system: &system,
+                messages: &wire,
+                tools: if native_tools { &tools[..] } else { &[] },
+                context_window: window,
+            };
+            provider.stream_chat(&request, &mut sink).await
+        };
 
         // Fallback Logic
-        if let Ok(ref response) = res {
-            if response.status() == reqwest::StatusCode::BAD_REQUEST {
+        if matches!(&result, Err(e) if e.is::<ToolsRejected>()) {
+            native_tools = false;
+            prompt_tools = config.tool_mode == ToolMode::Auto;
+            let notice = if prompt_tools {
+                "Falling back to prompt-based tool calling."
+            } else {
+                "Falling back to text-only mode."
+            };
+            app_tx
+                .send(AppEvent::Thinking(format!(
+                    "Model '{}' rejected tools. {}",
+                    config.model, notice
+                )))
+                .await?;
+            system = system_prompt(&config, prompt_tools, &tools);
+            let wire = wire_messages(&messages, prompt_tools);
+            let request = ChatRequest {
+                model: &config.model,
+                system: &system,
+                messages: &wire,
+                tools: &[],
+                context_window: window,
+            };
+            result = provider.stream_chat(&request, &mut sink).await;
+        }
+
+        if let Err(e) = result {
+            app_tx.send(AppEvent::Error(e.to_string())).await?;
+            break;
+        }
+
+        let TurnSink {
+            content: full_content,
+            mut tool_calls,
+            ..
+        } = sink;
+
+        if prompt_tools && tool_calls.is_empty() {
+            tool_calls = text_tools::parse_tool_calls(&full_content, &tools);
+        }
+
+        // --- VOICE TRIGGER ---
+        // If we have content and no tools (it's a text response), speak it.
+        if !full_content.is_empty() && tool_calls.is_empty() {
+            let text_to_speak = full_content.clone();
+            let audio_player =
+                AudioPlayer::new(config.voice_server_url.clone(), config.voice_enabled);
+            // Fire and forget audio so we don't block the loop
+            tokio::spawn(async move {
+                let _ = audio_player.play_text(&text_to_speak).await;
+            });
+        }
+
+        if tool_calls.is_empty() {
+            break;
+        }
+
+        let mut turn = ChatMessage::new(MessageRole::Assistant, full_content.clone());
+        turn.tool_calls = tool_calls.clone();
+        history.push(turn);
+        let mut assistant = ProviderMessage::text(ProviderRole::Assistant, full_content);
+        assistant.tool_calls = tool_calls.clone();
+        messages.push(assistant);
+        app_tx.send(AppEvent::ToolCalls(tool_calls.clone())).await?;
+
+        for tool in &tool_calls {
+            if !approve(&config, &app_tx, tool).await? {
+                let result = "The user declined this tool call. Ask them how to proceed instead of retrying it.".to_string();
                 app_tx
-                    .send(AppEvent::Thinking(format!(
-                        "Model '{}' rejected tools. Falling back to text-only mode.",
-                        config.model
-                    )))
+                    .send(AppEvent::ToolResult {
+                        call: tool.clone(),
+                        output: result.clone(),
+                        command: None,
+                    })
                     .await?;
-                body = json!({ "model": config.model, "messages": messages, "stream": true });
-                res = client.post(&config.ollama_url).json(&body).send().await;
+                history.push(ChatMessage::tool_result(tool, result.clone(), None));
+                messages.push(ProviderMessage::tool_result(tool, result));
+                continue;
             }
-        }
 
-        match res {
-            Err(e) => {
+            let (tx, rx) = oneshot::channel();
+
+            if let Err(e) = mcp_tx
+                .send(McpRequest::CallTool {
+                    name: tool.name.clone(),
+                    arguments: tool.arguments.clone(),
+                    response_tx: tx,
+                })
+                .await
+            {
                 app_tx
-                    .send(AppEvent::Error(format!("Ollama Connection Error: {}", e)))
+                    .send(AppEvent::Error(format!("Failed to call tool: {}", e)))
                     .await?;
                 break;
             }
-            Ok(response) => {
-                if !response.status().is_success() {
-                    let text = response.text().await.unwrap_or_default();
-                    app_tx
-                        .send(AppEvent::Error(format!("Ollama API Error: {}", text)))
-                        .await?;
-                    break;
-                }
 
-                let mut stream = response.bytes_stream();
-                let mut buffer = String::new();
-                let mut full_content = String::new();
-                let mut buffer_tools = Vec::new();
-                let mut parsing_thought = false;
-
-                while let Some(chunk_res) = stream.next().await {
-                    match chunk_res {
-                        Err(e) => {
-                            app_tx
-                                .send(AppEvent::Error(format!("Stream Error: {}", e)))
-                                .await?;
-                            break;
-                        }
-                        Ok(chunk) => {
-                            if let Ok(s) = std::str::from_utf8(&chunk) {
-                                buffer.push_str(s);
-                                while let Some(pos) = buffer.find('\n') {
-                                    let line = buffer[..pos].to_string();
-                                    buffer.drain(..=pos);
-                                    if line.trim().is_empty() {
-                                        continue;
-                                    }
-
-                                    match serde_json::from_str::<ChatResponse>(&line) {
-                                        Ok(resp) => {
-                                            if let Some(err) = resp.error {
-                                                app_tx
-                                                    .send(AppEvent::Error(format!(
-                                                        "Ollama Error: {}",
-                                                        err
-                                                    )))
-                                                    .await?;
-                                            }
-                                            if let Some(msg) = resp.message {
-                                                if let Some(think) = msg.thinking {
-                                                    if !think.is_empty() {
-                                                        app_tx
-                                                            .send(AppEvent::Thinking(think))
-                                                            .await?;
-                                                    }
-                                                } else if let Some(reason) = msg.reasoning_content {
-                                                    if !reason.is_empty() {
-                                                        app_tx
-                                                            .send(AppEvent::Thinking(reason))
-                                                            .await?;
-                                                    }
-                                                }
-
-                                                if let Some(content) = msg.content {
-                                                    if !content.is_empty() {
-                                                        let mut text = content.clone();
-                                                        if text.contains("<think>") {
-                                                            parsing_thought = true;
-                                                            text = text.replace("<think>", "");
-                                                        }
-                                                        if text.contains("</think>") {
-                                                            parsing_thought = false;
-                                                            let parts: Vec<&str> =
-                                                                text.split("</think>").collect();
-                                                            if let Some(t) = parts.first() {
-                                                                if !t.is_empty() {
-                                                                    app_tx
-                                                                        .send(AppEvent::Thinking(
-                                                                            t.to_string(),
-                                                                        ))
-                                                                        .await?;
-                                                                }
-                                                            }
-                                                            if parts.len() > 1 {
-                                                                let c = parts[1];
-                                                                if !c.is_empty() {
-                                                                    full_content.push_str(c);
-                                                                    app_tx
-                                                                        .send(AppEvent::Token(
-                                                                            c.to_string(),
-                                                                        ))
-                                                                        .await?;
-                                                                }
-                                                            }
-                                                            continue;
-                                                        }
-
-                                                        if parsing_thought {
-                                                            app_tx
-                                                                .send(AppEvent::Thinking(text))
-                                                                .await?;
-                                                        } else {
-                                                            full_content.push_str(&text);
-                                                            app_tx
-                                                                .send(AppEvent::Token(text))
-                                                                .await?;
-                                                        }
-                                                    }
-                                                }
-                                                if let Some(calls) = msg.tool_calls {
-                                                    buffer_tools.extend(calls);
-                                                }
-                                            }
-                                        }
-                                        Err(_) => {}
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+            let (result, command) = match rx.await {
+                Ok(Ok(out)) => (out.text, out.command),
+                Ok(Err(e)) => (format!("Tool Execution Error: {}", e), None),
+                Err(_) => ("Tool Execution Panicked".to_string(), None),
+            };
 
-                // --- VOICE TRIGGER ---
-                // If we have content and no tools (it's a text response), speak it.
-                if !full_content.is_empty() && buffer_tools.is_empty() {
-                    let text_to_speak = full_content.clone();
-                    let audio_player =
-                        AudioPlayer::new(config.voice_server_url.clone(), config.voice_enabled);
-                    // Fire and forget audio so we don't block the loop
-                    tokio::spawn(async move {
-                        let _ = audio_player.play_text(&text_to_speak).await;
-                    });
-                }
+            app_tx
+                .send(AppEvent::ToolResult {
+                    call: tool.clone(),
+                    output: result.clone(),
+                    command: command.clone(),
+                })
+                .await?;
+            history.push(ChatMessage::tool_result(tool, result.clone(), command));
+            messages.push(ProviderMessage::tool_result(tool, result));
+        }
+    }
 
-                if buffer_tools.is_empty() {
-                    break;
-                }
+    Ok(())
+}
 
-                messages.push(json!({ "role": "assistant", "content": full_content, "tool_calls": buffer_tools }));
+/// Summarizes older turns when the prompt no longer fits the context window.
+/// Returns whether `history` changed.
+async fn compact_if_needed(
+    provider: &dyn ChatProvider,
+    config: &Config,
+    system: &str,
+    tools: &[ToolDefinition],
+    history: &mut Vec<ChatMessage>,
+) -> Result<bool> {
+    let window = config.context_window();
+    if !context::needs_compaction(context::estimate_prompt(system, tools, history), window) {
+        return Ok(false);
+    }
+    match context::compact_history(provider, &config.model, history, window).await? {
+        Some(compacted) => {
+            *history = compacted;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
 
-                for tool in &buffer_tools {
-                    let (tx, rx) = oneshot::channel();
-                    app_tx
-                        .send(AppEvent::CommandStart(format!(
-                            "{}(...)",
-                            tool.function.name
-                        )))
-                        .await?;
+/// Asks the user first when `tool_approval` covers this tool. A prompt that goes
+/// unanswered (the turn was cancelled) counts as a no.
+async fn approve(config: &Config, app_tx: &mpsc::Sender<AppEvent>, tool: &ToolCall) -> Result<bool> {
+    if !mcp::needs_approval(config.tool_approval(), &tool.name) {
+        return Ok(true);
+    }
+    let (tx, rx) = oneshot::channel();
+    app_tx
+        .send(AppEvent::ApprovalRequest {
+            call: tool.clone(),
+            response_tx: tx,
+        })
+        .await?;
+    Ok(rx.await.unwrap_or(false))
+}
 
-                    if let Err(e) = mcp_tx
-                        .send(McpRequest::CallTool {
-                            name: tool.function.name.clone(),
-                            arguments: tool.function.arguments.clone(),
-                            response_tx: tx,
-                        })
-                        .await
-                    {
-                        app_tx
-                            .send(AppEvent::Error(format!("Failed to call tool: {}", e)))
-                            .await?;
-                        break;
-                    }
+fn system_prompt(config: &Config, prompt_tools: bool, tools: &[ToolDefinition]) -> String {
+    let (intro, environment) = match config.sandbox {
+        SandboxMode::Container => (" running in a secure Docker sandbox", SANDBOX_ENVIRONMENT),
+        SandboxMode::None => (" running directly on the user's machine", HOST_ENVIRONMENT),
+    };
+    let base = AGENT_SYSTEM_PROMPT
+        .replace("{environment_intro}", intro)
+        .replace("{environment}", environment);
+    if prompt_tools {
+        format!("{}{}", base, text_tools::tool_prompt(tools))
+    } else {
+        base
+    }
+}
 
-                    let result = match rx.await {
-                        Ok(Ok(out)) => out,
-                        Ok(Err(e)) => format!("Tool Execution Error: {}", e),
-                        Err(_) => "Tool Execution Panicked".to_string(),
-                    };
+fn history_to_messages(history: &[ChatMessage]) -> Vec<ProviderMessage> {
+    // Providers reject tool calls without results (and vice versa), which is
+    // what an aborted turn leaves behind, so only keep matched pairs.
+    let paired = paired_calls(history);
 
-                    app_tx.send(AppEvent::CommandEnd(result.clone())).await?;
-                    messages.push(json!({ "role": "tool", "content": result }));
+    history
+        .iter()
+        .zip(&paired)
+        .filter(|(msg, _)| !msg.ui_only)
+        .filter_map(|(msg, paired)| {
+            let role = match msg.role {
+                MessageRole::User => ProviderRole::User,
+                MessageRole::Assistant | MessageRole::Thinking => ProviderRole::Assistant,
+                MessageRole::System | MessageRole::Error => ProviderRole::System,
+                MessageRole::Tool => ProviderRole::Tool,
+            };
+            let mut out = ProviderMessage::text(role, msg.content.clone());
+            match msg.role {
+                MessageRole::Tool => {
+                    if paired.is_empty() {
+                        return None;
+                    }
+                    out.tool_call_id = msg.tool_call_id.clone();
+                    out.tool_name = msg.tool_name.clone();
+                }
+                MessageRole::Assistant => {
+                    out.tool_calls = msg
+                        .tool_calls
+                        .iter()
+                        .filter(|c| paired.contains(c.id.as_str()))
+                        .cloned()
+                        .collect();
+                }
+                _ => {}
+            }
+            Some(out)
+        })
+        .collect()
+}
+
+/// For each message, the call ids it takes part in: an assistant turn's calls
+/// that got a result, and the call a tool result answers. Results only count
+/// for the turn they follow, since ids are just unique within a turn (Ollama
+/// and text mode number calls from 0 every time).
+fn paired_calls(history: &[ChatMessage]) -> Vec<HashSet<&str>> {
+    let mut paired = vec![HashSet::new(); history.len()];
+    let mut turn: Option<usize> = None;
+    for (i, msg) in history.iter().enumerate() {
+        match msg.role {
+            MessageRole::Assistant => turn = (!msg.tool_calls.is_empty()).then_some(i),
+            MessageRole::Tool => {
+                if let (Some(t), Some(id)) = (turn, msg.tool_call_id.as_deref())
+                    && history[t].tool_calls.iter().any(|c| c.id == id)
+                    && paired[t].insert(id)
+                {
+                    paired[i].insert(id);
                 }
             }
+            MessageRole::User | MessageRole::Thinking => turn = None,
+            MessageRole::System | MessageRole::Error => {}
         }
     }
+    paired
+}
 
-    Ok(())
+/// Native mode sends the log as-is; prompt mode rewrites tool traffic as plain text.
+fn wire_messages(messages: &[ProviderMessage], prompt_tools: bool) -> Vec<ProviderMessage> {
+    if prompt_tools {
+        text_tools::as_text_protocol(messages)
+    } else {
+        messages.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn assistant(calls: &[&str]) -> ChatMessage {
+        let mut msg = ChatMessage::new(MessageRole::Assistant, String::new());
+        msg.tool_calls = calls
+            .iter()
+            .map(|id| ToolCall {
+                id: id.to_string(),
+                name: "read_file".into(),
+                arguments: json!({}),
+            })
+            .collect();
+        msg
+    }
+
+    fn result(id: &str) -> ChatMessage {
+        let mut msg = ChatMessage::new(MessageRole::Tool, format!("result of {}", id));
+        msg.tool_call_id = Some(id.to_string());
+        msg.tool_name = Some("read_file".into());
+        msg
+    }
+
+    fn user(text: &str) -> ChatMessage {
+        ChatMessage::new(MessageRole::User, text.into())
+    }
+
+    #[test]
+    fn keeps_answered_calls() {
+        let history = [user("hi"), assistant(&["call_0", "call_1"]), result("call_0"), result("call_1")];
+        let messages = history_to_messages(&history);
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[1].tool_calls.len(), 2);
+        assert_eq!(messages[3].tool_call_id.as_deref(), Some("call_1"));
+    }
+
+    #[test]
+    fn skips_ui_notices() {
+        let notice = ChatMessage {
+            ui_only: true,
+            ..ChatMessage::new(MessageRole::System, "🗜️ Compacted context: 40 messages → 6.".into())
+        };
+        let history = [user("hi"), notice, assistant(&[])];
+        let messages = history_to_messages(&history);
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| m.role != ProviderRole::System));
+    }
+
+    #[test]
+    fn drops_a_dangling_call_whose_id_another_turn_answered() {
+        let history = [
+            user("first"),
+            assistant(&["call_0"]),
+            result("call_0"),
+            user("second"),
+            // Aborted before its result came back
+            assistant(&["call_0", "call_1"]),
+            user("third"),
+            assistant(&["call_0"]),
+            result("call_0"),
+        ];
+        let messages = history_to_messages(&history);
+        assert_eq!(messages[1].tool_calls.len(), 1);
+        assert!(messages[4].tool_calls.is_empty());
+        assert_eq!(messages[6].tool_calls.len(), 1);
+        assert_eq!(messages.iter().filter(|m| m.role == ProviderRole::Tool).count(), 2);
+    }
+
+    #[test]
+    fn drops_results_that_do_not_follow_their_call() {
+        let history = [
+            user("hi"),
+            assistant(&["call_0"]),
+            result("call_0"),
+            result("call_0"),
+            user("again"),
+            result("call_0"),
+            assistant(&["call_1"]),
+            result("call_0"),
+        ];
+        let messages = history_to_messages(&history);
+        let results: Vec<_> = messages.iter().filter(|m| m.role == ProviderRole::Tool).collect();
+        assert_eq!(results.len(), 1);
+        assert!(messages.last().unwrap().tool_calls.is_empty());
+    }
 }
@@ -0,0 +1,361 @@
+use super::provider::{
+    drain_lines, parse_arguments, ChatProvider, ChatRequest, ProviderMessage, ProviderRole,
+    ToolCall, TurnSink,
+};
+use anyhow::{anyhow, Result};
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+// Anthropic Messages API (`/v1/messages`, SSE streaming)
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    ContentBlockStart {
+        index: usize,
+        content_block: ContentBlock,
+    },
+    ContentBlockDelta {
+        index: usize,
+        delta: BlockDelta,
+    },
+    MessageStop,
+    Error {
+        error: serde_json::Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    ToolUse {
+        id: String,
+        name: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BlockDelta {
+    TextDelta { text: String },
+    ThinkingDelta { thinking: String },
+    InputJsonDelta { partial_json: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Default)]
+struct PartialCall {
+    id: String,
+    name: String,
+    input_json: String,
+}
+
+pub struct AnthropicProvider {
+    client: Client,
+    url: String,
+    api_key: Option<String>,
+    max_tokens: u32,
+}
+
+impl AnthropicProvider {
+    pub fn new(url: String, api_key: Option<String>, max_tokens: u32) -> Self {
+        Self {
+            client: Client::new(),
+            url,
+            api_key,
+            max_tokens,
+        }
+    }
+
+    fn authorize(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let req = req.header("anthropic-version", ANTHROPIC_VERSION);
+        match &self.api_key {
+            Some(key) => req.header("x-api-key", key),
+            None => req,
+        }
+    }
+
+    /// The Messages API only knows `user` and `assistant`, requires them to alternate,
+    /// and carries tool results as `tool_result` blocks inside user turns.
+    fn build_messages(messages: &[ProviderMessage]) -> Vec<serde_json::Value> {
+        let mut out: Vec<serde_json::Value> = Vec::new();
+
+        for msg in messages {
+            let (role, mut blocks) = match msg.role {
+                ProviderRole::User | ProviderRole::System => {
+                    ("user", vec![json!({ "type": "text", "text": msg.content })])
+                }
+                ProviderRole::Assistant => {
+                    let mut blocks = Vec::new();
+                    if !msg.content.is_empty() {
+                        blocks.push(json!({ "type": "text", "text": msg.content }));
+                    }
+                    for call in &msg.tool_calls {
+                        blocks.push(json!({
+                            "type": "tool_use",
+                            "id": call.id,
+                            "name": call.name,
+                            "input": call.arguments
+                        }));
+                    }
+                    ("assistant", blocks)
+                }
+                ProviderRole::Tool => (
+                    "user",
+                    vec![json!({
+                        "type": "tool_result",
+                        "tool_use_id": msg.tool_call_id,
+                        "content": msg.content
+                    })],
+                ),
+            };
+
+            if blocks.is_empty() {
+                continue;
+            }
+
+            match out.last_mut() {
+                Some(last) if last["role"] == role => {
+                    if let Some(content) = last["content"].as_array_mut() {
+                        content.append(&mut blocks);
+                    }
+                }
+                _ => out.push(json!({ "role": role, "content": blocks })),
+            }
+        }
+
+        out
+    }
+
+    async fn stream(&self, request: &ChatRequest<'_>, sink: &mut TurnSink) -> Result<()> {
+        let mut body = json!({
+            "model": request.model,
+            "max_tokens": self.max_tokens,
+            "system": request.system,
+            "messages": Self::build_messages(request.messages),
+            "stream": true
+        });
+        if !request.tools.is_empty() {
+            let tools: Vec<serde_json::Value> = request
+                .tools
+                .iter()
+                .map(|t| {
+                    json!({
+                        "name": t.name,
+                        "description": t.description,
+                        "input_schema": t.input_schema
+                    })
+                })
+                .collect();
+            body["tools"] = json!(tools);
+        }
+
+        let response = self
+            .authorize(self.client.post(&self.url))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Anthropic Connection Error: {}", e))?;
+
+        if !response.status().is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Anthropic API Error: {}", text));
+        }
+
+        let mut stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        let mut partial_calls: BTreeMap<usize, PartialCall> = BTreeMap::new();
+
+        'outer: while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| anyhow!("Stream Error: {}", e))?;
+            buffer.extend_from_slice(&chunk);
+
+            for line in drain_lines(&mut buffer) {
+                // `event:` lines duplicate the `type` field of the data payload
+                let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                    continue;
+                };
+                let Ok(event) = serde_json::from_str::<StreamEvent>(data) else {
+                    continue;
+                };
+
+                match event {
+                    StreamEvent::ContentBlockStart {
+                        index,
+                        content_block: ContentBlock::ToolUse { id, name },
+                    } => {
+                        partial_calls.insert(
+                            index,
+                            PartialCall {
+                                id,
+                                name,
+                                input_json: String::new(),
+                            },
+                        );
+                    }
+                    StreamEvent::ContentBlockDelta { index, delta } => match delta {
+                        BlockDelta::TextDelta { text } => sink.content(&text).await?,
+                        BlockDelta::ThinkingDelta { thinking } => sink.reasoning(&thinking).await?,
+                        BlockDelta::InputJsonDelta { partial_json } => {
+                            if let Some(call) = partial_calls.get_mut(&index) {
+                                call.input_json.push_str(&partial_json);
+                            }
+                        }
+                        BlockDelta::Other => {}
+                    },
+                    StreamEvent::MessageStop => break 'outer,
+                    StreamEvent::Error { error } => {
+                        return Err(anyhow!("Anthropic Error: {}", error));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for call in partial_calls.into_values() {
+            sink.tool_call(ToolCall {
+                id: call.id,
+                name: call.name,
+                arguments: parse_arguments(&call.input_json),
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn models(&self) -> Result<Vec<String>> {
+        let url = self.url.replace("/messages", "/models");
+        let json: serde_json::Value = self
+            .authorize(self.client.get(&url))
+            .send()
+            .await?
+            .json()
+            .await?;
+        let names = json
+            .get("data")
+            .and_then(|v| v.as_array())
+            .map(|models| {
+                models
+                    .iter()
+                    .filter_map(|m| m.get("id").and_then(|n| n.as_str()))
+                    .map(|s| s.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(names)
+    }
+}
+
+impl ChatProvider for AnthropicProvider {
+    fn stream_chat<'a>(
+        &'a self,
+        request: &'a ChatRequest<'a>,
+        sink: &'a mut TurnSink,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.stream(request, sink))
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(self.models())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::mock_http::{drain_events, serve, tool};
+    use crate::mcp::ToolDefinition;
+    use tokio::sync::mpsc;
+
+    fn request<'a>(messages: &'a [ProviderMessage], tools: &'a [ToolDefinition]) -> ChatRequest<'a> {
+        ChatRequest {
+            model: "claude",
+            system: "system prompt",
+            messages,
+            tools,
+            context_window: 8192,
+        }
+    }
+
+    #[tokio::test]
+    async fn stitches_sse_events_and_input_json_fragments() {
+        let (url, server) = serve(
+            200,
+            &[
+                b"event: message_start\ndata: {\"type\":\"message_start\",\"message\":{}}\n\n",
+                b"event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"thinking\",\"thinking\":\"\"}}\n\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"thinking_delta\",\"thinking\":\"let me",
+                b" see\"}}\n\n",
+                b"data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"text_delta\",\"text\":\"Reading it.\"}}\n\n",
+                b"data: {\"type\":\"content_block_start\",\"index\":2,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_1\",\"name\":\"read_file\",\"input\":{}}}\n\n",
+                b"data: {\"type\":\"content_block_delta\",\"index\":2,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"path\\\":\"}}\n\ndata: {\"type\":\"content_block_delta\",\"index\":2,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\" \\\"a.rs\\\"}\"}}\n\n",
+                b"data: {\"type\":\"message_stop\"}\n\n",
+            ],
+        )
+        .await;
+        let provider = AnthropicProvider::new(format!("{}/v1/messages", url), Some("key".into()), 1024);
+        let (tx, mut rx) = mpsc::channel(100);
+        let mut sink = TurnSink::new(tx);
+        let call = ToolCall {
+            id: "toolu_0".into(),
+            name: "list_files".into(),
+            arguments: json!({}),
+        };
+        let mut assistant = ProviderMessage::text(ProviderRole::Assistant, "");
+        assistant.tool_calls = vec![call.clone()];
+        let messages = [
+            ProviderMessage::text(ProviderRole::User, "hi"),
+            assistant,
+            ProviderMessage::tool_result(&call, "src/".into()),
+        ];
+        let tools = [tool("read_file")];
+
+        provider.stream(&request(&messages, &tools), &mut sink).await.unwrap();
+
+        let (thinking, tokens) = drain_events(&mut rx);
+        assert_eq!(thinking, "let me see");
+        assert_eq!(tokens, "Reading it.");
+        assert_eq!(sink.tool_calls.len(), 1);
+        assert_eq!(sink.tool_calls[0].id, "toolu_1");
+        assert_eq!(sink.tool_calls[0].arguments, json!({ "path": "a.rs" }));
+
+        let body: serde_json::Value = serde_json::from_str(&server.await.unwrap()).unwrap();
+        assert_eq!(body["system"], "system prompt");
+        assert_eq!(body["max_tokens"], 1024);
+        assert_eq!(body["messages"][2]["content"][0]["type"], "tool_result");
+        assert_eq!(body["messages"][2]["content"][0]["tool_use_id"], "toolu_0");
+    }
+
+    #[tokio::test]
+    async fn error_status_and_error_events_fail_the_turn() {
+        let (url, _server) = serve(400, &[b"{\"error\":{\"message\":\"bad tools\"}}"]).await;
+        let provider = AnthropicProvider::new(url, None, 1024);
+        let err = provider
+            .stream(&request(&[], &[tool("read_file")]), &mut TurnSink::silent())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("bad tools"));
+
+        let (url, _server) = serve(
+            200,
+            &[b"data: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\"}}\n\n"],
+        )
+        .await;
+        let provider = AnthropicProvider::new(url, None, 1024);
+        let err = provider
+            .stream(&request(&[], &[]), &mut TurnSink::silent())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("overloaded_error"));
+    }
+}
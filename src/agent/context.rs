@@ -0,0 +1,333 @@
+use super::provider::{ChatProvider, ChatRequest, ProviderMessage, ProviderRole, TurnSink};
+use crate::app::{ChatMessage, MessageRole};
+use crate::mcp::ToolDefinition;
+use anyhow::{anyhow, Result};
+
+// Context window management: token estimation and summarization of older turns.
+
+/// Compact once the estimated prompt passes this share of the window,
+/// leaving headroom for the reply and the tool results of the current turn.
+const COMPACT_AT: f32 = 0.75;
+/// Share of the window kept verbatim (most recent turns) when compacting.
+const KEEP_RECENT: f32 = 0.3;
+/// Tool output is clipped in the summarization transcript.
+const TRANSCRIPT_TOOL_CHARS: usize = 2000;
+
+pub const SUMMARY_PREFIX: &str = "Summary of earlier conversation:";
+
+const SUMMARY_SYSTEM_PROMPT: &str = r#"
+You compress conversations between a user and a software development agent.
+Write a concise summary that preserves:
+- The user's goals and any open tasks.
+- Decisions made and constraints stated.
+- File paths, commands run and their outcomes (especially errors).
+- Facts about the codebase the agent learned.
+Reply with the summary only, as a short bullet list.
+"#;
+
+/// Rough token estimate (~4 characters per token plus per-message overhead).
+/// Deliberately provider-agnostic; it only needs to be in the right ballpark.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+pub fn estimate_message(msg: &ChatMessage) -> usize {
+    if msg.ui_only {
+        return 0;
+    }
+    let calls: usize = msg
+        .tool_calls
+        .iter()
+        .map(|c| estimate_tokens(&c.name) + estimate_tokens(&c.arguments.to_string()))
+        .sum();
+    4 + estimate_tokens(&msg.content) + calls
+}
+
+pub fn estimate_prompt(system: &str, tools: &[ToolDefinition], history: &[ChatMessage]) -> usize {
+    let tools: usize = tools
+        .iter()
+        .map(|t| {
+            estimate_tokens(&t.name)
+                + estimate_tokens(&t.description)
+                + estimate_tokens(&t.input_schema.to_string())
+        })
+        .sum();
+    estimate_tokens(system) + tools + history.iter().map(estimate_message).sum::<usize>()
+}
+
+pub fn needs_compaction(prompt_tokens: usize, window: usize) -> bool {
+    prompt_tokens as f32 > window as f32 * COMPACT_AT
+}
+
+/// Summarizes everything except pinned messages and the most recent turns.
+/// Returns `None` when there is nothing old enough to compact.
+pub async fn compact_history(
+    provider: &dyn ChatProvider,
+    model: &str,
+    history: &[ChatMessage],
+    window: usize,
+) -> Result<Option<Vec<ChatMessage>>> {
+    let Some(cut) = find_cut(history, window) else {
+        return Ok(None);
+    };
+
+    let (old, recent) = history.split_at(cut);
+    let to_summarize: Vec<&ChatMessage> = old.iter().filter(|m| !m.pinned && !m.ui_only).collect();
+    // Re-summarizing a lone summary gains nothing
+    if to_summarize.iter().all(|m| m.content.starts_with(SUMMARY_PREFIX)) {
+        return Ok(None);
+    }
+
+    let transcript = render_transcript(&to_summarize);
+    let messages = [ProviderMessage::text(ProviderRole::User, transcript)];
+    let request = ChatRequest {
+        model,
+        system: SUMMARY_SYSTEM_PROMPT,
+        messages: &messages,
+        tools: &[],
+        context_window: window,
+    };
+    let mut sink = TurnSink::silent();
+    provider.stream_chat(&request, &mut sink).await?;
+
+    let summary = sink.content.trim();
+    if summary.is_empty() {
+        return Err(anyhow!("Model returned an empty summary"));
+    }
+
+    let mut compacted: Vec<ChatMessage> = old.iter().filter(|m| m.pinned).cloned().collect();
+    compacted.push(ChatMessage::new(
+        MessageRole::System,
+        format!("{}\n{}", SUMMARY_PREFIX, summary),
+    ));
+    compacted.extend_from_slice(recent);
+    Ok(Some(compacted))
+}
+
+/// Index of the first message kept verbatim. Always lands on a user message,
+/// so an assistant tool call is never separated from its results.
+fn find_cut(history: &[ChatMessage], window: usize) -> Option<usize> {
+    let keep_budget = (window as f32 * KEEP_RECENT) as usize;
+    let mut kept = 0;
+    let mut cut = None;
+
+    for (i, msg) in history.iter().enumerate().rev() {
+        if !msg.pinned {
+            kept += estimate_message(msg);
+        }
+        if matches!(msg.role, MessageRole::User) {
+            cut = Some(i);
+            if kept >= keep_budget {
+                break;
+            }
+        }
+    }
+
+    // Keep at least the last user turn
+    cut.filter(|&i| i > 0)
+}
+
+fn render_transcript(messages: &[&ChatMessage]) -> String {
+    let mut out = String::from("Summarize this conversation:\n\n");
+    for msg in messages {
+        let label = match msg.role {
+            MessageRole::User => "user".to_string(),
+            MessageRole::Assistant => "assistant".to_string(),
+            MessageRole::Thinking => continue,
+            MessageRole::System => "system".to_string(),
+            MessageRole::Error => "error".to_string(),
+            MessageRole::Tool => format!("tool {}", msg.tool_name.as_deref().unwrap_or("")),
+        };
+        let content: String = if matches!(msg.role, MessageRole::Tool)
+            && msg.content.chars().count() > TRANSCRIPT_TOOL_CHARS
+        {
+            let clipped: String = msg.content.chars().take(TRANSCRIPT_TOOL_CHARS).collect();
+            format!("{}\n...[truncated]", clipped)
+        } else {
+            msg.content.clone()
+        };
+        out.push_str(&format!("[{}]: {}\n", label, content));
+        for call in &msg.tool_calls {
+            out.push_str(&format!("[assistant called {}]: {}\n", call.name, call.arguments));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::provider::ToolCall;
+    use futures_util::future::BoxFuture;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    fn msg(role: MessageRole, chars: usize) -> ChatMessage {
+        ChatMessage::new(role, "x".repeat(chars))
+    }
+
+    fn with_call(mut msg: ChatMessage, id: &str) -> ChatMessage {
+        msg.tool_calls.push(ToolCall {
+            id: id.into(),
+            name: "read_file".into(),
+            arguments: json!({ "path": "a.rs" }),
+        });
+        msg
+    }
+
+    fn result(id: &str, chars: usize) -> ChatMessage {
+        let mut msg = msg(MessageRole::Tool, chars);
+        msg.tool_call_id = Some(id.into());
+        msg
+    }
+
+    /// Answers every request with a fixed summary and keeps the transcript it was sent.
+    struct FakeSummarizer {
+        transcript: Mutex<String>,
+    }
+
+    impl ChatProvider for FakeSummarizer {
+        fn stream_chat<'a>(
+            &'a self,
+            request: &'a ChatRequest<'a>,
+            sink: &'a mut TurnSink,
+        ) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async move {
+                *self.transcript.lock().unwrap() = request.messages[0].content.clone();
+                sink.content("- the user wants a parser").await
+            })
+        }
+
+        fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+    }
+
+    #[test]
+    fn estimates_four_chars_per_token() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+        // Characters, not bytes
+        assert_eq!(estimate_tokens("ééééé"), 2);
+    }
+
+    #[test]
+    fn estimates_messages_tools_and_system_prompt() {
+        let plain = msg(MessageRole::User, 40);
+        assert_eq!(estimate_message(&plain), 4 + 10);
+
+        // "read_file" is 3 tokens, {"path":"a.rs"} is 4
+        let call = with_call(msg(MessageRole::Assistant, 0), "call_0");
+        assert_eq!(estimate_message(&call), 4 + 3 + 4);
+
+        let notice = ChatMessage {
+            ui_only: true,
+            ..msg(MessageRole::System, 400)
+        };
+        assert_eq!(estimate_message(&notice), 0);
+
+        let tool = ToolDefinition {
+            name: "grep".into(),
+            description: "x".repeat(8),
+            input_schema: json!({}),
+        };
+        let history = [plain, notice];
+        assert_eq!(estimate_prompt(&"x".repeat(40), &[tool], &history), 10 + (1 + 2 + 1) + 14);
+    }
+
+    #[test]
+    fn cut_never_separates_tool_calls_from_their_results() {
+        let history = [
+            msg(MessageRole::User, 100),
+            with_call(msg(MessageRole::Assistant, 400), "call_0"),
+            result("call_0", 800),
+            msg(MessageRole::Thinking, 200),
+            with_call(msg(MessageRole::Assistant, 400), "call_1"),
+            result("call_1", 800),
+            msg(MessageRole::User, 100),
+            with_call(msg(MessageRole::Assistant, 400), "call_0"),
+            result("call_0", 1200),
+            msg(MessageRole::Assistant, 400),
+            msg(MessageRole::User, 400),
+            msg(MessageRole::Assistant, 40),
+        ];
+
+        let mut cuts = Vec::new();
+        for window in [100, 500, 1000, 2000, 4000, 16000] {
+            let Some(cut) = find_cut(&history, window) else {
+                continue;
+            };
+            cuts.push(cut);
+            assert!(matches!(history[cut].role, MessageRole::User));
+            for (i, msg) in history.iter().enumerate().skip(cut) {
+                if let Some(id) = &msg.tool_call_id {
+                    let call = history[..i]
+                        .iter()
+                        .rposition(|m| m.tool_calls.iter().any(|c| &c.id == id))
+                        .unwrap();
+                    assert!(call >= cut, "result {} kept without its call", i);
+                }
+            }
+        }
+        // Small windows keep only the last turn, larger ones keep more
+        assert_eq!(cuts.first(), Some(&10));
+        assert!(cuts.contains(&6));
+        // Everything fits the recent share: nothing to compact
+        assert_eq!(find_cut(&history, 16000), None);
+    }
+
+    #[test]
+    fn pinned_messages_do_not_use_up_the_recent_budget() {
+        let mut history = vec![
+            msg(MessageRole::User, 20),
+            msg(MessageRole::Assistant, 1200),
+            msg(MessageRole::User, 20),
+            msg(MessageRole::System, 4000),
+            msg(MessageRole::User, 20),
+            msg(MessageRole::Assistant, 40),
+        ];
+        assert_eq!(find_cut(&history, 1000), Some(2));
+
+        history[3].pinned = true;
+        assert_eq!(find_cut(&history, 1000), None);
+    }
+
+    #[tokio::test]
+    async fn compaction_keeps_pinned_context_and_drops_notices() {
+        let mut pinned = ChatMessage::new(MessageRole::System, "File context loaded: a.rs".into());
+        pinned.pinned = true;
+        let notice = ChatMessage {
+            ui_only: true,
+            ..ChatMessage::new(MessageRole::System, "Allowed run_command".into())
+        };
+        let history = [
+            pinned,
+            ChatMessage::new(MessageRole::User, "write a parser ".repeat(80)),
+            msg(MessageRole::Assistant, 1200),
+            notice,
+            msg(MessageRole::User, 1200),
+            msg(MessageRole::Assistant, 40),
+        ];
+        let provider = FakeSummarizer {
+            transcript: Mutex::new(String::new()),
+        };
+
+        let compacted = compact_history(&provider, "m", &history, 1000).await.unwrap().unwrap();
+
+        assert_eq!(compacted.len(), 4);
+        assert!(compacted[0].pinned);
+        assert_eq!(compacted[0].content, "File context loaded: a.rs");
+        assert!(compacted[1].content.starts_with(SUMMARY_PREFIX));
+        assert!(compacted[1].content.ends_with("- the user wants a parser"));
+        assert_eq!(compacted[2].content, history[4].content);
+
+        let transcript = provider.transcript.lock().unwrap().clone();
+        assert!(transcript.contains("[user]: write a parser"));
+        assert!(!transcript.contains("File context loaded"));
+        assert!(!transcript.contains("Allowed run_command"));
+
+        // A second pass would only summarize the summary
+        assert!(compact_history(&provider, "m", &compacted, 1000).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,97 @@
+use crate::app::AppEvent;
+use crate::mcp::ToolDefinition;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+// A one-shot HTTP server for the backend tests. The body goes out as one HTTP
+// chunk per slice, flushed and spaced apart, so the client reads it in those
+// pieces and the stream parsers have to stitch frames back together.
+
+/// Serves a single request with `status` and `chunks` as the body. Returns the
+/// base URL and a handle that yields the request body the client sent.
+pub async fn serve(status: u16, chunks: &[&[u8]]) -> (String, JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let chunks: Vec<Vec<u8>> = chunks.iter().map(|c| c.to_vec()).collect();
+
+    let handle = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let body = read_request(&mut socket).await;
+
+        let head = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Type: text/plain\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n",
+            status
+        );
+        socket.write_all(head.as_bytes()).await.unwrap();
+        for chunk in chunks {
+            socket
+                .write_all(format!("{:x}\r\n", chunk.len()).as_bytes())
+                .await
+                .unwrap();
+            socket.write_all(&chunk).await.unwrap();
+            socket.write_all(b"\r\n").await.unwrap();
+            socket.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        socket.write_all(b"0\r\n\r\n").await.unwrap();
+        socket.flush().await.unwrap();
+        body
+    });
+
+    (url, handle)
+}
+
+/// Reads the request head and its `Content-Length` body.
+async fn read_request(socket: &mut tokio::net::TcpStream) -> String {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+    let head_end = loop {
+        let n = socket.read(&mut buf).await.unwrap();
+        assert!(n > 0, "client closed before sending a request");
+        data.extend_from_slice(&buf[..n]);
+        if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&data[..head_end]).to_lowercase();
+    let length: usize = head
+        .lines()
+        .find_map(|l| l.strip_prefix("content-length:"))
+        .map(|v| v.trim().parse().unwrap())
+        .unwrap_or(0);
+    while data.len() < head_end + length {
+        let n = socket.read(&mut buf).await.unwrap();
+        if n == 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..n]);
+    }
+    String::from_utf8_lossy(&data[head_end..]).into_owned()
+}
+
+/// Everything a `TurnSink` forwarded, as (thinking, visible tokens).
+pub fn drain_events(rx: &mut mpsc::Receiver<AppEvent>) -> (String, String) {
+    let mut thinking = String::new();
+    let mut tokens = String::new();
+    while let Ok(event) = rx.try_recv() {
+        match event {
+            AppEvent::Thinking(t) => thinking.push_str(&t),
+            AppEvent::Token(t) => tokens.push_str(&t),
+            _ => {}
+        }
+    }
+    (thinking, tokens)
+}
+
+/// A tool definition with an empty schema, for requests that offer tools.
+pub fn tool(name: &str) -> ToolDefinition {
+    ToolDefinition {
+        name: name.to_string(),
+        description: String::new(),
+        input_schema: serde_json::json!({ "type": "object" }),
+    }
+}
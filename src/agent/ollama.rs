@@ -0,0 +1,288 @@
+use super::provider::{
+    drain_lines, rejects_tools, ChatProvider, ChatRequest, ProviderMessage, ProviderRole,
+    ToolCall, ToolsRejected, TurnSink,
+};
+use anyhow::{anyhow, Result};
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+// Ollama native `/api/chat` (NDJSON streaming)
+
+#[derive(Deserialize, Debug)]
+struct ChatResponse {
+    message: Option<Message>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Message {
+    content: Option<String>,
+    thinking: Option<String>,
+    reasoning_content: Option<String>,
+    tool_calls: Option<Vec<OllamaToolCall>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OllamaToolCall {
+    function: OllamaFunction,
+}
+
+#[derive(Deserialize, Debug)]
+struct OllamaFunction {
+    name: String,
+    arguments: serde_json::Value,
+}
+
+pub struct OllamaProvider {
+    client: Client,
+    url: String,
+}
+
+impl OllamaProvider {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: Client::new(),
+            url,
+        }
+    }
+
+    fn message_json(msg: &ProviderMessage) -> serde_json::Value {
+        match msg.role {
+            ProviderRole::System => json!({ "role": "system", "content": msg.content }),
+            ProviderRole::User => json!({ "role": "user", "content": msg.content }),
+            ProviderRole::Assistant => {
+                if msg.tool_calls.is_empty() {
+                    json!({ "role": "assistant", "content": msg.content })
+                } else {
+                    let calls: Vec<serde_json::Value> = msg
+                        .tool_calls
+                        .iter()
+                        .map(|c| json!({ "function": { "name": c.name, "arguments": c.arguments } }))
+                        .collect();
+                    json!({ "role": "assistant", "content": msg.content, "tool_calls": calls })
+                }
+            }
+            ProviderRole::Tool => json!({
+                "role": "tool",
+                "content": msg.content,
+                "tool_name": msg.tool_name,
+            }),
+        }
+    }
+
+    async fn stream(&self, request: &ChatRequest<'_>, sink: &mut TurnSink) -> Result<()> {
+        let mut messages = vec![json!({ "role": "system", "content": request.system })];
+        messages.extend(request.messages.iter().map(Self::message_json));
+
+        // Without an explicit num_ctx Ollama silently truncates to its default window
+        let mut body = json!({
+            "model": request.model,
+            "messages": messages,
+            "stream": true,
+            "options": { "num_ctx": request.context_window }
+        });
+        if !request.tools.is_empty() {
+            let tools: Vec<serde_json::Value> = request
+                .tools
+                .iter()
+                .map(|t| {
+                    json!({
+                        "type": "function",
+                        "function": {
+                            "name": t.name,
+                            "description": t.description,
+                            "parameters": t.input_schema
+                        }
+                    })
+                })
+                .collect();
+            body["tools"] = json!(tools);
+        }
+
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Ollama Connection Error: {}", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            if status == reqwest::StatusCode::BAD_REQUEST && !request.tools.is_empty() && rejects_tools(&text) {
+                return Err(ToolsRejected.into());
+            }
+            return Err(anyhow!("Ollama API Error: {}", text));
+        }
+
+        let mut stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| anyhow!("Stream Error: {}", e))?;
+            buffer.extend_from_slice(&chunk);
+
+            for line in drain_lines(&mut buffer) {
+                let Ok(resp) = serde_json::from_str::<ChatResponse>(&line) else {
+                    continue;
+                };
+                if let Some(err) = resp.error {
+                    return Err(anyhow!("Ollama Error: {}", err));
+                }
+                let Some(msg) = resp.message else {
+                    continue;
+                };
+
+                if let Some(think) = msg.thinking.or(msg.reasoning_content) {
+                    sink.reasoning(&think).await?;
+                }
+                if let Some(content) = msg.content {
+                    sink.content(&content).await?;
+                }
+                for call in msg.tool_calls.unwrap_or_default() {
+                    // Ollama does not assign call ids, so number them per turn
+                    let id = format!("call_{}", sink.tool_calls.len());
+                    sink.tool_call(ToolCall {
+                        id,
+                        name: call.function.name,
+                        arguments: call.function.arguments,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn models(&self) -> Result<Vec<String>> {
+        let url = self.url.replace("/api/chat", "/api/tags");
+        let json: serde_json::Value = self.client.get(&url).send().await?.json().await?;
+        let names = json
+            .get("models")
+            .and_then(|v| v.as_array())
+            .map(|models| {
+                models
+                    .iter()
+                    .filter_map(|m| m.get("name").and_then(|n| n.as_str()))
+                    .map(|s| s.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(names)
+    }
+}
+
+impl ChatProvider for OllamaProvider {
+    fn stream_chat<'a>(
+        &'a self,
+        request: &'a ChatRequest<'a>,
+        sink: &'a mut TurnSink,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.stream(request, sink))
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(self.models())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::mock_http::{drain_events, serve, tool};
+    use crate::mcp::ToolDefinition;
+    use tokio::sync::mpsc;
+
+    fn request<'a>(messages: &'a [ProviderMessage], tools: &'a [ToolDefinition]) -> ChatRequest<'a> {
+        ChatRequest {
+            model: "qwen3:8b",
+            system: "system prompt",
+            messages,
+            tools,
+            context_window: 8192,
+        }
+    }
+
+    #[tokio::test]
+    async fn stitches_lines_split_across_chunks() {
+        let (url, server) = serve(
+            200,
+            &[
+                b"{\"message\":{\"thinking\":\"hm",
+                b"m\"}}\n{\"message\":{\"content\":\"caf\xc3",
+                b"\xa9 \"}}\n{\"message\":{\"reasoning_content\":\"!\",\"content\":\"<think>inline</think>ok\"}}\n",
+                b"{\"message\":{\"tool_calls\":[{\"function\":{\"name\":\"read_file\",\"arguments\":{\"path\":\"a.rs\"}}}]}}\n{\"done\":true}\n",
+            ],
+        )
+        .await;
+        let provider = OllamaProvider::new(format!("{}/api/chat", url));
+        let (tx, mut rx) = mpsc::channel(100);
+        let mut sink = TurnSink::new(tx);
+        let messages = [ProviderMessage::text(ProviderRole::User, "hi")];
+        let tools = [tool("read_file")];
+
+        provider.stream(&request(&messages, &tools), &mut sink).await.unwrap();
+
+        let (thinking, tokens) = drain_events(&mut rx);
+        assert_eq!(thinking, "hmm!inline");
+        assert_eq!(tokens, "café ok");
+        assert_eq!(sink.content, "café ok");
+        assert_eq!(sink.tool_calls.len(), 1);
+        assert_eq!(sink.tool_calls[0].name, "read_file");
+        assert_eq!(sink.tool_calls[0].arguments, json!({ "path": "a.rs" }));
+
+        let body: serde_json::Value = serde_json::from_str(&server.await.unwrap()).unwrap();
+        assert_eq!(body["options"]["num_ctx"], 8192);
+        assert_eq!(body["tools"][0]["function"]["name"], "read_file");
+        assert_eq!(body["messages"][0]["content"], "system prompt");
+    }
+
+    #[tokio::test]
+    async fn bad_request_with_tools_is_tools_rejected() {
+        let (url, _server) = serve(400, &[b"{\"error\":\"model does not support tools\"}"]).await;
+        let provider = OllamaProvider::new(url);
+        let mut sink = TurnSink::silent();
+        let tools = [tool("read_file")];
+
+        let err = provider.stream(&request(&[], &tools), &mut sink).await.unwrap_err();
+        assert!(err.is::<ToolsRejected>());
+    }
+
+    #[tokio::test]
+    async fn bad_request_without_tools_is_an_api_error() {
+        let (url, _server) = serve(400, &[b"{\"error\":\"bad model\"}"]).await;
+        let provider = OllamaProvider::new(url);
+        let mut sink = TurnSink::silent();
+
+        let err = provider.stream(&request(&[], &[]), &mut sink).await.unwrap_err();
+        assert!(!err.is::<ToolsRejected>());
+        assert!(err.to_string().contains("bad model"));
+    }
+
+    #[tokio::test]
+    async fn unrelated_bad_request_with_tools_is_an_api_error() {
+        let (url, _server) = serve(400, &[b"{\"error\":\"model \\\"qwen9\\\" not found\"}"]).await;
+        let provider = OllamaProvider::new(url);
+        let mut sink = TurnSink::silent();
+        let tools = [tool("read_file")];
+
+        let err = provider.stream(&request(&[], &tools), &mut sink).await.unwrap_err();
+        assert!(!err.is::<ToolsRejected>());
+        assert!(err.to_string().contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn error_line_in_stream_fails_the_turn() {
+        let (url, _server) = serve(200, &[b"{\"message\":{\"content\":\"a\"}}\n{\"error\":\"out of memory\"}\n"]).await;
+        let provider = OllamaProvider::new(url);
+        let mut sink = TurnSink::silent();
+
+        let err = provider.stream(&request(&[], &[]), &mut sink).await.unwrap_err();
+        assert!(err.to_string().contains("out of memory"));
+    }
+}
@@ -0,0 +1,348 @@
+use super::provider::{
+    drain_lines, parse_arguments, rejects_tools, ChatProvider, ChatRequest, ProviderMessage,
+    ProviderRole, ToolCall, ToolsRejected, TurnSink,
+};
+use anyhow::{anyhow, Result};
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+// OpenAI-compatible `/v1/chat/completions` (SSE streaming).
+// Covers llama.cpp server, vLLM, LM Studio and OpenAI itself.
+
+#[derive(Deserialize, Debug)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<Choice>,
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Choice {
+    delta: Option<Delta>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Delta {
+    content: Option<String>,
+    // vLLM / llama.cpp use `reasoning_content`, OpenRouter-style servers use `reasoning`
+    reasoning_content: Option<String>,
+    reasoning: Option<String>,
+    tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ToolCallDelta {
+    #[serde(default)]
+    index: usize,
+    id: Option<String>,
+    function: Option<FunctionDelta>,
+}
+
+#[derive(Deserialize, Debug)]
+struct FunctionDelta {
+    name: Option<String>,
+    arguments: Option<String>,
+}
+
+#[derive(Default)]
+struct PartialCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+pub struct OpenAiProvider {
+    client: Client,
+    url: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiProvider {
+    pub fn new(url: String, api_key: Option<String>) -> Self {
+        Self {
+            client: Client::new(),
+            url,
+            api_key,
+        }
+    }
+
+    fn authorize(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.api_key {
+            Some(key) => req.bearer_auth(key),
+            None => req,
+        }
+    }
+
+    fn message_json(msg: &ProviderMessage) -> serde_json::Value {
+        match msg.role {
+            ProviderRole::System => json!({ "role": "system", "content": msg.content }),
+            ProviderRole::User => json!({ "role": "user", "content": msg.content }),
+            ProviderRole::Assistant => {
+                if msg.tool_calls.is_empty() {
+                    json!({ "role": "assistant", "content": msg.content })
+                } else {
+                    let calls: Vec<serde_json::Value> = msg
+                        .tool_calls
+                        .iter()
+                        .map(|c| {
+                            json!({
+                                "id": c.id,
+                                "type": "function",
+                                "function": {
+                                    "name": c.name,
+                                    "arguments": c.arguments.to_string()
+                                }
+                            })
+                        })
+                        .collect();
+                    json!({ "role": "assistant", "content": msg.content, "tool_calls": calls })
+                }
+            }
+            ProviderRole::Tool => json!({
+                "role": "tool",
+                "tool_call_id": msg.tool_call_id,
+                "content": msg.content,
+            }),
+        }
+    }
+
+    async fn stream(&self, request: &ChatRequest<'_>, sink: &mut TurnSink) -> Result<()> {
+        let mut messages = vec![json!({ "role": "system", "content": request.system })];
+        messages.extend(request.messages.iter().map(Self::message_json));
+
+        let mut body = json!({
+            "model": request.model,
+            "messages": messages,
+            "stream": true
+        });
+        if !request.tools.is_empty() {
+            let tools: Vec<serde_json::Value> = request
+                .tools
+                .iter()
+                .map(|t| {
+                    json!({
+                        "type": "function",
+                        "function": {
+                            "name": t.name,
+                            "description": t.description,
+                            "parameters": t.input_schema
+                        }
+                    })
+                })
+                .collect();
+            body["tools"] = json!(tools);
+        }
+
+        let response = self
+            .authorize(self.client.post(&self.url))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| anyhow!("OpenAI Connection Error: {}", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            if status == reqwest::StatusCode::BAD_REQUEST && !request.tools.is_empty() && rejects_tools(&text) {
+                return Err(ToolsRejected.into());
+            }
+            return Err(anyhow!("OpenAI API Error: {}", text));
+        }
+
+        let mut stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        // Tool calls arrive in fragments keyed by `index`
+        let mut partial_calls: BTreeMap<usize, PartialCall> = BTreeMap::new();
+
+        'outer: while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| anyhow!("Stream Error: {}", e))?;
+            buffer.extend_from_slice(&chunk);
+
+            for line in drain_lines(&mut buffer) {
+                let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                    continue;
+                };
+                if data == "[DONE]" {
+                    break 'outer;
+                }
+                let Ok(chunk) = serde_json::from_str::<StreamChunk>(data) else {
+                    continue;
+                };
+                if let Some(err) = chunk.error {
+                    return Err(anyhow!("OpenAI Error: {}", err));
+                }
+
+                for delta in chunk.choices.into_iter().filter_map(|c| c.delta) {
+                    if let Some(reason) = delta.reasoning_content.or(delta.reasoning) {
+                        sink.reasoning(&reason).await?;
+                    }
+                    if let Some(content) = delta.content {
+                        sink.content(&content).await?;
+                    }
+                    for call in delta.tool_calls.unwrap_or_default() {
+                        let entry = partial_calls.entry(call.index).or_default();
+                        if let Some(id) = call.id {
+                            entry.id = id;
+                        }
+                        if let Some(function) = call.function {
+                            if let Some(name) = function.name {
+                                entry.name.push_str(&name);
+                            }
+                            if let Some(args) = function.arguments {
+                                entry.arguments.push_str(&args);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for (index, call) in partial_calls {
+            let id = if call.id.is_empty() {
+                format!("call_{}", index)
+            } else {
+                call.id
+            };
+            sink.tool_call(ToolCall {
+                id,
+                name: call.name,
+                arguments: parse_arguments(&call.arguments),
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn models(&self) -> Result<Vec<String>> {
+        let url = self.url.replace("/chat/completions", "/models");
+        let json: serde_json::Value = self
+            .authorize(self.client.get(&url))
+            .send()
+            .await?
+            .json()
+            .await?;
+        let names = json
+            .get("data")
+            .and_then(|v| v.as_array())
+            .map(|models| {
+                models
+                    .iter()
+                    .filter_map(|m| m.get("id").and_then(|n| n.as_str()))
+                    .map(|s| s.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(names)
+    }
+}
+
+impl ChatProvider for OpenAiProvider {
+    fn stream_chat<'a>(
+        &'a self,
+        request: &'a ChatRequest<'a>,
+        sink: &'a mut TurnSink,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.stream(request, sink))
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(self.models())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::mock_http::{drain_events, serve, tool};
+    use crate::mcp::ToolDefinition;
+    use tokio::sync::mpsc;
+
+    fn request<'a>(messages: &'a [ProviderMessage], tools: &'a [ToolDefinition]) -> ChatRequest<'a> {
+        ChatRequest {
+            model: "gpt",
+            system: "system prompt",
+            messages,
+            tools,
+            context_window: 8192,
+        }
+    }
+
+    #[tokio::test]
+    async fn stitches_sse_frames_and_tool_call_fragments() {
+        let (url, server) = serve(
+            200,
+            &[
+                b": keep-alive\n\ndata: {\"choices\":[{\"delta\":{\"reasoning_content\":\"th",
+                b"ink\"}}]}\n\ndata: {\"choices\":[{\"delta\":{\"reasoning\":\"ing\"}}]}\n\n",
+                b"data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"lo\"}}]}\n\n",
+                b"data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_abc\",\"function\":{\"name\":\"edit_file\",\"arguments\":\"{\\\"pa\"}}]}}]}\n\n",
+                b"data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":1,\"function\":{\"name\":\"list_files\",\"arguments\":\"\"}}]}}]}\n\n",
+                b"data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"th\\\": \\\"src/main.rs\\\"}\"}}]}}]}\n\n",
+                b"data: [DONE]\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"after done\"}}]}\n\n",
+            ],
+        )
+        .await;
+        let provider = OpenAiProvider::new(format!("{}/v1/chat/completions", url), Some("key".into()));
+        let (tx, mut rx) = mpsc::channel(100);
+        let mut sink = TurnSink::new(tx);
+        let messages = [ProviderMessage::text(ProviderRole::User, "hi")];
+        let tools = [tool("edit_file"), tool("list_files")];
+
+        provider.stream(&request(&messages, &tools), &mut sink).await.unwrap();
+
+        let (thinking, tokens) = drain_events(&mut rx);
+        assert_eq!(thinking, "thinking");
+        assert_eq!(tokens, "Hello");
+        assert_eq!(sink.tool_calls.len(), 2);
+        assert_eq!(sink.tool_calls[0].id, "call_abc");
+        assert_eq!(sink.tool_calls[0].name, "edit_file");
+        assert_eq!(sink.tool_calls[0].arguments, json!({ "path": "src/main.rs" }));
+        assert_eq!(sink.tool_calls[1].name, "list_files");
+        assert_eq!(sink.tool_calls[1].arguments, json!({}));
+
+        let body: serde_json::Value = serde_json::from_str(&server.await.unwrap()).unwrap();
+        assert_eq!(body["tools"][0]["function"]["name"], "edit_file");
+        assert_eq!(body["messages"][1]["role"], "user");
+    }
+
+    #[tokio::test]
+    async fn bad_request_with_tools_is_tools_rejected() {
+        let (url, _server) = serve(400, &[b"{\"error\":{\"message\":\"tools are not supported\"}}"]).await;
+        let provider = OpenAiProvider::new(url, None);
+        let mut sink = TurnSink::silent();
+        let tools = [tool("read_file")];
+
+        let err = provider.stream(&request(&[], &tools), &mut sink).await.unwrap_err();
+        assert!(err.is::<ToolsRejected>());
+    }
+
+    #[tokio::test]
+    async fn other_bad_requests_with_tools_are_api_errors() {
+        let (url, _server) = serve(
+            400,
+            &[b"{\"error\":{\"message\":\"This model's maximum context length is 8192 tokens\"}}"],
+        )
+        .await;
+        let provider = OpenAiProvider::new(url, None);
+        let mut sink = TurnSink::silent();
+        let tools = [tool("read_file")];
+
+        let err = provider.stream(&request(&[], &tools), &mut sink).await.unwrap_err();
+        assert!(!err.is::<ToolsRejected>());
+        assert!(err.to_string().contains("maximum context length"));
+    }
+
+    #[tokio::test]
+    async fn error_frame_fails_the_turn() {
+        let (url, _server) = serve(200, &[b"data: {\"error\":{\"message\":\"overloaded\"}}\n\n"]).await;
+        let provider = OpenAiProvider::new(url, None);
+        let mut sink = TurnSink::silent();
+
+        let err = provider.stream(&request(&[], &[]), &mut sink).await.unwrap_err();
+        assert!(err.to_string().contains("overloaded"));
+    }
+}
@@ -0,0 +1,217 @@
+use crate::app::AppEvent;
+use crate::config::{Config, ProviderKind};
+use crate::mcp::ToolDefinition;
+use anyhow::Result;
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use super::anthropic::AnthropicProvider;
+use super::ollama::OllamaProvider;
+use super::openai::OpenAiProvider;
+
+// --- Provider-neutral conversation types ---
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProviderRole {
+    System,
+    User,
+    Assistant,
+    Tool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProviderMessage {
+    pub role: ProviderRole,
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    // Only set on `Tool` messages
+    pub tool_call_id: Option<String>,
+    pub tool_name: Option<String>,
+}
+
+impl ProviderMessage {
+    pub fn text(role: ProviderRole, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+            tool_name: None,
+        }
+    }
+
+    pub fn tool_result(call: &ToolCall, content: String) -> Self {
+        Self {
+            role: ProviderRole::Tool,
+            content,
+            tool_calls: Vec::new(),
+            tool_call_id: Some(call.id.clone()),
+            tool_name: Some(call.name.clone()),
+        }
+    }
+}
+
+pub struct ChatRequest<'a> {
+    pub model: &'a str,
+    pub system: &'a str,
+    pub messages: &'a [ProviderMessage],
+    pub tools: &'a [ToolDefinition],
+    /// Context window in tokens, for backends that need it spelled out (Ollama's `num_ctx`)
+    pub context_window: usize,
+}
+
+/// Returned (wrapped in `anyhow::Error`) when a backend refuses the `tools` field,
+/// so the agent loop can retry the same turn without tools.
+#[derive(Debug)]
+pub struct ToolsRejected;
+
+impl std::fmt::Display for ToolsRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "model rejected tool definitions")
+    }
+}
+
+impl std::error::Error for ToolsRejected {}
+
+/// Whether a 400 response body blames the `tools` field. Other 400s (context
+/// overflow, unknown model, ...) are real errors and must not be retried.
+pub fn rejects_tools(body: &str) -> bool {
+    let body = body.to_lowercase();
+    body.contains("tool") || body.contains("function")
+}
+
+pub trait ChatProvider: Send + Sync {
+    /// Streams one assistant turn into `sink`. Content and reasoning deltas are
+    /// forwarded as they arrive, tool calls are collected for the agent loop.
+    fn stream_chat<'a>(
+        &'a self,
+        request: &'a ChatRequest<'a>,
+        sink: &'a mut TurnSink,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>>;
+}
+
+pub fn build_provider(config: &Config) -> Box<dyn ChatProvider> {
+    let url = config.provider_url();
+    let api_key = config.provider_api_key();
+    match config.provider {
+        ProviderKind::Ollama => Box::new(OllamaProvider::new(url)),
+        ProviderKind::OpenAi => Box::new(OpenAiProvider::new(url, api_key)),
+        ProviderKind::Anthropic => {
+            Box::new(AnthropicProvider::new(url, api_key, config.max_tokens))
+        }
+    }
+}
+
+// --- Stream handling shared by every backend ---
+
+pub struct TurnSink {
+    app_tx: Option<mpsc::Sender<AppEvent>>,
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    in_think: bool,
+}
+
+impl TurnSink {
+    pub fn new(app_tx: mpsc::Sender<AppEvent>) -> Self {
+        Self {
+            app_tx: Some(app_tx),
+            content: String::new(),
+            tool_calls: Vec::new(),
+            in_think: false,
+        }
+    }
+
+    /// Collects the turn without forwarding anything to the UI (e.g. summarization).
+    pub fn silent() -> Self {
+        Self {
+            app_tx: None,
+            content: String::new(),
+            tool_calls: Vec::new(),
+            in_think: false,
+        }
+    }
+
+    /// Visible content delta. Inline `<think>` blocks are routed to the Thinking view.
+    pub async fn content(&mut self, text: &str) -> Result<()> {
+        let mut rest = text;
+        while !rest.is_empty() {
+            let tag = if self.in_think { "</think>" } else { "<think>" };
+            match rest.find(tag) {
+                Some(pos) => {
+                    self.emit(&rest[..pos]).await?;
+                    self.in_think = !self.in_think;
+                    rest = &rest[pos + tag.len()..];
+                }
+                None => {
+                    self.emit(rest).await?;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Dedicated reasoning delta (`thinking`, `reasoning_content`, thinking blocks).
+    pub async fn reasoning(&mut self, text: &str) -> Result<()> {
+        if !text.is_empty() {
+            self.send(AppEvent::Thinking(text.to_string())).await?;
+        }
+        Ok(())
+    }
+
+    pub fn tool_call(&mut self, call: ToolCall) {
+        self.tool_calls.push(call);
+    }
+
+    async fn emit(&mut self, text: &str) -> Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        if self.in_think {
+            self.send(AppEvent::Thinking(text.to_string())).await?;
+        } else {
+            self.content.push_str(text);
+            self.send(AppEvent::Token(text.to_string())).await?;
+        }
+        Ok(())
+    }
+
+    async fn send(&self, event: AppEvent) -> Result<()> {
+        if let Some(tx) = &self.app_tx {
+            tx.send(event).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits complete lines off a byte buffer, leaving any partial line in place.
+/// Works on bytes so multi-byte characters split across chunks survive.
+pub fn drain_lines(buffer: &mut Vec<u8>) -> Vec<String> {
+    let mut lines = Vec::new();
+    while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+        let line: Vec<u8> = buffer.drain(..=pos).collect();
+        let line = String::from_utf8_lossy(&line).trim().to_string();
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+/// Parses tool call arguments that arrive as a JSON-encoded string.
+pub fn parse_arguments(raw: &str) -> serde_json::Value {
+    if raw.trim().is_empty() {
+        return serde_json::json!({});
+    }
+    serde_json::from_str(raw).unwrap_or_else(|_| serde_json::Value::String(raw.to_string()))
+}
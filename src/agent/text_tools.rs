@@ -0,0 +1,230 @@
+use super::provider::{ProviderMessage, ProviderRole, ToolCall};
+use crate::mcp::ToolDefinition;
+use regex::Regex;
+use std::sync::LazyLock;
+
+// Prompt-based tool calling for models without native `tools` support.
+// The tool list is described in the system prompt and invocations are parsed
+// back out of the streamed content.
+
+static TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<tool_call>\s*(.*?)\s*</tool_call>").unwrap());
+static FENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)```(?:json|tool_call)?\s*\n(.*?)\n\s*```").unwrap());
+
+pub fn tool_prompt(tools: &[ToolDefinition]) -> String {
+    let mut prompt = String::from(
+        r#"
+# TOOLS
+You can use the tools listed below. To call a tool, reply with a block in exactly this format:
+
+<tool_call>
+{"name": "tool_name", "arguments": {"param": "value"}}
+</tool_call>
+
+- You may emit several <tool_call> blocks in one reply. They run in order.
+- After your reply, the results come back in <tool_result> blocks. Wait for them before continuing.
+- When you are done and need no more tools, answer normally without any <tool_call> block.
+
+## Available tools
+"#,
+    );
+
+    for tool in tools {
+        prompt.push_str(&format!(
+            "\n### {}\n{}\nParameters (JSON schema): {}\n",
+            tool.name, tool.description, tool.input_schema
+        ));
+    }
+
+    prompt
+}
+
+/// Extracts invocations of `tools` from `<tool_call>` tags or fenced JSON blocks.
+/// Anything naming another tool is left as text: a fenced `package.json` in a
+/// normal answer has a `"name"` too.
+pub fn parse_tool_calls(content: &str, tools: &[ToolDefinition]) -> Vec<ToolCall> {
+    extract_calls(content, |name| tools.iter().any(|t| t.name == name))
+}
+
+fn extract_calls(content: &str, known: impl Fn(&str) -> bool) -> Vec<ToolCall> {
+    let mut candidates: Vec<&str> = TAG
+        .captures_iter(content)
+        .filter_map(|c| c.get(1).map(|m| m.as_str()))
+        .collect();
+
+    // Only fall back to fenced blocks when the model ignored the tag format,
+    // otherwise a tag wrapping a fence would be counted twice.
+    if candidates.is_empty() {
+        candidates = FENCE
+            .captures_iter(content)
+            .filter_map(|c| c.get(1).map(|m| m.as_str()))
+            .collect();
+    }
+
+    candidates
+        .into_iter()
+        .filter_map(|raw| parse_call(strip_fence(raw)))
+        .filter(|(name, _)| known(name))
+        .enumerate()
+        .map(|(i, (name, arguments))| ToolCall {
+            id: format!("text_call_{}", i),
+            name,
+            arguments,
+        })
+        .collect()
+}
+
+pub fn format_tool_result(call: &ToolCall, result: &str) -> String {
+    format!(
+        "<tool_result name=\"{}\">\n{}\n</tool_result>",
+        call.name, result
+    )
+}
+
+pub fn format_tool_call(call: &ToolCall) -> String {
+    let body = serde_json::json!({ "name": call.name, "arguments": call.arguments });
+    format!("<tool_call>\n{}\n</tool_call>", body)
+}
+
+/// Rewrites native tool traffic (assistant `tool_calls`, `tool` role results)
+/// into the text protocol, so a history recorded in either mode can be replayed.
+pub fn as_text_protocol(messages: &[ProviderMessage]) -> Vec<ProviderMessage> {
+    messages
+        .iter()
+        .map(|msg| match msg.role {
+            ProviderRole::Assistant if !msg.tool_calls.is_empty() => {
+                let mut content = msg.content.clone();
+                // Calls parsed from text are already in the content
+                let already_written =
+                    !extract_calls(&content, |name| msg.tool_calls.iter().any(|c| c.name == name))
+                        .is_empty();
+                if !already_written {
+                    for call in &msg.tool_calls {
+                        content.push('\n');
+                        content.push_str(&format_tool_call(call));
+                    }
+                }
+                ProviderMessage::text(ProviderRole::Assistant, content)
+            }
+            ProviderRole::Tool => {
+                let call = ToolCall {
+                    id: msg.tool_call_id.clone().unwrap_or_default(),
+                    name: msg.tool_name.clone().unwrap_or_default(),
+                    arguments: serde_json::Value::Null,
+                };
+                ProviderMessage::text(ProviderRole::User, format_tool_result(&call, &msg.content))
+            }
+            _ => msg.clone(),
+        })
+        .collect()
+}
+
+fn strip_fence(raw: &str) -> &str {
+    let trimmed = raw.trim();
+    match trimmed.strip_prefix("```") {
+        Some(rest) => {
+            let rest = rest.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_');
+            rest.trim_end_matches("```").trim()
+        }
+        None => trimmed,
+    }
+}
+
+fn parse_call(raw: &str) -> Option<(String, serde_json::Value)> {
+    let value: serde_json::Value = serde_json::from_str(raw).ok()?;
+    let name = value.get("name")?.as_str()?.to_string();
+    // Small models mix up the key name, accept the common variants
+    let arguments = value
+        .get("arguments")
+        .or_else(|| value.get("parameters"))
+        .or_else(|| value.get("input"))
+        .cloned()
+        .unwrap_or_else(|| serde_json::json!({}));
+
+    // Some models double-encode the arguments as a string
+    let arguments = match arguments {
+        serde_json::Value::String(s) => serde_json::from_str(&s).unwrap_or(serde_json::Value::String(s)),
+        other => other,
+    };
+
+    Some((name, arguments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tools() -> Vec<ToolDefinition> {
+        ["read_file", "run_command"]
+            .iter()
+            .map(|name| ToolDefinition {
+                name: name.to_string(),
+                description: String::new(),
+                input_schema: json!({ "type": "object" }),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_tool_call_tags() {
+        let content = "Let me look.\n<tool_call>\n{\"name\": \"read_file\", \"arguments\": {\"path\": \"a.rs\"}}\n</tool_call>\n<tool_call>{\"name\": \"run_command\", \"parameters\": \"{\\\"command\\\": \\\"ls\\\"}\"}</tool_call>";
+        let calls = parse_tool_calls(content, &tools());
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].name, "read_file");
+        assert_eq!(calls[0].arguments, json!({ "path": "a.rs" }));
+        // `parameters` and double-encoded arguments are accepted
+        assert_eq!(calls[1].name, "run_command");
+        assert_eq!(calls[1].arguments, json!({ "command": "ls" }));
+    }
+
+    #[test]
+    fn falls_back_to_fenced_blocks() {
+        let content = "I'll run it:\n```json\n{\"name\": \"run_command\", \"arguments\": {\"command\": \"cargo build\"}}\n```\n";
+        let calls = parse_tool_calls(content, &tools());
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].arguments, json!({ "command": "cargo build" }));
+    }
+
+    #[test]
+    fn a_tag_wrapping_a_fence_counts_once() {
+        let content = "<tool_call>\n```json\n{\"name\": \"read_file\", \"arguments\": {\"path\": \"x\"}}\n```\n</tool_call>";
+        assert_eq!(parse_tool_calls(content, &tools()).len(), 1);
+    }
+
+    #[test]
+    fn skips_malformed_json() {
+        let content = "<tool_call>\n{\"name\": \"read_file\", \"arguments\": {\"path\": }\n</tool_call>\n<tool_call>\n{\"name\": \"read_file\", \"arguments\": {\"path\": \"ok\"}}\n</tool_call>";
+        let calls = parse_tool_calls(content, &tools());
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].arguments, json!({ "path": "ok" }));
+    }
+
+    #[test]
+    fn json_examples_in_prose_are_not_calls() {
+        let content = "Your package.json should look like this:\n```json\n{\n  \"name\": \"my-app\",\n  \"version\": \"1.0.0\",\n  \"scripts\": { \"start\": \"node index.js\" }\n}\n```\nThen run `npm start`.";
+        assert!(parse_tool_calls(content, &tools()).is_empty());
+    }
+
+    #[test]
+    fn text_protocol_does_not_repeat_calls_already_in_the_content() {
+        let call = ToolCall {
+            id: "text_call_0".into(),
+            name: "read_file".into(),
+            arguments: json!({ "path": "a.rs" }),
+        };
+        let mut from_text = ProviderMessage::text(ProviderRole::Assistant, format_tool_call(&call));
+        from_text.tool_calls = vec![call.clone()];
+        let mut native = ProviderMessage::text(
+            ProviderRole::Assistant,
+            "```json\n{\"name\": \"my-app\"}\n```",
+        );
+        native.tool_calls = vec![call.clone()];
+
+        let out = as_text_protocol(&[from_text, native]);
+        assert_eq!(out[0].content.matches("<tool_call>").count(), 1);
+        assert_eq!(out[1].content.matches("<tool_call>").count(), 1);
+        assert!(out.iter().all(|m| m.tool_calls.is_empty()));
+    }
+}
@@ -1,11 +1,17 @@
+use crate::agent::provider::ToolCall;
 use crate::config::Config;
+use crate::docker_setup::runtime::ContainerRuntime;
 use crate::mcp::McpRequest;
+use crate::processes::{ProcessInfo, ProcessManager};
+use crate::pty::PtyTerminal;
 use crate::session::SessionManager;
-use crate::shell::ShellRequest;
+use crate::shell::{CommandStatus, ShellRequest, DEFAULT_SESSION};
 use chrono::Local;
 use ratatui::widgets::ListState;
 use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc;
+use std::cell::Cell;
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
 use tokio::task::JoinHandle;
 
 // Declare the sub-modules so Rust knows to compile them
@@ -27,6 +33,7 @@ pub enum MessageRole {
     System,
     Error,
     Thinking,
+    Tool,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -35,19 +42,100 @@ pub struct ChatMessage {
     pub content: String,
     #[serde(default)]
     pub collapsed: bool, // Track expanded/collapsed state
+    // --- Tool state, replayed to the provider on the next turn ---
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>, // Assistant messages that invoked tools
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>, // Tool messages: which call this answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<CommandStatus>, // run_command results: how the command ended
+    /// Survives context compaction verbatim (e.g. `/add` file context)
+    #[serde(default)]
+    pub pinned: bool,
+    /// Status notice for the chat view; never sent to the model
+    #[serde(default)]
+    pub ui_only: bool,
+}
+
+impl ChatMessage {
+    pub fn new(role: MessageRole, content: String) -> Self {
+        Self {
+            role,
+            content,
+            collapsed: false,
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+            tool_name: None,
+            command: None,
+            pinned: false,
+            ui_only: false,
+        }
+    }
+
+    /// A tool's result, answering `call`.
+    pub fn tool_result(call: &ToolCall, output: String, command: Option<CommandStatus>) -> Self {
+        let mut msg = Self::new(MessageRole::Tool, output);
+        msg.tool_call_id = Some(call.id.clone());
+        msg.tool_name = Some(call.name.clone());
+        msg.command = command;
+        msg
+    }
+}
+
+/// Scrollback of one of the agent's shell sessions.
+pub struct ShellLog {
+    pub name: String,
+    pub lines: Vec<String>,
+    pub scroll: ListState,
+}
+
+impl ShellLog {
+    pub fn new(name: &str, lines: Vec<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            lines,
+            scroll: ListState::default(),
+        }
+    }
+}
+
+/// A tool call waiting for the user's y/n under `tool_approval`.
+pub struct PendingApproval {
+    pub call: ToolCall,
+    pub response_tx: oneshot::Sender<bool>,
 }
 
 pub enum AppEvent {
     Token(String),
     Thinking(String),
     AgentFinished,
-    CommandStart(String),
-    CommandEnd(String),
-    TerminalLine(String),
+    ToolCalls(Vec<ToolCall>),
+    ToolResult {
+        call: ToolCall,
+        output: String,
+        /// How a `run_command` ended
+        command: Option<CommandStatus>,
+    },
+    /// The agent wants to run `call` and waits for the user's answer
+    ApprovalRequest {
+        call: ToolCall,
+        response_tx: oneshot::Sender<bool>,
+    },
+    /// A line for one agent shell session's log in the Terminal view
+    TerminalLine { session: String, line: String },
+    /// The interactive terminal produced output (redraw)
+    TerminalOutput,
+    TerminalExited,
+    /// Background processes started, exited or were killed
+    ProcessesChanged(Vec<ProcessInfo>),
     Error(String),
+    Notice(String),
     Tick,
     ModelsLoaded(Vec<String>),
-    WorkspaceRestarted(mpsc::Sender<ShellRequest>, mpsc::Sender<McpRequest>),
+    HistoryCompacted(Vec<ChatMessage>),
+    WorkspaceRestarted(mpsc::Sender<ShellRequest>, mpsc::Sender<McpRequest>, ProcessManager),
 }
 
 pub struct App {
@@ -64,8 +152,18 @@ pub struct App {
     // UI State
     pub chat_scroll: u16,
     pub chat_stick_to_bottom: bool,
-    pub terminal_lines: Vec<String>,
-    pub term_scroll: ListState,
+    /// One log per agent shell session, in the order they appeared
+    pub shell_logs: Vec<ShellLog>,
+    /// The log shown under the interactive terminal
+    pub active_log: usize,
+    /// Interactive shell behind the Terminal tab, started on first use
+    pub pty: Option<PtyTerminal>,
+    /// Keys go straight to the pty until Ctrl+] releases them
+    pub term_focused: bool,
+    /// Rows/cols the terminal view had at the last draw
+    pub term_size: Cell<(u16, u16)>,
+    /// The agent's background processes, newest snapshot
+    pub processes: Vec<ProcessInfo>,
     pub spinner_frame: usize,
 
     // Model Selector State
@@ -75,12 +173,17 @@ pub struct App {
     // Async State
     pub is_processing: bool,
     pub agent_task: Option<JoinHandle<()>>,
+    pub pending_approval: Option<PendingApproval>,
 
     // Channels
     pub event_tx: mpsc::Sender<AppEvent>,
     pub shell_tx: mpsc::Sender<ShellRequest>,
     pub mcp_tx: mpsc::Sender<McpRequest>,
+    /// The MCP server's background processes, killed directly at exit
+    pub process_manager: ProcessManager,
     pub config: Config,
+    /// Docker or Podman, for the sandbox of whichever workspace is open
+    pub runtime: Arc<dyn ContainerRuntime>,
 }
 
 impl App {
@@ -88,7 +191,9 @@ impl App {
         event_tx: mpsc::Sender<AppEvent>,
         shell_tx: mpsc::Sender<ShellRequest>,
         mcp_tx: mpsc::Sender<McpRequest>,
+        process_manager: ProcessManager,
         config: Config,
+        runtime: Arc<dyn ContainerRuntime>,
     ) -> Self {
         let session_manager = SessionManager::new();
         let current_session = format!("chat_{}", Local::now().format("%Y-%m-%d_%H-%M"));
@@ -101,9 +206,8 @@ impl App {
             last_mode: AppMode::Chat,
             input_buffer: String::new(),
             messages: vec![ChatMessage {
-                role: MessageRole::System,
-                content: format!("Ready. Model: {}", config.model),
-                collapsed: false,
+                ui_only: true,
+                ..ChatMessage::new(MessageRole::System, format!("Ready. Model: {}", config.model))
             }],
             current_session,
             session_manager,
@@ -112,29 +216,40 @@ impl App {
             chat_scroll: 0,
             chat_stick_to_bottom: true,
 
-            terminal_lines: vec![String::from("--- Shell Connected ---")],
-            term_scroll: ListState::default(),
+            shell_logs: vec![ShellLog::new(
+                DEFAULT_SESSION,
+                vec![String::from("--- Shell Connected ---")],
+            )],
+            active_log: 0,
+            pty: None,
+            term_focused: false,
+            term_size: Cell::new((24, 80)),
+            processes: Vec::new(),
 
             available_models: vec![],
             model_list_state: ListState::default(),
 
             is_processing: false,
             agent_task: None,
+            pending_approval: None,
             spinner_frame: 0,
 
             event_tx,
             shell_tx,
             mcp_tx,
+            process_manager,
             config,
+            runtime,
         }
     }
 
-    // Helper used by all sub-modules
+    // Helper used by all sub-modules. Anything but a user message is a notice
+    // for the chat view and stays out of the model's context.
     pub fn add_system_message(&mut self, content: String, role: MessageRole) {
+        let ui_only = !matches!(role, MessageRole::User);
         self.messages.push(ChatMessage {
-            role,
-            content,
-            collapsed: false,
+            ui_only,
+            ..ChatMessage::new(role, content)
         });
         self.chat_stick_to_bottom = true;
     }
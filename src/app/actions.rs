@@ -1,7 +1,10 @@
 use super::{App, AppEvent, AppMode, MessageRole};
+use crate::agent::context;
+use crate::agent::provider::build_provider;
 use crate::docker_setup;
 use crate::mcp::McpServer;
-use crate::shell::{ShellRequest, ShellSession};
+use crate::pty::PtyTerminal;
+use crate::shell::{ShellRequest, ShellSession, DEFAULT_SESSION};
 use chrono::Local;
 use std::fs;
 use std::path::PathBuf;
@@ -68,17 +71,26 @@ impl App {
             MessageRole::System,
         );
         self.add_system_message(
-            "Restarting Docker sandbox... (this may take a moment)".into(),
+            "Starting the workspace's sandbox... (this may take a moment)".into(),
             MessageRole::Thinking,
         );
 
         let config_clone = self.config.clone();
         let event_tx_clone = self.event_tx.clone();
+        let runtime = self.runtime.clone();
 
         tokio::spawn(async move {
-            if let Err(e) = docker_setup::restart_docker_env(&config_clone) {
+            // Each workspace has its own container; the previous one keeps running for reuse.
+            // Setup blocks on the runtime's CLI, for minutes when it provisions.
+            let (setup_config, setup_runtime) = (config_clone.clone(), runtime.clone());
+            let ensured = tokio::task::spawn_blocking(move || {
+                docker_setup::ensure_docker_env(&setup_config, setup_runtime)
+            })
+            .await
+            .unwrap_or_else(|e| Err(e.into()));
+            if let Err(e) = ensured {
                 let _ = event_tx_clone
-                    .send(AppEvent::Error(format!("Failed to restart Docker: {}", e)))
+                    .send(AppEvent::Error(format!("Failed to start the sandbox: {}", e)))
                     .await;
                 return;
             }
@@ -87,14 +99,17 @@ impl App {
             let tx_shell_for_app = tx_shell.clone();
             let tx_shell_for_mcp = tx_shell.clone();
             let tx_app_for_shell = event_tx_clone.clone();
+            let shell_config = config_clone.clone();
+            let shell_runtime = runtime.clone();
 
             tokio::spawn(async move {
-                ShellSession::run_actor(rx_shell, tx_app_for_shell).await;
+                ShellSession::run_actor(rx_shell, tx_app_for_shell, shell_config, shell_runtime).await;
             });
 
-            let tx_mcp = McpServer::start(tx_shell_for_mcp, config_clone).await;
+            let (tx_mcp, processes) =
+                McpServer::start(tx_shell_for_mcp, config_clone, runtime, event_tx_clone.clone()).await;
             let _ = event_tx_clone
-                .send(AppEvent::WorkspaceRestarted(tx_shell_for_app, tx_mcp))
+                .send(AppEvent::WorkspaceRestarted(tx_shell_for_app, tx_mcp, processes))
                 .await;
         });
 
@@ -112,24 +127,12 @@ impl App {
         self.mode = AppMode::ModelSelector;
 
         let tx = self.event_tx.clone();
-        let url = self.config.ollama_url.replace("/api/chat", "/api/tags");
+        let provider = build_provider(&self.config);
 
         tokio::spawn(async move {
-            match reqwest::get(&url).await {
-                Ok(resp) => {
-                    if let Ok(json) = resp.json::<serde_json::Value>().await {
-                        if let Some(models) = json.get("models").and_then(|v| v.as_array()) {
-                            let names: Vec<String> = models
-                                .iter()
-                                .filter_map(|m| {
-                                    m.get("name")
-                                        .and_then(|n| n.as_str())
-                                        .map(|s| s.to_string())
-                                })
-                                .collect();
-                            let _ = tx.send(AppEvent::ModelsLoaded(names)).await;
-                        }
-                    }
+            match provider.list_models().await {
+                Ok(names) => {
+                    let _ = tx.send(AppEvent::ModelsLoaded(names)).await;
                 }
                 Err(e) => {
                     let _ = tx
@@ -249,10 +252,84 @@ impl App {
         self.save_current_session();
     }
 
+    pub fn compact_context(&mut self) {
+        if self.is_processing {
+            return;
+        }
+        self.is_processing = true;
+        self.add_system_message("Summarizing older turns...".into(), MessageRole::Thinking);
+
+        let tx = self.event_tx.clone();
+        let config = self.config.clone();
+        // Drop the progress note we just added
+        let history = self.messages[..self.messages.len() - 1].to_vec();
+
+        let handle = tokio::spawn(async move {
+            let provider = build_provider(&config);
+            let window = config.context_window();
+            match context::compact_history(provider.as_ref(), &config.model, &history, window)
+                .await
+            {
+                Ok(Some(compacted)) => {
+                    let _ = tx.send(AppEvent::HistoryCompacted(compacted)).await;
+                }
+                Ok(None) => {
+                    let _ = tx
+                        .send(AppEvent::Thinking("Nothing to compact yet.".into()))
+                        .await;
+                }
+                Err(e) => {
+                    let _ = tx
+                        .send(AppEvent::Error(format!("Compaction failed: {}", e)))
+                        .await;
+                }
+            }
+            let _ = tx.send(AppEvent::AgentFinished).await;
+        });
+        self.agent_task = Some(handle);
+    }
+
+    /// Focuses the Terminal tab's shell, starting it on first use.
+    pub fn open_terminal(&mut self) {
+        if self.pty.is_none() {
+            let (rows, cols) = self.term_size.get();
+            let sandbox = docker_setup::Sandbox::new(&self.config, self.runtime.clone());
+            match PtyTerminal::spawn(&sandbox, rows, cols, self.event_tx.clone()) {
+                Ok(pty) => self.pty = Some(pty),
+                Err(e) => {
+                    self.push_shell_line(DEFAULT_SESSION, format!("Failed to start terminal: {}", e));
+                    return;
+                }
+            }
+        }
+        self.term_focused = true;
+    }
+
+    /// Resizes the terminal to whatever the last draw gave it.
+    pub fn sync_terminal_size(&mut self) {
+        let (rows, cols) = self.term_size.get();
+        if let Some(pty) = self.pty.as_mut() {
+            pty.resize(rows, cols);
+        }
+    }
+
+    /// Answers the agent's pending tool approval.
+    pub fn answer_approval(&mut self, approved: bool) {
+        let Some(pending) = self.pending_approval.take() else {
+            return;
+        };
+        let verdict = if approved { "Allowed" } else { "Declined" };
+        self.add_system_message(format!("{} {}", verdict, pending.call.name), MessageRole::System);
+        let _ = pending.response_tx.send(approved);
+    }
+
     pub fn abort_agent(&mut self) {
         if let Some(task) = self.agent_task.take() {
             task.abort();
         }
+        self.pending_approval = None;
+        // The tool call outlives the agent task; stop the command it started too
+        let _ = self.shell_tx.try_send(ShellRequest::Interrupt { session: None });
         self.is_processing = false;
         self.add_system_message("🛑 Cancelled by user.".into(), MessageRole::System);
         self.save_current_session();
@@ -1,4 +1,4 @@
-use super::{App, AppEvent, ChatMessage, MessageRole};
+use super::{App, AppEvent, ChatMessage, MessageRole, PendingApproval, ShellLog};
 
 impl App {
     pub fn handle_internal_event(&mut self, event: AppEvent) {
@@ -8,15 +8,36 @@ impl App {
                     self.spinner_frame = self.spinner_frame.wrapping_add(1);
                 }
             }
-            AppEvent::WorkspaceRestarted(new_shell, new_mcp) => {
+            AppEvent::WorkspaceRestarted(new_shell, new_mcp, new_processes) => {
                 self.shell_tx = new_shell;
                 self.mcp_tx = new_mcp;
+                self.process_manager = new_processes;
+                // The next visit to the Terminal tab attaches to the new container
+                self.pty = None;
+                self.term_focused = false;
+                // The old MCP actor kills these when its last sender, the old mcp_tx, is dropped
+                self.processes.clear();
                 self.add_system_message(
                     "Workspace changed successfully.".into(),
                     MessageRole::System,
                 );
-                self.terminal_lines
-                    .push("--- Workspace Changed / Shell Restarted ---".into());
+                for log in &mut self.shell_logs {
+                    log.lines
+                        .push("--- Workspace Changed / Shell Restarted ---".into());
+                }
+            }
+            AppEvent::HistoryCompacted(history) => {
+                let before = self.messages.len();
+                self.messages = history;
+                self.add_system_message(
+                    format!(
+                        "🗜️ Compacted context: {} messages → {}.",
+                        before,
+                        self.messages.len()
+                    ),
+                    MessageRole::System,
+                );
+                self.save_current_session();
             }
             AppEvent::ModelsLoaded(models) => {
                 self.available_models = models;
@@ -34,33 +55,50 @@ impl App {
             }
             AppEvent::Token(t) => self.append_message_content(t, MessageRole::Assistant),
             AppEvent::Thinking(t) => self.append_message_content(t, MessageRole::Thinking),
-            AppEvent::CommandStart(c) => {
-                self.add_system_message(format!("🛠️ {}", c), MessageRole::System)
+            AppEvent::ToolCalls(calls) => {
+                // Attach to the assistant turn that issued them
+                match self.messages.last_mut() {
+                    Some(last) if matches!(last.role, MessageRole::Assistant) => {
+                        last.tool_calls.extend(calls);
+                    }
+                    _ => {
+                        let mut msg = ChatMessage::new(MessageRole::Assistant, String::new());
+                        msg.tool_calls = calls;
+                        self.messages.push(msg);
+                    }
+                }
+                self.chat_stick_to_bottom = true;
             }
-            AppEvent::CommandEnd(o) => {
-                let s = if o.len() > 200 {
-                    format!("Output ({} bytes) sent to terminal.", o.len())
-                } else {
-                    o
-                };
-                self.add_system_message(s, MessageRole::System);
+            AppEvent::ToolResult { call, output, command } => {
+                self.messages.push(ChatMessage::tool_result(&call, output, command));
+                self.chat_stick_to_bottom = true;
             }
-            AppEvent::TerminalLine(l) => {
-                let was_at_bottom = self
-                    .term_scroll
-                    .selected()
-                    .map_or(true, |s| s >= self.terminal_lines.len().saturating_sub(1));
-                self.terminal_lines.push(l);
-                if was_at_bottom {
-                    self.term_scroll
-                        .select(Some(self.terminal_lines.len().saturating_sub(1)));
-                }
+            AppEvent::ApprovalRequest { call, response_tx } => {
+                // The command or path says more than the tool name alone
+                let detail = ["command", "path"]
+                    .iter()
+                    .find_map(|key| call.arguments.get(key).and_then(|v| v.as_str()))
+                    .map(|s| format!(": {}", s))
+                    .unwrap_or_default();
+                self.add_system_message(
+                    format!("⚠ Allow {}{}?  [y] yes  [n] no", call.name, detail),
+                    MessageRole::System,
+                );
+                self.pending_approval = Some(PendingApproval { call, response_tx });
             }
+            AppEvent::TerminalOutput => {}
+            AppEvent::TerminalExited => {
+                self.pty = None;
+                self.term_focused = false;
+            }
+            AppEvent::ProcessesChanged(processes) => self.processes = processes,
+            AppEvent::TerminalLine { session, line } => self.push_shell_line(&session, line),
             AppEvent::AgentFinished => {
                 self.is_processing = false;
                 self.agent_task = None;
                 self.save_current_session();
             }
+            AppEvent::Notice(n) => self.add_system_message(n, MessageRole::System),
             AppEvent::Error(e) => {
                 self.add_system_message(e, MessageRole::Error);
                 self.is_processing = false;
@@ -70,6 +108,25 @@ impl App {
         }
     }
 
+    /// Appends to a session's log, creating it on its first line.
+    pub fn push_shell_line(&mut self, session: &str, line: String) {
+        let log = match self.shell_logs.iter().position(|l| l.name == session) {
+            Some(i) => &mut self.shell_logs[i],
+            None => {
+                self.shell_logs.push(ShellLog::new(session, Vec::new()));
+                self.shell_logs.last_mut().unwrap()
+            }
+        };
+        let was_at_bottom = log
+            .scroll
+            .selected()
+            .map_or(true, |s| s >= log.lines.len().saturating_sub(1));
+        log.lines.push(line);
+        if was_at_bottom {
+            log.scroll.select(Some(log.lines.len().saturating_sub(1)));
+        }
+    }
+
     fn append_message_content(&mut self, content: String, role: MessageRole) {
         let start_new = if let Some(last) = self.messages.last() {
             match (&last.role, &role) {
@@ -84,11 +141,9 @@ impl App {
         if start_new {
             // Default Thinking blocks to collapsed
             let collapsed = matches!(role, MessageRole::Thinking);
-            self.messages.push(ChatMessage {
-                role,
-                content,
-                collapsed,
-            });
+            let mut msg = ChatMessage::new(role, content);
+            msg.collapsed = collapsed;
+            self.messages.push(msg);
         } else {
             if let Some(last) = self.messages.last_mut() {
                 last.content.push_str(&content);
@@ -1,7 +1,7 @@
 use super::{App, AppEvent, AppMode, ChatMessage, MessageRole};
 use crate::agent::run_agent_loop;
-use crate::shell::ShellRequest;
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
+use crate::pty;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEvent, MouseEventKind};
 use std::fs;
 
 impl App {
@@ -17,6 +17,11 @@ impl App {
     }
 
     pub fn handle_key_event(&mut self, key: KeyEvent) {
+        if self.terminal_focused() {
+            self.handle_terminal_key(key);
+            return;
+        }
+
         if key.modifiers.contains(KeyModifiers::CONTROL) {
             match key.code {
                 KeyCode::Char('p') => {
@@ -39,6 +44,15 @@ impl App {
             }
         }
 
+        // The agent is waiting on a tool approval; the input is idle meanwhile anyway
+        if self.pending_approval.is_some() && self.mode != AppMode::ModelSelector {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => return self.answer_approval(true),
+                KeyCode::Char('n') | KeyCode::Char('N') => return self.answer_approval(false),
+                _ => {}
+            }
+        }
+
         match self.mode {
             AppMode::ModelSelector => match key.code {
                 KeyCode::Esc => self.mode = self.last_mode.clone(),
@@ -48,13 +62,22 @@ impl App {
                 KeyCode::Enter => self.confirm_model_selection(),
                 _ => {}
             },
-            AppMode::Chat | AppMode::Terminal => match key.code {
+            AppMode::Terminal => match key.code {
+                KeyCode::Tab => self.mode = AppMode::Chat,
+                KeyCode::Esc if self.is_processing => self.abort_agent(),
+                KeyCode::Enter | KeyCode::Char('i') => self.open_terminal(),
+                KeyCode::Up => self.scroll_up(),
+                KeyCode::Down => self.scroll_down(),
+                KeyCode::PageUp => self.scroll_terminal(10),
+                KeyCode::PageDown => self.scroll_terminal(-10),
+                KeyCode::Char('[') => self.cycle_shell_log(-1),
+                KeyCode::Char(']') => self.cycle_shell_log(1),
+                _ => {}
+            },
+            AppMode::Chat => match key.code {
                 KeyCode::Tab => {
-                    self.mode = match self.mode {
-                        AppMode::Chat => AppMode::Terminal,
-                        AppMode::Terminal => AppMode::Chat,
-                        _ => AppMode::Chat,
-                    };
+                    self.mode = AppMode::Terminal;
+                    self.open_terminal();
                 }
                 KeyCode::Esc if self.is_processing => self.abort_agent(),
                 KeyCode::Up => self.scroll_up(),
@@ -128,6 +151,10 @@ impl App {
                     self.add_system_message("Context reset.".into(), MessageRole::System);
                     return;
                 }
+                "/compact" => {
+                    self.compact_context();
+                    return;
+                }
                 "/add" => {
                     if let Some(filename) = parts.get(1) {
                         let path = self.config.workspace_path.join(filename);
@@ -138,6 +165,10 @@ impl App {
                                     filename, content
                                 );
                                 self.add_system_message(context_msg, MessageRole::System);
+                                if let Some(msg) = self.messages.last_mut() {
+                                    msg.pinned = true;
+                                    msg.ui_only = false;
+                                }
                                 self.add_system_message(
                                     format!("✅ Added {} to context.", filename),
                                     MessageRole::System,
@@ -170,32 +201,23 @@ impl App {
             }
         }
 
-        match self.mode {
-            AppMode::Chat => {
-                self.is_processing = true;
-                self.add_system_message(text.clone(), MessageRole::User);
-                self.save_current_session();
-
-                let tx = self.event_tx.clone();
-                let mcp = self.mcp_tx.clone();
-                let history = self.messages.clone();
-                let config = self.config.clone();
-
-                let handle = tokio::spawn(async move {
-                    if let Err(e) = run_agent_loop(config, history, tx.clone(), mcp).await {
-                        let _ = tx.send(AppEvent::Error(e.to_string())).await;
-                    }
-                    let _ = tx.send(AppEvent::AgentFinished).await;
-                });
-                self.agent_task = Some(handle);
-            }
-            AppMode::Terminal => {
-                let shell = self.shell_tx.clone();
-                tokio::spawn(async move {
-                    let _ = shell.send(ShellRequest::UserInput(text)).await;
-                });
-            }
-            _ => {}
+        if self.mode == AppMode::Chat {
+            self.is_processing = true;
+            self.add_system_message(text.clone(), MessageRole::User);
+            self.save_current_session();
+
+            let tx = self.event_tx.clone();
+            let mcp = self.mcp_tx.clone();
+            let history = self.messages.clone();
+            let config = self.config.clone();
+
+            let handle = tokio::spawn(async move {
+                if let Err(e) = run_agent_loop(config, history, tx.clone(), mcp).await {
+                    let _ = tx.send(AppEvent::Error(e.to_string())).await;
+                }
+                let _ = tx.send(AppEvent::AgentFinished).await;
+            });
+            self.agent_task = Some(handle);
         }
     }
 
@@ -204,8 +226,7 @@ impl App {
             self.chat_stick_to_bottom = false;
             self.chat_scroll = self.chat_scroll.saturating_sub(1);
         } else {
-            let i = self.term_scroll.selected().unwrap_or(0) as i32;
-            self.term_scroll.select(Some((i - 1).max(0) as usize));
+            self.term_scroll_delta(-1);
         }
     }
     pub fn scroll_down(&mut self) {
@@ -213,8 +234,7 @@ impl App {
             self.chat_stick_to_bottom = false;
             self.chat_scroll = self.chat_scroll.saturating_add(1);
         } else {
-            let i = self.term_scroll.selected().unwrap_or(0) as i32;
-            self.term_scroll.select(Some((i + 1).max(0) as usize));
+            self.term_scroll_delta(1);
         }
     }
     pub fn scroll_page(&mut self, amt: i16) {
@@ -226,14 +246,56 @@ impl App {
                 self.chat_scroll = self.chat_scroll.saturating_add(amt.abs() as u16);
             }
         } else {
-            let i = self.term_scroll.selected().unwrap_or(0) as i32;
-            self.term_scroll
-                .select(Some((i + amt as i32).max(0) as usize));
+            self.term_scroll_delta(amt as i32);
+        }
+    }
+
+    pub fn terminal_focused(&self) -> bool {
+        self.mode == AppMode::Terminal && self.term_focused
+    }
+
+    /// Raw key forwarding while the terminal has focus. Ctrl+] hands keys back to Agerus.
+    fn handle_terminal_key(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        if key.code == KeyCode::Char(']') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            self.term_focused = false;
+            return;
+        }
+        let Some(pty) = self.pty.as_mut() else {
+            self.term_focused = false;
+            return;
+        };
+        let app_cursor = pty
+            .parser()
+            .map(|p| p.screen().application_cursor())
+            .unwrap_or(false);
+        if let Some(bytes) = pty::key_to_bytes(key, app_cursor) {
+            // Typing jumps back to the live screen
+            pty.scroll_to_bottom();
+            pty.write(&bytes);
+        }
+    }
+
+    fn scroll_terminal(&mut self, delta: i32) {
+        if let Some(pty) = self.pty.as_mut() {
+            pty.scroll(delta);
         }
     }
 
     pub fn term_scroll_delta(&mut self, delta: i32) {
-        let i = self.term_scroll.selected().unwrap_or(0) as i32;
-        self.term_scroll.select(Some((i + delta).max(0) as usize));
+        if let Some(log) = self.shell_logs.get_mut(self.active_log) {
+            let i = log.scroll.selected().unwrap_or(0) as i32;
+            log.scroll.select(Some((i + delta).max(0) as usize));
+        }
+    }
+
+    /// Shows the next (or previous) agent shell session's log.
+    fn cycle_shell_log(&mut self, step: i32) {
+        let count = self.shell_logs.len() as i32;
+        if count > 0 {
+            self.active_log = (self.active_log as i32 + step).rem_euclid(count) as usize;
+        }
     }
 }
@@ -1,13 +1,217 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    #[default]
+    Ollama,
+    OpenAi,
+    Anthropic,
+}
+
+/// How tools are offered to the model.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolMode {
+    /// Native `tools`, switching to the prompt protocol if the model rejects them
+    #[default]
+    Auto,
+    Native,
+    /// Tools described in the system prompt and parsed out of the reply
+    Prompt,
+}
+
+/// Where commands and processes run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxMode {
+    /// The workspace's container, see `[container]`
+    #[default]
+    Container,
+    /// Straight on the host, in the workspace; no isolation at all
+    None,
+}
+
+/// Which tool calls wait for the user's go-ahead.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolApproval {
+    Off,
+    /// Anything that runs commands or changes files
+    Changes,
+    All,
+}
+
+/// An external MCP server spawned over stdio, e.g.
+/// `[mcp_servers.fs]` with `command = "npx"` and `args = [...]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalMcpServer {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+/// Which container CLI drives the sandbox.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuntimeKind {
+    /// Docker if installed, else Podman
+    #[default]
+    Auto,
+    Docker,
+    Podman,
+}
+
+/// Network access for the sandbox.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkMode {
+    /// Docker's default bridge: full access
+    #[default]
+    Bridge,
+    /// No network at all
+    None,
+    /// Only `allowed_hosts`, through a filtering proxy
+    Allowlist,
+}
+
+/// The sandbox container, `[container]` in config.toml.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerConfig {
+    /// `auto`, `docker` or `podman`
+    #[serde(default)]
+    pub runtime: RuntimeKind,
+    /// Image to run when the project has no Dockerfile
+    #[serde(default = "default_image")]
+    pub image: String,
+    /// Dockerfile to build the sandbox from, relative to the workspace.
+    /// `.agerus/Dockerfile` is used when unset and present. Built with its
+    /// own directory as the context.
+    #[serde(default)]
+    pub dockerfile: Option<PathBuf>,
+    /// Installed when missing: rust, python, node, go. Unset means Rust for
+    /// the stock image and nothing for a project Dockerfile.
+    #[serde(default)]
+    pub toolchains: Option<Vec<String>>,
+    /// Run once, in order, in every newly created container
+    #[serde(default)]
+    pub setup_commands: Vec<String>,
+    /// Run commands as root instead of a user with the host's UID/GID
+    #[serde(default)]
+    pub run_as_root: bool,
+    // --- Limits ---
+    /// CPU cores, e.g. 2 or 1.5 (`--cpus`)
+    #[serde(default)]
+    pub cpus: Option<f64>,
+    /// Memory cap including swap, e.g. "4g" (`--memory`)
+    #[serde(default)]
+    pub memory: Option<String>,
+    /// Most processes at once (`--pids-limit`); stops fork bombs
+    #[serde(default)]
+    pub pids_limit: Option<u32>,
+    /// Read-only root filesystem; /workspace, /tmp and /run stay writable
+    #[serde(default)]
+    pub read_only: bool,
+    // --- Network ---
+    #[serde(default)]
+    pub network: NetworkMode,
+    /// Domains reachable with `network = "allowlist"`; a leading dot includes subdomains
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+}
+
+impl Default for ContainerConfig {
+    fn default() -> Self {
+        Self {
+            runtime: RuntimeKind::Auto,
+            image: default_image(),
+            dockerfile: None,
+            toolchains: None,
+            setup_commands: Vec::new(),
+            run_as_root: false,
+            cpus: None,
+            memory: None,
+            pids_limit: None,
+            read_only: false,
+            network: NetworkMode::Bridge,
+            allowed_hosts: Vec::new(),
+        }
+    }
+}
+
+impl ContainerConfig {
+    /// Short description of the network policy and limits, for the status bar.
+    pub fn policy_summary(&self) -> String {
+        let mut parts = vec![match self.network {
+            NetworkMode::Bridge => "net:open".to_string(),
+            NetworkMode::None => "net:none".to_string(),
+            NetworkMode::Allowlist => format!("net:{} hosts", self.allowed_hosts.len()),
+        }];
+        if let Some(cpus) = self.cpus {
+            parts.push(format!("{}cpu", cpus));
+        }
+        if let Some(memory) = &self.memory {
+            parts.push(memory.clone());
+        }
+        if let Some(pids) = self.pids_limit {
+            parts.push(format!("{}pids", pids));
+        }
+        if self.read_only {
+            parts.push("ro".to_string());
+        }
+        parts.join(" ")
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub model: String,
     pub workspace_path: PathBuf,
     pub ollama_url: String,
+    // --- LLM Backend ---
+    #[serde(default)]
+    pub provider: ProviderKind,
+    /// Endpoint for non-Ollama providers. Falls back to the provider's usual default.
+    #[serde(default)]
+    pub api_url: Option<String>,
+    /// Falls back to OPENAI_API_KEY / ANTHROPIC_API_KEY when unset.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+    #[serde(default)]
+    pub tool_mode: ToolMode,
+    // --- Context Window ---
+    /// Context window (tokens) for models without an entry in `context_windows`
+    #[serde(default = "default_context_tokens")]
+    pub context_tokens: usize,
+    #[serde(default)]
+    pub context_windows: BTreeMap<String, usize>,
+    // --- Shell ---
+    /// Default `run_command` timeout; the model can override it per call (5s to 30min)
+    #[serde(default = "default_command_timeout")]
+    pub command_timeout_secs: u64,
+    // --- File Tools ---
+    /// Most bytes `read_file` returns in one call
+    #[serde(default = "default_read_max_bytes")]
+    pub read_max_bytes: usize,
+    // --- Sandbox ---
+    #[serde(default)]
+    pub sandbox: SandboxMode,
+    #[serde(default)]
+    pub container: ContainerConfig,
+    /// Defaults to `changes` without a sandbox and `off` with one
+    #[serde(default)]
+    pub tool_approval: Option<ToolApproval>,
+    // --- External MCP Servers ---
+    #[serde(default)]
+    pub mcp_servers: BTreeMap<String, ExternalMcpServer>,
     // --- New Config ---
     #[serde(default = "default_voice_url")]
     pub voice_server_url: String,
@@ -19,12 +223,45 @@ fn default_voice_url() -> String {
     "http://127.0.0.1:5000/tts".to_string()
 }
 
+fn default_max_tokens() -> u32 {
+    8192
+}
+
+fn default_context_tokens() -> usize {
+    8192
+}
+
+fn default_command_timeout() -> u64 {
+    120
+}
+
+fn default_read_max_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_image() -> String {
+    "ubuntu:latest".to_string()
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             model: "qwen2.5-coder:latest".to_string(),
             workspace_path: PathBuf::from("./workspace"),
             ollama_url: "http://localhost:11434/api/chat".to_string(),
+            provider: ProviderKind::Ollama,
+            api_url: None,
+            api_key: None,
+            max_tokens: default_max_tokens(),
+            tool_mode: ToolMode::Auto,
+            context_tokens: default_context_tokens(),
+            context_windows: BTreeMap::new(),
+            command_timeout_secs: default_command_timeout(),
+            read_max_bytes: default_read_max_bytes(),
+            sandbox: SandboxMode::Container,
+            container: ContainerConfig::default(),
+            tool_approval: None,
+            mcp_servers: BTreeMap::new(),
             voice_server_url: default_voice_url(),
             voice_enabled: false, // Off by default
         }
@@ -60,6 +297,43 @@ impl Config {
         Ok(Config::default())
     }
 
+    pub fn provider_url(&self) -> String {
+        match self.provider {
+            ProviderKind::Ollama => self.ollama_url.clone(),
+            ProviderKind::OpenAi => self
+                .api_url
+                .clone()
+                .unwrap_or_else(|| "http://localhost:8080/v1/chat/completions".to_string()),
+            ProviderKind::Anthropic => self
+                .api_url
+                .clone()
+                .unwrap_or_else(|| "https://api.anthropic.com/v1/messages".to_string()),
+        }
+    }
+
+    pub fn tool_approval(&self) -> ToolApproval {
+        self.tool_approval.unwrap_or(match self.sandbox {
+            SandboxMode::Container => ToolApproval::Off,
+            SandboxMode::None => ToolApproval::Changes,
+        })
+    }
+
+    pub fn context_window(&self) -> usize {
+        self.context_windows
+            .get(&self.model)
+            .copied()
+            .unwrap_or(self.context_tokens)
+    }
+
+    pub fn provider_api_key(&self) -> Option<String> {
+        let env_var = match self.provider {
+            ProviderKind::Ollama => return self.api_key.clone(),
+            ProviderKind::OpenAi => "OPENAI_API_KEY",
+            ProviderKind::Anthropic => "ANTHROPIC_API_KEY",
+        };
+        self.api_key.clone().or_else(|| std::env::var(env_var).ok())
+    }
+
     pub fn save(&self) -> Result<()> {
         let config_path = Self::get_config_path()?;
         let content = toml::to_string_pretty(self)?;
@@ -67,3 +341,25 @@ impl Config {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tool_approval_defaults_follow_the_sandbox() {
+        let mut config = Config::default();
+        assert_eq!(config.tool_approval(), ToolApproval::Off);
+
+        // Without a sandbox, changes wait for the user
+        config.sandbox = SandboxMode::None;
+        assert_eq!(config.tool_approval(), ToolApproval::Changes);
+
+        // An explicit setting wins either way
+        config.tool_approval = Some(ToolApproval::Off);
+        assert_eq!(config.tool_approval(), ToolApproval::Off);
+        config.sandbox = SandboxMode::Container;
+        config.tool_approval = Some(ToolApproval::All);
+        assert_eq!(config.tool_approval(), ToolApproval::All);
+    }
+}
@@ -1,102 +1,654 @@
-use crate::config::Config;
-use anyhow::{anyhow, Result};
+use crate::config::{Config, ContainerConfig, NetworkMode, SandboxMode};
+use anyhow::{anyhow, bail, Result};
+use network::ProxyAccess;
+use runtime::{ContainerRuntime, RunSpec};
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::process::Command;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-const CONTAINER_NAME: &str = "agerus_sandbox";
+#[cfg(test)]
+mod fake;
+pub mod image;
+pub mod manage;
+pub mod network;
+pub mod runtime;
 
-pub fn ensure_docker_env(config: &Config) -> Result<()> {
-    // Standard startup check
-    setup_container(config, false)
+/// Hash of everything the container was created from; a mismatch recreates it
+const SETUP_LABEL: &str = "agerus.setup";
+/// Canonical workspace path the container mounts; `agerus sandbox` finds containers by it
+const WORKSPACE_LABEL: &str = "agerus.workspace";
+
+/// Installed alongside the first missing toolchain
+const BASE_PACKAGES: &str = "curl ca-certificates git vim nano wget sudo";
+
+/// Given to the host's UID when the image has no user with it
+const SANDBOX_USER: &str = "agerus";
+
+struct Toolchain {
+    name: &'static str,
+    /// Succeeds when the toolchain is already there
+    check: &'static str,
+    install: &'static str,
 }
 
-pub fn restart_docker_env(config: &Config) -> Result<()> {
-    // Force restart
-    setup_container(config, true)
+// Installs assume an apt-based image (Ubuntu, Debian)
+const TOOLCHAINS: &[Toolchain] = &[
+    Toolchain {
+        name: "rust",
+        check: "cargo --version",
+        // System-wide rather than in /root, so the mapped user can use and update it
+        install: "apt-get install -y build-essential && \
+                  curl --proto '=https' --tlsv1.2 -sSf https://sh.rustup.rs | \
+                  RUSTUP_HOME=/usr/local/rustup CARGO_HOME=/usr/local/cargo sh -s -- -y --no-modify-path && \
+                  echo 'export RUSTUP_HOME=/usr/local/rustup CARGO_HOME=/usr/local/cargo PATH=/usr/local/cargo/bin:$PATH' \
+                  > /etc/profile.d/rust.sh && \
+                  chmod -R a+rwX /usr/local/rustup /usr/local/cargo",
+    },
+    Toolchain {
+        name: "python",
+        check: "python3 --version && pip3 --version",
+        install: "apt-get install -y python3 python3-pip python3-venv",
+    },
+    Toolchain {
+        name: "node",
+        check: "node --version && npm --version",
+        install: "apt-get install -y nodejs npm",
+    },
+    Toolchain {
+        name: "go",
+        check: "go version",
+        install: "apt-get install -y golang-go",
+    },
+];
+
+/// The workspace's own sandbox, so instances on different projects don't share one.
+/// Readable prefix from the directory name, uniqueness from a hash of the canonical path.
+pub fn container_name(config: &Config) -> String {
+    let path = fs::canonicalize(&config.workspace_path)
+        .unwrap_or_else(|_| config.workspace_path.clone());
+    let slug: String = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .take(24)
+        .collect();
+    let hash = format!("{:x}", Sha256::digest(path.to_string_lossy().as_bytes()));
+    if slug.is_empty() {
+        format!("agerus_{}", &hash[..10])
+    } else {
+        format!("agerus_{}_{}", slug, &hash[..10])
+    }
 }
 
-fn setup_container(config: &Config, force_restart: bool) -> Result<()> {
+/// The `uid:gid` that commands run as in the sandbox, so files they write into
+/// the workspace belong to the host user. `None` runs them as root.
+pub fn exec_user(config: &Config, rt: &dyn ContainerRuntime) -> Option<String> {
+    if config.container.run_as_root || rt.rootless() {
+        return None;
+    }
+    host_ids().map(|(uid, gid)| format!("{}:{}", uid, gid))
+}
+
+/// Where commands run and how to start them there.
+#[derive(Clone)]
+pub enum Sandbox {
+    Container {
+        runtime: Arc<dyn ContainerRuntime>,
+        container: String,
+        /// See `exec_user`
+        user: Option<String>,
+    },
+    /// `sandbox = "none"`: on the host, from the workspace
+    Local { workspace: PathBuf },
+}
+
+impl Sandbox {
+    pub fn new(config: &Config, runtime: Arc<dyn ContainerRuntime>) -> Self {
+        match config.sandbox {
+            SandboxMode::Container => Self::Container {
+                container: container_name(config),
+                user: exec_user(config, runtime.as_ref()),
+                runtime,
+            },
+            SandboxMode::None => Self::Local {
+                workspace: fs::canonicalize(&config.workspace_path)
+                    .unwrap_or_else(|_| config.workspace_path.clone()),
+            },
+        }
+    }
+
+    /// Program, arguments and host working directory that run `argv` from the
+    /// workspace as the sandbox user. `exec_flags` go to the runtime's `exec`.
+    pub fn invocation(&self, exec_flags: &[&str], argv: &[&str]) -> (String, Vec<String>, PathBuf) {
+        let argv = argv.iter().map(|a| a.to_string());
+        match self {
+            Self::Container {
+                runtime,
+                container,
+                user,
+            } => {
+                let mut args = vec!["exec".to_string(), "-w".to_string(), "/workspace".to_string()];
+                args.extend(exec_flags.iter().map(|f| f.to_string()));
+                if let Some(user) = user {
+                    args.extend(["--user".to_string(), user.clone()]);
+                }
+                args.push(container.clone());
+                args.extend(argv);
+                // Host cwd "/" avoids the OCI namespace path issue, see ShellSession
+                (runtime.program().to_string(), args, PathBuf::from("/"))
+            }
+            Self::Local { workspace } => {
+                let mut argv = argv;
+                let program = argv.next().unwrap_or_default();
+                (program, argv.collect(), workspace.clone())
+            }
+        }
+    }
+
+    pub fn command(&self, exec_flags: &[&str], argv: &[&str]) -> tokio::process::Command {
+        let (program, args, cwd) = self.invocation(exec_flags, argv);
+        let mut cmd = tokio::process::Command::new(program);
+        cmd.args(args).current_dir(cwd);
+        cmd
+    }
+
+    /// Like `command`, but as root, which can signal whatever user the commands run as.
+    pub fn root_command(&self, argv: &[&str]) -> tokio::process::Command {
+        match self {
+            Self::Container {
+                runtime, container, ..
+            } => {
+                let mut cmd = runtime.async_command();
+                cmd.arg("exec").arg(container).args(argv);
+                cmd
+            }
+            Self::Local { .. } => self.command(&[], argv),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn host_ids() -> Option<(u32, u32)> {
+    // SAFETY: getuid and getgid always succeed and touch no memory of ours
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    // Root on the host already owns whatever root writes
+    (uid != 0).then_some((uid, gid))
+}
+
+#[cfg(not(unix))]
+fn host_ids() -> Option<(u32, u32)> {
+    None
+}
+
+/// Makes sure the workspace's container is running and set up from the current config.
+/// With `sandbox = "none"` there is no container; only the workspace is created.
+pub fn ensure_docker_env(config: &Config, rt: Arc<dyn ContainerRuntime>) -> Result<()> {
     let workspace_path = &config.workspace_path;
 
     if !workspace_path.exists() {
         fs::create_dir_all(workspace_path)?;
     }
+    if config.sandbox == SandboxMode::None {
+        return Ok(());
+    }
 
     let abs_workspace = fs::canonicalize(workspace_path)?;
+    let rt = rt.as_ref();
+    let container = container_name(config);
+
+    let toolchains = resolve_toolchains(config)?;
+    let image = image::resolve(rt, config, &abs_workspace)?;
+    let toolchains = toolchains.unwrap_or_else(|| {
+        // A project Dockerfile brings its own tools; the stock image gets Rust as before
+        let default = if image.from_dockerfile { "" } else { "rust" };
+        TOOLCHAINS.iter().filter(|t| t.name == default).collect()
+    });
+    let user = exec_user(config, rt);
+    let mut policy_args = limit_args(&config.container);
+    policy_args.extend(network::run_args(&container, &config.container));
+
+    let fingerprint = {
+        let mut hasher = Sha256::new();
+        hasher.update(abs_workspace.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(image.reference.as_bytes());
+        for toolchain in &toolchains {
+            hasher.update([0]);
+            hasher.update(toolchain.name.as_bytes());
+        }
+        for cmd in &config.container.setup_commands {
+            hasher.update([1]);
+            hasher.update(cmd.as_bytes());
+        }
+        for arg in &policy_args {
+            hasher.update([2]);
+            hasher.update(arg.as_bytes());
+        }
+        for host in &config.container.allowed_hosts {
+            hasher.update([3]);
+            hasher.update(host.as_bytes());
+        }
+        if let Some(user) = &user {
+            hasher.update([4]);
+            hasher.update(user.as_bytes());
+        }
+        format!("{:x}", hasher.finalize())
+    };
+
+    // Check status, stopped containers included; the label tells whether it was set up from the current config
+    let existing = rt
+        .container_state(&container, SETUP_LABEL)?
+        .filter(|c| c.label == fingerprint);
 
-    // Check status
-    let output = Command::new("docker")
-        .args([
-            "ps",
-            "--filter",
-            &format!("name={}", CONTAINER_NAME),
-            "--format",
-            "{{.Names}}",
-        ])
-        .output()?; // Use output() to capture stdout
-
-    let output_str = String::from_utf8_lossy(&output.stdout);
-    let is_running = output_str.trim() == CONTAINER_NAME;
-
-    if !is_running || force_restart {
+    if let Some(existing) = existing {
+        network::ensure_proxy(rt, &container, &config.container, false, ProxyAccess::Allowlist)?;
+        if existing.state != "running" {
+            // Stopped (e.g. after a reboot): its tools and files are still there
+            rt.start(&container)?;
+        }
+    } else {
         // Kill existing
-        let _ = Command::new("docker")
-            .args(["rm", "-f", CONTAINER_NAME])
-            .output();
+        let _ = rt.remove(&container);
+        // Provisioning needs hosts the allowlist doesn't have; it's enforced once that's done
+        network::ensure_proxy(rt, &container, &config.container, true, ProxyAccess::Setup)?;
 
         // Start new
-        // CHANGED: .status()? -> .output()? to prevent printing Container ID to TUI
-        let output = Command::new("docker")
-            .arg("run")
-            .arg("-d")
-            .arg("--name")
-            .arg(CONTAINER_NAME)
-            .arg("-v")
-            .arg(format!("{}:/workspace", abs_workspace.to_string_lossy()))
-            .arg("-w")
-            .arg("/workspace")
-            .arg("ubuntu:latest")
-            .args(["tail", "-f", "/dev/null"])
-            .output()?;
-
-        if !output.status.success() {
-            return Err(anyhow!("Failed to start Docker container."));
+        let workspace_label = abs_workspace.to_string_lossy();
+        let mut run_args = vec![
+            "-v".to_string(),
+            format!("{}:/workspace", workspace_label),
+            "-w".to_string(),
+            "/workspace".to_string(),
+        ];
+        run_args.extend(policy_args);
+        let run = rt.run_container(&RunSpec {
+            name: &container,
+            image: &image.reference,
+            labels: &[(SETUP_LABEL, &fingerprint), (WORKSPACE_LABEL, &workspace_label)],
+            args: &run_args,
+            command: &["tail", "-f", "/dev/null"],
+        });
+        if let Err(e) = run {
+            network::remove(rt, &container);
+            return Err(e);
         }
 
-        // Only verify/install rust if we actually restarted/created the container
-        check_and_install_tools()?;
+        // Only provision if we actually restarted/created the container.
+        // A half-provisioned container would carry the label, so remove it on failure.
+        let provisioned = provision(rt, &container, &toolchains, &config.container, user.as_deref());
+        let enforced =
+            network::ensure_proxy(rt, &container, &config.container, false, ProxyAccess::Allowlist);
+        if let Err(e) = provisioned.and(enforced) {
+            let _ = rt.remove(&container);
+            return Err(e);
+        }
     }
 
     Ok(())
 }
 
-fn check_and_install_tools() -> Result<()> {
-    let cargo_check = Command::new("docker")
-        .args([
-            "exec",
-            CONTAINER_NAME,
-            "bash",
-            "-l",
-            "-c",
-            "cargo --version",
-        ])
-        .output();
-
-    let needs_install = match cargo_check {
-        Ok(out) => !out.status.success(),
-        Err(_) => true,
+/// `docker run` arguments for the resource limits in `[container]`.
+fn limit_args(container: &ContainerConfig) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(cpus) = container.cpus {
+        args.extend(["--cpus".to_string(), cpus.to_string()]);
+    }
+    if let Some(memory) = &container.memory {
+        // Same swap limit, so a runaway build hits the cap instead of swapping the host
+        args.extend(["--memory".to_string(), memory.clone()]);
+        args.extend(["--memory-swap".to_string(), memory.clone()]);
+    }
+    if let Some(pids) = container.pids_limit {
+        args.extend(["--pids-limit".to_string(), pids.to_string()]);
+    }
+    if container.read_only {
+        args.push("--read-only".to_string());
+        for dir in ["/tmp", "/run"] {
+            args.extend(["--tmpfs".to_string(), dir.to_string()]);
+        }
+    }
+    args
+}
+
+/// `None` when the config leaves the choice to the image.
+fn resolve_toolchains(config: &Config) -> Result<Option<Vec<&'static Toolchain>>> {
+    let Some(names) = &config.container.toolchains else {
+        return Ok(None);
     };
+    names
+        .iter()
+        .map(|name| {
+            TOOLCHAINS
+                .iter()
+                .find(|t| t.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| {
+                    let known: Vec<&str> = TOOLCHAINS.iter().map(|t| t.name).collect();
+                    anyhow!(
+                        "Unknown toolchain '{}' in [container] (known: {})",
+                        name,
+                        known.join(", ")
+                    )
+                })
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(Some)
+}
+
+/// Toolchains are installed as root; setup commands run as `user`, like the agent's commands.
+fn provision(
+    rt: &dyn ContainerRuntime,
+    container: &str,
+    toolchains: &[&Toolchain],
+    config: &ContainerConfig,
+    user: Option<&str>,
+) -> Result<()> {
+    let missing: Vec<&Toolchain> = toolchains
+        .iter()
+        .copied()
+        .filter(|t| !rt.exec(container, None, t.check).is_ok_and(|out| out.success))
+        .collect();
+
+    if !missing.is_empty() {
+        let names: Vec<&str> = missing.iter().map(|t| t.name).collect();
+        let blocker = if config.read_only {
+            Some("the root filesystem is read-only")
+        } else if config.network == NetworkMode::None {
+            Some("the sandbox has no network")
+        } else {
+            None
+        };
+        if let Some(blocker) = blocker {
+            bail!(
+                "Can't install {} because {}. Use an image that already has them, or change [container].",
+                names.join(", "),
+                blocker
+            );
+        }
 
-    if needs_install {
-        // Silent install if possible
-        let install_cmd = "apt-get update && \
-                           apt-get install -y curl git vim nano wget build-essential && \
-                           curl --proto '=https' --tlsv1.2 -sSf https://sh.rustup.rs | sh -s -- -y";
+        let mut script = format!("apt-get update && apt-get install -y {}", BASE_PACKAGES);
+        for toolchain in &missing {
+            script.push_str(" && ");
+            script.push_str(toolchain.install);
+        }
+        let output = rt.exec(container, None, &script)?;
+        if !output.success {
+            bail!(
+                "Failed to install {} in the sandbox:\n{}",
+                names.join(", "),
+                output.tail(20)
+            );
+        }
+    }
 
-        // CHANGED: .status()? -> .output()? to prevent printing apt-get logs to TUI
-        Command::new("docker")
-            .args(["exec", CONTAINER_NAME, "bash", "-c", install_cmd])
-            .output()?;
+    // A read-only root can't take a new user; the image has to bring one
+    if let Some(user) = user
+        && !config.read_only
+    {
+        create_user(rt, container, user)?;
+    }
+
+    for cmd in &config.setup_commands {
+        let output = rt.exec(container, user, cmd)?;
+        if !output.success {
+            bail!(
+                "Setup command failed in the sandbox: {}\n{}",
+                cmd,
+                output.tail(20)
+            );
+        }
     }
     Ok(())
 }
+
+/// Gives `uid:gid` a passwd entry and home directory (so bash and tools that
+/// want `$HOME` work) and, when sudo is installed, passwordless sudo.
+fn create_user(rt: &dyn ContainerRuntime, container: &str, user: &str) -> Result<()> {
+    let (uid, gid) = user.split_once(':').unwrap_or((user, user));
+    // Images without shadow-utils get the entries written directly
+    let script = format!(
+        r##"set -e
+if ! getent group {gid} >/dev/null; then
+  groupadd -g {gid} {name} 2>/dev/null || echo "{name}:x:{gid}:" >> /etc/group
+fi
+if ! getent passwd {uid} >/dev/null; then
+  useradd -m -u {uid} -g {gid} -s /bin/bash {name} 2>/dev/null \
+    || echo "{name}:x:{uid}:{gid}::/home/{name}:/bin/bash" >> /etc/passwd
+fi
+home=$(getent passwd {uid} | cut -d: -f6)
+mkdir -p "$home" && chown {uid}:{gid} "$home"
+if [ -d /etc/sudoers.d ]; then
+  echo "#{uid} ALL=(ALL) NOPASSWD:ALL" > /etc/sudoers.d/{name} && chmod 440 /etc/sudoers.d/{name}
+fi"##,
+        name = SANDBOX_USER
+    );
+    let output = rt.exec(container, None, &script)?;
+    if !output.success {
+        bail!("Failed to create the sandbox user {}:\n{}", user, output.tail(10));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fake::FakeRuntime;
+    use super::*;
+
+    fn name_for(path: &std::path::Path) -> String {
+        container_name(&Config {
+            workspace_path: path.to_path_buf(),
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn container_names_have_a_readable_slug_and_a_path_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("My Project!.v2-final_cut_of_the_long_name");
+        fs::create_dir(&project).unwrap();
+
+        let name = name_for(&project);
+        let (slug, hash) = name.rsplit_once('_').unwrap();
+        assert_eq!(slug, "agerus_myprojectv2-final_cut_of");
+        assert_eq!(hash.len(), 10);
+        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+
+        // Nothing usable in the name: just the hash
+        let symbols = dir.path().join("!!!");
+        fs::create_dir(&symbols).unwrap();
+        let name = name_for(&symbols);
+        assert!(name.starts_with("agerus_") && name.len() == "agerus_".len() + 10);
+        let root = name_for(std::path::Path::new("/"));
+        assert!(root.starts_with("agerus_") && root.len() == "agerus_".len() + 10);
+        assert_ne!(name, root);
+    }
+
+    #[test]
+    fn container_names_follow_the_canonical_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("app");
+        fs::create_dir(&project).unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&project, &link).unwrap();
+
+        let name = name_for(&project);
+        assert_eq!(name_for(&dir.path().join("app/../app/.")), name);
+        assert_eq!(name_for(&link), name);
+        assert_ne!(name_for(&dir.path().join("other")), name);
+    }
+
+    #[test]
+    fn ensure_docker_env_creates_starts_or_reuses_the_container() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config {
+            workspace_path: dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        let container = container_name(&config);
+        let image = config.container.image.clone();
+        let fake = Arc::new(FakeRuntime::default());
+
+        // Missing: created, then the default toolchain is installed
+        ensure_docker_env(&config, fake.clone()).unwrap();
+        assert_eq!(
+            fake.take_calls(),
+            [
+                format!("rm {}", container),
+                format!("rm {}_proxy", container),
+                format!("network rm {}_net", container),
+                format!("run {} {}", container, image),
+                "exec cargo --version".to_string(),
+                "exec apt-get update".to_string(),
+            ]
+        );
+
+        // Running and set up from the same config: left alone
+        ensure_docker_env(&config, fake.clone()).unwrap();
+        assert_eq!(fake.take_calls(), Vec::<String>::new());
+
+        // Stopped: started again with its tools and files
+        fake.stop(&container);
+        ensure_docker_env(&config, fake.clone()).unwrap();
+        assert_eq!(fake.take_calls(), [format!("start {}", container)]);
+
+        // Set up from a different config: replaced
+        config.container.setup_commands.push("make deps".into());
+        ensure_docker_env(&config, fake.clone()).unwrap();
+        let calls = fake.take_calls();
+        assert_eq!(calls[0], format!("rm {}", container));
+        assert!(calls.contains(&format!("run {} {}", container, image)));
+        assert_eq!(calls.last().unwrap(), "exec make deps");
+    }
+
+    #[test]
+    fn allowlisted_sandboxes_are_provisioned_before_the_allowlist_applies() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config {
+            workspace_path: dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        config.container.network = NetworkMode::Allowlist;
+        config.container.allowed_hosts = vec!["crates.io".into()];
+        let container = container_name(&config);
+        let proxy = network::proxy_name(&container);
+        let net = network::network_name(&container);
+        let fake = Arc::new(FakeRuntime::default());
+
+        ensure_docker_env(&config, fake.clone()).unwrap();
+        let conf_path = network::squid_conf_path(&proxy).unwrap();
+        let conf = fs::read_to_string(&conf_path).unwrap();
+        let _ = fs::remove_file(&conf_path);
+
+        // The proxy admits any host while the toolchain installs, then only the allowlist
+        assert_eq!(
+            fake.take_calls(),
+            [
+                format!("rm {}", container),
+                format!("network create {}", net),
+                format!("rm {}", proxy),
+                format!("run {} docker.io/ubuntu/squid:latest", proxy),
+                format!("network connect {} {}", net, proxy),
+                format!("run {} {}", container, config.container.image),
+                "exec cargo --version".to_string(),
+                "exec apt-get update".to_string(),
+                format!("restart {}", proxy),
+            ]
+        );
+        assert!(conf.contains("acl allowed dstdomain crates.io\n"));
+        assert!(conf.contains("http_access allow allowed\n"));
+        assert!(!conf.contains("http_access allow all\n"));
+    }
+
+    #[test]
+    fn rootful_sandboxes_run_commands_as_the_host_user() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config {
+            workspace_path: dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        config.container.setup_commands.push("make deps".into());
+        let container = container_name(&config);
+        let fake = Arc::new(FakeRuntime {
+            rootful: true,
+            ..FakeRuntime::default()
+        });
+
+        // Root on the host already owns what root writes, so gets no user
+        let user = exec_user(&config, fake.as_ref());
+        assert_eq!(user, host_ids().map(|(uid, gid)| format!("{}:{}", uid, gid)));
+
+        ensure_docker_env(&config, fake.clone()).unwrap();
+        let calls = fake.take_calls();
+        let (_, args, _) = Sandbox::new(&config, fake).invocation(&[], &["ls"]);
+        match user {
+            Some(user) => {
+                assert_eq!(
+                    calls[calls.len() - 2..],
+                    ["exec set -e".to_string(), format!("exec --user {} make deps", user)]
+                );
+                assert_eq!(args, ["exec", "-w", "/workspace", "--user", &user, &container, "ls"]);
+            }
+            None => {
+                assert_eq!(calls.last().unwrap(), "exec make deps");
+                assert_eq!(args, ["exec", "-w", "/workspace", &container, "ls"]);
+            }
+        }
+    }
+
+    #[test]
+    fn provision_creates_the_user_before_setup_commands_unless_read_only() {
+        let fake = FakeRuntime::default();
+        let mut config = ContainerConfig::default();
+        config.setup_commands.push("make deps".into());
+
+        provision(&fake, "box", &[], &config, Some("1000:1000")).unwrap();
+        assert_eq!(fake.take_calls(), ["exec set -e", "exec --user 1000:1000 make deps"]);
+
+        // A read-only root can't take the user; the image has to bring it
+        config.read_only = true;
+        provision(&fake, "box", &[], &config, Some("1000:1000")).unwrap();
+        assert_eq!(fake.take_calls(), ["exec --user 1000:1000 make deps"]);
+    }
+
+    #[test]
+    fn toolchains_resolve_by_name() {
+        let mut config = Config::default();
+        assert!(resolve_toolchains(&config).unwrap().is_none());
+
+        config.container.toolchains = Some(vec!["Rust".into(), "node".into()]);
+        let names: Vec<&str> = resolve_toolchains(&config)
+            .unwrap()
+            .unwrap()
+            .iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(names, ["rust", "node"]);
+
+        config.container.toolchains = Some(Vec::new());
+        assert!(resolve_toolchains(&config).unwrap().unwrap().is_empty());
+
+        config.container.toolchains = Some(vec!["python".into(), "cobol".into()]);
+        let err = resolve_toolchains(&config).err().unwrap().to_string();
+        assert_eq!(err, "Unknown toolchain 'cobol' in [container] (known: rust, python, node, go)");
+    }
+
+    #[test]
+    fn limit_args_cover_each_limit() {
+        let mut config = ContainerConfig::default();
+        assert_eq!(limit_args(&config), Vec::<String>::new());
+
+        config.cpus = Some(1.5);
+        config.memory = Some("2g".into());
+        config.pids_limit = Some(256);
+        config.read_only = true;
+        assert_eq!(
+            limit_args(&config),
+            [
+                "--cpus", "1.5", "--memory", "2g", "--memory-swap", "2g", "--pids-limit", "256",
+                "--read-only", "--tmpfs", "/tmp", "--tmpfs", "/run",
+            ]
+        );
+    }
+}
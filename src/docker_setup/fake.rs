@@ -0,0 +1,141 @@
+use super::runtime::{ContainerRuntime, ContainerState, ContainerSummary, ExecOutput, RunSpec};
+use super::SETUP_LABEL;
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Mutex;
+
+// An in-memory `ContainerRuntime` for the setup tests; nothing here runs a CLI.
+
+/// Keeps containers, images and networks in memory and records what setup asked of them.
+#[derive(Default)]
+pub(super) struct FakeRuntime {
+    /// Name to state and setup label
+    pub(super) containers: Mutex<HashMap<String, (String, String)>>,
+    pub(super) images: Mutex<HashSet<String>>,
+    pub(super) networks: Mutex<HashSet<String>>,
+    pub(super) calls: Mutex<Vec<String>>,
+    /// Container root is real root, so commands run as the host user
+    pub(super) rootful: bool,
+}
+
+impl FakeRuntime {
+    fn record(&self, call: String) {
+        self.calls.lock().unwrap().push(call);
+    }
+
+    pub(super) fn take_calls(&self) -> Vec<String> {
+        std::mem::take(&mut *self.calls.lock().unwrap())
+    }
+
+    pub(super) fn stop(&self, name: &str) {
+        if let Some((state, _)) = self.containers.lock().unwrap().get_mut(name) {
+            *state = "exited".into();
+        }
+    }
+}
+
+impl ContainerRuntime for FakeRuntime {
+    fn program(&self) -> &str {
+        "false"
+    }
+
+    fn rootless(&self) -> bool {
+        !self.rootful
+    }
+
+    fn container_state(&self, name: &str, _label: &str) -> Result<Option<ContainerState>> {
+        Ok(self
+            .containers
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|(state, label)| ContainerState {
+                state: state.clone(),
+                label: label.clone(),
+            }))
+    }
+
+    fn start(&self, name: &str) -> Result<()> {
+        self.record(format!("start {}", name));
+        if let Some((state, _)) = self.containers.lock().unwrap().get_mut(name) {
+            *state = "running".into();
+        }
+        Ok(())
+    }
+
+    fn restart(&self, name: &str) -> Result<()> {
+        self.record(format!("restart {}", name));
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        self.record(format!("rm {}", name));
+        self.containers.lock().unwrap().remove(name);
+        Ok(())
+    }
+
+    fn image_exists(&self, reference: &str) -> bool {
+        self.images.lock().unwrap().contains(reference)
+    }
+
+    fn build_image(&self, tag: &str, _dockerfile: &Path, context: &Path) -> Result<()> {
+        self.record(format!("build {} {}", tag, context.display()));
+        self.images.lock().unwrap().insert(tag.into());
+        Ok(())
+    }
+
+    fn network_exists(&self, name: &str) -> bool {
+        self.networks.lock().unwrap().contains(name)
+    }
+
+    fn create_network(&self, name: &str, _label: (&str, &str)) -> Result<()> {
+        self.record(format!("network create {}", name));
+        self.networks.lock().unwrap().insert(name.into());
+        Ok(())
+    }
+
+    fn connect_network(&self, network: &str, container: &str) -> Result<()> {
+        self.record(format!("network connect {} {}", network, container));
+        Ok(())
+    }
+
+    fn remove_network(&self, name: &str) -> Result<()> {
+        self.record(format!("network rm {}", name));
+        self.networks.lock().unwrap().remove(name);
+        Ok(())
+    }
+
+    fn run_container(&self, spec: &RunSpec) -> Result<()> {
+        self.record(format!("run {} {}", spec.name, spec.image));
+        let label = spec
+            .labels
+            .iter()
+            .find(|(key, _)| *key == SETUP_LABEL)
+            .map(|(_, value)| value.to_string())
+            .unwrap_or_default();
+        self.containers
+            .lock()
+            .unwrap()
+            .insert(spec.name.into(), ("running".into(), label));
+        Ok(())
+    }
+
+    fn list_containers(&self, _label: &str) -> Result<Vec<ContainerSummary>> {
+        Ok(Vec::new())
+    }
+
+    fn exec(&self, _container: &str, user: Option<&str>, script: &str) -> Result<ExecOutput> {
+        let first = script.lines().next().unwrap_or_default();
+        let first = first.split(" &&").next().unwrap_or_default();
+        match user {
+            Some(user) => self.record(format!("exec --user {} {}", user, first)),
+            None => self.record(format!("exec {}", first)),
+        }
+        // No toolchain is installed yet; everything else works
+        Ok(ExecOutput {
+            success: script != "cargo --version",
+            log: String::new(),
+        })
+    }
+}
@@ -0,0 +1,141 @@
+use super::runtime::ContainerRuntime;
+use crate::config::Config;
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Which image the sandbox runs: the configured one, or one built from the
+// project's Dockerfile and tagged with a hash of its content. The hash is the
+// cache key, so files the Dockerfile copies in don't trigger a rebuild; editing
+// the Dockerfile (or removing the image) does.
+
+/// Picked up when `container.dockerfile` is unset
+const PROJECT_DOCKERFILE: &str = ".agerus/Dockerfile";
+
+/// Repository for images built from project Dockerfiles
+const BUILT_IMAGE_REPO: &str = "agerus-sandbox";
+
+pub struct SandboxImage {
+    pub reference: String,
+    /// Built from a project Dockerfile rather than pulled
+    pub from_dockerfile: bool,
+}
+
+pub fn resolve(rt: &dyn ContainerRuntime, config: &Config, workspace: &Path) -> Result<SandboxImage> {
+    let Some(dockerfile) = project_dockerfile(config, workspace)? else {
+        return Ok(SandboxImage {
+            reference: config.container.image.clone(),
+            from_dockerfile: false,
+        });
+    };
+
+    let content = fs::read(&dockerfile)
+        .with_context(|| format!("Failed to read {}", dockerfile.display()))?;
+    let hash = format!("{:x}", Sha256::digest(&content));
+    let reference = format!("{}:{}", BUILT_IMAGE_REPO, &hash[..16]);
+
+    // Same Dockerfile, same tag: reuse the image instead of rebuilding.
+    // The context is the Dockerfile's directory (`.agerus/` by default), not the
+    // whole workspace with its build output and secrets.
+    if !rt.image_exists(&reference) {
+        let context = dockerfile.parent().unwrap_or(workspace);
+        rt.build_image(&reference, &dockerfile, context)?;
+    }
+
+    Ok(SandboxImage {
+        reference,
+        from_dockerfile: true,
+    })
+}
+
+fn project_dockerfile(config: &Config, workspace: &Path) -> Result<Option<PathBuf>> {
+    match &config.container.dockerfile {
+        Some(path) => {
+            let path = workspace.join(path);
+            if !path.is_file() {
+                bail!("Dockerfile from [container] not found: {}", path.display());
+            }
+            Ok(Some(path))
+        }
+        None => Ok(Some(workspace.join(PROJECT_DOCKERFILE)).filter(|p| p.is_file())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fake::FakeRuntime;
+    use super::*;
+
+    fn workspace(dockerfile: Option<(&str, &str)>) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        if let Some((path, content)) = dockerfile {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn without_a_dockerfile_the_configured_image_runs() {
+        let dir = workspace(None);
+        let config = Config::default();
+        let fake = FakeRuntime::default();
+
+        let image = resolve(&fake, &config, dir.path()).unwrap();
+        assert_eq!(image.reference, config.container.image);
+        assert!(!image.from_dockerfile);
+        assert_eq!(fake.take_calls(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn the_project_dockerfile_is_built_once_per_content() {
+        let dir = workspace(Some((PROJECT_DOCKERFILE, "FROM ubuntu:24.04\n")));
+        let context = dir.path().join(".agerus");
+        let config = Config::default();
+        let fake = FakeRuntime::default();
+
+        let image = resolve(&fake, &config, dir.path()).unwrap();
+        assert!(image.from_dockerfile);
+        let (repo, hash) = image.reference.split_once(':').unwrap();
+        assert_eq!(repo, BUILT_IMAGE_REPO);
+        assert_eq!(hash.len(), 16);
+        assert_eq!(
+            fake.take_calls(),
+            [format!("build {} {}", image.reference, context.display())]
+        );
+
+        // Unchanged: the built image is reused
+        let again = resolve(&fake, &config, dir.path()).unwrap();
+        assert_eq!(again.reference, image.reference);
+        assert_eq!(fake.take_calls(), Vec::<String>::new());
+
+        // Changed: a new tag, built again
+        fs::write(context.join("Dockerfile"), "FROM debian:12\n").unwrap();
+        let changed = resolve(&fake, &config, dir.path()).unwrap();
+        assert_ne!(changed.reference, image.reference);
+        assert_eq!(
+            fake.take_calls(),
+            [format!("build {} {}", changed.reference, context.display())]
+        );
+    }
+
+    #[test]
+    fn a_configured_dockerfile_is_built_from_its_own_directory() {
+        let dir = workspace(Some(("docker/sandbox.Dockerfile", "FROM ubuntu:24.04\n")));
+        let mut config = Config::default();
+        config.container.dockerfile = Some("docker/sandbox.Dockerfile".into());
+        let fake = FakeRuntime::default();
+
+        let image = resolve(&fake, &config, dir.path()).unwrap();
+        assert_eq!(
+            fake.take_calls(),
+            [format!("build {} {}", image.reference, dir.path().join("docker").display())]
+        );
+
+        config.container.dockerfile = Some("missing/Dockerfile".into());
+        let err = resolve(&fake, &config, dir.path()).err().unwrap().to_string();
+        assert!(err.starts_with("Dockerfile from [container] not found"), "{}", err);
+    }
+}
@@ -0,0 +1,108 @@
+use super::runtime::ContainerRuntime;
+use super::{container_name, network, WORKSPACE_LABEL};
+use crate::config::Config;
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::sync::Arc;
+
+// `agerus sandbox ls|rm|prune`: the per-workspace containers, from the command line.
+
+const USAGE: &str = "Usage: agerus sandbox [ls | rm [workspace|name] | prune]";
+
+struct Sandbox {
+    name: String,
+    state: String,
+    status: String,
+    workspace: String,
+}
+
+impl Sandbox {
+    fn workspace_exists(&self) -> bool {
+        Path::new(&self.workspace).is_dir()
+    }
+}
+
+pub fn run(args: &[String], config: &Config, rt: Arc<dyn ContainerRuntime>) -> Result<()> {
+    let rt = rt.as_ref();
+    match args.first().map(String::as_str) {
+        None | Some("ls") => list(rt),
+        Some("rm") => remove(rt, args.get(1).map(String::as_str), config),
+        Some("prune") => prune(rt),
+        Some(other) => bail!("Unknown sandbox command '{}'. {}", other, USAGE),
+    }
+}
+
+fn sandboxes(rt: &dyn ContainerRuntime) -> Result<Vec<Sandbox>> {
+    Ok(rt
+        .list_containers(WORKSPACE_LABEL)?
+        .into_iter()
+        .map(|c| Sandbox {
+            name: c.name,
+            state: c.state,
+            status: c.status,
+            workspace: c.label,
+        })
+        .collect())
+}
+
+fn list(rt: &dyn ContainerRuntime) -> Result<()> {
+    let all = sandboxes(rt)?;
+    if all.is_empty() {
+        println!("No Agerus sandboxes.");
+        return Ok(());
+    }
+    let name_width = all.iter().map(|s| s.name.len()).max().unwrap_or(0).max(4);
+    let status_width = all.iter().map(|s| s.status.len()).max().unwrap_or(0).max(6);
+    println!("{:name_width$}  {:status_width$}  WORKSPACE", "NAME", "STATUS");
+    for s in &all {
+        let missing = if s.workspace_exists() { "" } else { "  (missing)" };
+        println!("{:name_width$}  {:status_width$}  {}{}", s.name, s.status, s.workspace, missing);
+    }
+    Ok(())
+}
+
+/// `target` is a name from `ls` or a workspace path; the configured workspace by default.
+fn remove(rt: &dyn ContainerRuntime, target: Option<&str>, config: &Config) -> Result<()> {
+    let all = sandboxes(rt)?;
+    let name = match target {
+        Some(name) if all.iter().any(|s| s.name == name) => name.to_string(),
+        Some(path) => {
+            let mut config = config.clone();
+            config.workspace_path = path.into();
+            container_name(&config)
+        }
+        None => container_name(config),
+    };
+    if !all.iter().any(|s| s.name == name) {
+        bail!("No sandbox named '{}'. See `agerus sandbox ls`.", name);
+    }
+    remove_container(rt, &name)?;
+    println!("Removed {}", name);
+    Ok(())
+}
+
+/// Removes stopped sandboxes and those whose workspace no longer exists.
+fn prune(rt: &dyn ContainerRuntime) -> Result<()> {
+    let mut removed = 0;
+    for s in sandboxes(rt)? {
+        if s.state == "running" && s.workspace_exists() {
+            continue;
+        }
+        remove_container(rt, &s.name)?;
+        let reason = if s.workspace_exists() { "stopped" } else { "workspace missing" };
+        println!("Removed {} ({})", s.name, reason);
+        removed += 1;
+    }
+    println!(
+        "Pruned {} sandbox{}.",
+        removed,
+        if removed == 1 { "" } else { "es" }
+    );
+    Ok(())
+}
+
+fn remove_container(rt: &dyn ContainerRuntime, name: &str) -> Result<()> {
+    rt.remove(name)?;
+    network::remove(rt, name);
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use super::runtime::{ContainerRuntime, RunSpec};
+use crate::config::{ContainerConfig, NetworkMode};
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::PathBuf;
+
+// Network policy for the sandbox. `allowlist` puts the sandbox on an internal
+// network with no route out; its only way to the internet is a squid proxy
+// that sits on both networks and admits the allowed domains.
+
+/// Fully qualified, so Podman doesn't have to resolve a short name
+const PROXY_IMAGE: &str = "docker.io/ubuntu/squid:latest";
+const PROXY_PORT: u16 = 3128;
+
+/// Marks the proxy with the sandbox it serves
+const PROXY_LABEL: &str = "agerus.proxy-for";
+
+/// What the proxy lets through.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ProxyAccess {
+    /// Any host, while a new sandbox is provisioned: apt mirrors and toolchain
+    /// installers are rarely in `allowed_hosts`, and the agent isn't running yet
+    Setup,
+    /// Only `allowed_hosts`
+    Allowlist,
+}
+
+pub fn proxy_name(container: &str) -> String {
+    format!("{}_proxy", container)
+}
+
+pub fn network_name(container: &str) -> String {
+    format!("{}_net", container)
+}
+
+/// `docker run` arguments that put the sandbox under `config.network`.
+pub fn run_args(container: &str, config: &ContainerConfig) -> Vec<String> {
+    match config.network {
+        NetworkMode::Bridge => Vec::new(),
+        NetworkMode::None => vec!["--network".into(), "none".into()],
+        NetworkMode::Allowlist => {
+            let proxy = format!("http://{}:{}", proxy_name(container), PROXY_PORT);
+            let mut args = vec!["--network".into(), network_name(container)];
+            // curl, pip, npm and apt read the lowercase ones, some tools only the uppercase
+            for var in ["http_proxy", "https_proxy", "HTTP_PROXY", "HTTPS_PROXY"] {
+                args.push("-e".into());
+                args.push(format!("{}={}", var, proxy));
+            }
+            for var in ["no_proxy", "NO_PROXY"] {
+                args.push("-e".into());
+                args.push(format!("{}=localhost,127.0.0.1", var));
+            }
+            args
+        }
+    }
+}
+
+/// Creates the internal network and gets the proxy running under `allowlist`,
+/// admitting what `access` says. `recreate` replaces the proxy, or drops one
+/// left over from an earlier config; a running proxy whose rules changed is restarted.
+pub fn ensure_proxy(
+    rt: &dyn ContainerRuntime,
+    container: &str,
+    config: &ContainerConfig,
+    recreate: bool,
+    access: ProxyAccess,
+) -> Result<()> {
+    if config.network != NetworkMode::Allowlist {
+        if recreate {
+            remove(rt, container);
+        }
+        return Ok(());
+    }
+
+    let network = network_name(container);
+    if !rt.network_exists(&network) {
+        rt.create_network(&network, (PROXY_LABEL, container))?;
+    }
+
+    let proxy = proxy_name(container);
+    let state = rt.container_state(&proxy, PROXY_LABEL)?;
+    let hosts = match access {
+        ProxyAccess::Setup => None,
+        ProxyAccess::Allowlist => Some(config.allowed_hosts.as_slice()),
+    };
+    let (conf, changed) = write_squid_conf(&proxy, hosts)?;
+
+    if recreate || state.is_none() {
+        let _ = rt.remove(&proxy);
+
+        let mount = format!("{}:/etc/squid/squid.conf:ro", conf.to_string_lossy());
+        rt.run_container(&RunSpec {
+            name: &proxy,
+            image: PROXY_IMAGE,
+            labels: &[(PROXY_LABEL, container)],
+            args: &["-v".to_string(), mount],
+            command: &[],
+        })?;
+
+        // Started on the default bridge for its own way out, then joined to the sandbox's network
+        if let Err(e) = rt.connect_network(&network, &proxy) {
+            let _ = rt.remove(&proxy);
+            return Err(e);
+        }
+    } else if state.is_some_and(|s| s.state != "running") {
+        rt.start(&proxy)?;
+    } else if changed {
+        // squid only reads its config at startup
+        rt.restart(&proxy)?;
+    }
+    Ok(())
+}
+
+/// Removes the proxy and network that belong to `container`, if there are any.
+pub fn remove(rt: &dyn ContainerRuntime, container: &str) {
+    let _ = rt.remove(&proxy_name(container));
+    let _ = rt.remove_network(&network_name(container));
+}
+
+/// Where the config for `proxy` is kept; mounted into the proxy container.
+pub(super) fn squid_conf_path(proxy: &str) -> Result<PathBuf> {
+    let dir = dirs::cache_dir()
+        .ok_or_else(|| anyhow!("Could not find a cache directory for the proxy config"))?
+        .join("agerus")
+        .join("proxy");
+    Ok(dir.join(format!("{}.conf", proxy)))
+}
+
+/// Writes the proxy's config, admitting only `hosts` or, for `None`, any host.
+/// Also says whether it differs from what was there before.
+fn write_squid_conf(proxy: &str, hosts: Option<&[String]>) -> Result<(PathBuf, bool)> {
+    let path = squid_conf_path(proxy)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let conf = squid_conf(hosts);
+    let changed = !fs::read_to_string(&path).is_ok_and(|old| old == conf);
+    fs::write(&path, conf).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok((path, changed))
+}
+
+fn squid_conf(hosts: Option<&[String]>) -> String {
+    let mut conf = format!("http_port {}\n", PROXY_PORT);
+    if let Some(hosts) = hosts.filter(|h| !h.is_empty()) {
+        conf.push_str(&format!("acl allowed dstdomain {}\n", hosts.join(" ")));
+    }
+    conf.push_str(
+        "acl SSL_ports port 443\n\
+         acl CONNECT method CONNECT\n\
+         http_access deny CONNECT !SSL_ports\n",
+    );
+    match hosts {
+        None => conf.push_str("http_access allow all\n"),
+        Some(hosts) if !hosts.is_empty() => conf.push_str("http_access allow allowed\n"),
+        Some(_) => {}
+    }
+    conf.push_str("http_access deny all\ncache deny all\n");
+    conf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = "acl SSL_ports port 443\n\
+                         acl CONNECT method CONNECT\n\
+                         http_access deny CONNECT !SSL_ports\n";
+
+    #[test]
+    fn squid_conf_admits_the_allowed_hosts() {
+        let hosts = vec!["crates.io".to_string(), ".github.com".to_string()];
+        assert_eq!(
+            squid_conf(Some(&hosts)),
+            format!(
+                "http_port 3128\nacl allowed dstdomain crates.io .github.com\n{}\
+                 http_access allow allowed\nhttp_access deny all\ncache deny all\n",
+                RULES
+            )
+        );
+    }
+
+    #[test]
+    fn squid_conf_with_no_allowed_hosts_denies_everything() {
+        assert_eq!(
+            squid_conf(Some(&[])),
+            format!("http_port 3128\n{}http_access deny all\ncache deny all\n", RULES)
+        );
+    }
+
+    #[test]
+    fn squid_conf_for_setup_admits_any_host() {
+        assert_eq!(
+            squid_conf(None),
+            format!(
+                "http_port 3128\n{}http_access allow all\nhttp_access deny all\ncache deny all\n",
+                RULES
+            )
+        );
+    }
+}
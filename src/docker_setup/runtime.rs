@@ -0,0 +1,440 @@
+use crate::config::{Config, RuntimeKind};
+use anyhow::{anyhow, bail, Result};
+use std::path::Path;
+use std::process::{Command, Output};
+use std::sync::{Arc, OnceLock};
+
+// The container runtime behind the sandbox. Setup and teardown go through
+// `ContainerRuntime`, which Docker and Podman implement via their shared CLI.
+
+/// One container as `ps -a` reports it.
+pub struct ContainerState {
+    /// "running", "exited", "created", ...
+    pub state: String,
+    /// Value of the label that was asked for, empty when unset
+    pub label: String,
+}
+
+/// A container found by `list_containers`.
+pub struct ContainerSummary {
+    pub name: String,
+    pub state: String,
+    /// Human-readable, e.g. "Up 2 hours"
+    pub status: String,
+    /// Value of the label that was asked for
+    pub label: String,
+}
+
+/// What `run_container` starts, detached.
+pub struct RunSpec<'a> {
+    pub name: &'a str,
+    pub image: &'a str,
+    pub labels: &'a [(&'a str, &'a str)],
+    /// Mounts, limits, network and the like
+    pub args: &'a [String],
+    /// Replaces the image's command unless empty
+    pub command: &'a [&'a str],
+}
+
+/// How a script run with `exec` went.
+pub struct ExecOutput {
+    pub success: bool,
+    /// stderr, or stdout when that is empty
+    pub log: String,
+}
+
+impl ExecOutput {
+    fn from_output(output: &Output) -> Self {
+        let stream = if output.stderr.is_empty() { &output.stdout } else { &output.stderr };
+        Self {
+            success: output.status.success(),
+            log: String::from_utf8_lossy(stream).into_owned(),
+        }
+    }
+
+    /// Last `lines` lines of the log, for error messages.
+    pub fn tail(&self, lines: usize) -> String {
+        let all: Vec<&str> = self.log.trim_end().lines().collect();
+        all[all.len().saturating_sub(lines)..].join("\n")
+    }
+}
+
+/// Last `lines` lines of a failed command's stderr (stdout if that is empty), for error messages.
+fn tail(output: &Output, lines: usize) -> String {
+    ExecOutput::from_output(output).tail(lines)
+}
+
+/// What setup, teardown and the sandbox need from a container runtime.
+/// `CliRuntime` implements it by running the runtime's CLI.
+pub trait ContainerRuntime: Send + Sync {
+    /// The CLI that runs commands in the sandbox
+    fn program(&self) -> &str;
+
+    /// Rootless runtimes map container root to the host user, so files root
+    /// writes into the workspace are already the user's
+    fn rootless(&self) -> bool;
+
+    fn command(&self) -> Command {
+        let mut cmd = Command::new(self.program());
+        // Host cwd "/" avoids the OCI namespace path issue, see ShellSession
+        cmd.current_dir("/");
+        cmd
+    }
+
+    fn async_command(&self) -> tokio::process::Command {
+        let mut cmd = tokio::process::Command::new(self.program());
+        cmd.current_dir("/");
+        cmd
+    }
+
+    /// `None` when there is no container called `name`.
+    fn container_state(&self, name: &str, label: &str) -> Result<Option<ContainerState>>;
+
+    fn start(&self, name: &str) -> Result<()>;
+
+    fn restart(&self, name: &str) -> Result<()>;
+
+    /// Force-removes `name`, running or not.
+    fn remove(&self, name: &str) -> Result<()>;
+
+    fn run_container(&self, spec: &RunSpec) -> Result<()>;
+
+    /// Runs `script` in a login shell in `container` (login so rustup's PATH
+    /// applies), as `user` or root. Errs only when the runtime can't be run.
+    fn exec(&self, container: &str, user: Option<&str>, script: &str) -> Result<ExecOutput>;
+
+    fn image_exists(&self, reference: &str) -> bool;
+
+    fn build_image(&self, tag: &str, dockerfile: &Path, context: &Path) -> Result<()>;
+
+    fn network_exists(&self, name: &str) -> bool;
+
+    /// Creates an internal network (no route out) carrying `label`.
+    fn create_network(&self, name: &str, label: (&str, &str)) -> Result<()>;
+
+    fn connect_network(&self, network: &str, container: &str) -> Result<()>;
+
+    fn remove_network(&self, name: &str) -> Result<()>;
+
+    /// Containers, stopped ones included, that carry `label`, with its value.
+    fn list_containers(&self, label: &str) -> Result<Vec<ContainerSummary>>;
+}
+
+/// A Docker-compatible CLI. Docker and Podman take the same commands and
+/// flags; implementations cover where their filters and output differ.
+pub trait CliRuntime: Send + Sync {
+    /// For messages, e.g. "Docker"
+    fn name(&self) -> &'static str;
+
+    /// The CLI every command runs through
+    fn program(&self) -> &str;
+
+    /// `ps --filter` value that matches exactly the container called `name`
+    fn name_filter(&self, name: &str) -> String;
+
+    /// `ps --format` template that prints the value of `label`
+    fn label_template(&self, label: &str) -> String;
+
+    /// See `ContainerRuntime::rootless`
+    fn rootless(&self) -> bool;
+
+    /// Extra `run` flags the sandbox container needs under this runtime
+    fn run_args(&self) -> &'static [&'static str] {
+        &[]
+    }
+}
+
+impl<T: CliRuntime> ContainerRuntime for T {
+    fn program(&self) -> &str {
+        CliRuntime::program(self)
+    }
+
+    fn rootless(&self) -> bool {
+        CliRuntime::rootless(self)
+    }
+
+    fn container_state(&self, name: &str, label: &str) -> Result<Option<ContainerState>> {
+        let output = self
+            .command()
+            .args(["ps", "-a", "--filter", &self.name_filter(name), "--format"])
+            .arg(format!("{{{{.State}}}}\t{}", self.label_template(label)))
+            .output()
+            .map_err(|e| {
+                anyhow!(
+                    "Failed to run {}: {}. Is {} installed? `runtime` in [container] picks the runtime.",
+                    CliRuntime::program(self),
+                    e,
+                    self.name()
+                )
+            })?;
+        if !output.status.success() {
+            bail!("{} ps failed:\n{}", CliRuntime::program(self), tail(&output, 10));
+        }
+        let text = String::from_utf8_lossy(&output.stdout);
+        Ok(text.lines().find(|l| !l.trim().is_empty()).map(|line| {
+            let (state, label) = line.split_once('\t').unwrap_or((line, ""));
+            ContainerState {
+                state: state.trim().to_string(),
+                label: label.trim().to_string(),
+            }
+        }))
+    }
+
+    fn start(&self, name: &str) -> Result<()> {
+        let output = self.command().args(["start", name]).output()?;
+        if !output.status.success() {
+            bail!("Failed to start container '{}':\n{}", name, tail(&output, 10));
+        }
+        Ok(())
+    }
+
+    fn restart(&self, name: &str) -> Result<()> {
+        let output = self.command().args(["restart", name]).output()?;
+        if !output.status.success() {
+            bail!("Failed to restart container '{}':\n{}", name, tail(&output, 10));
+        }
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        let output = self.command().args(["rm", "-f", name]).output()?;
+        if !output.status.success() {
+            bail!("Failed to remove {}:\n{}", name, tail(&output, 10));
+        }
+        Ok(())
+    }
+
+    fn run_container(&self, spec: &RunSpec) -> Result<()> {
+        let mut cmd = self.command();
+        cmd.args(["run", "-d", "--name", spec.name]);
+        for (key, value) in spec.labels {
+            cmd.arg("--label").arg(format!("{}={}", key, value));
+        }
+        // .output() rather than .status(), so the container ID doesn't end up in the TUI
+        let output = cmd
+            .args(spec.args)
+            .args(self.run_args())
+            .arg(spec.image)
+            .args(spec.command)
+            .output()?;
+        if !output.status.success() {
+            bail!(
+                "Failed to start {} container '{}' from '{}':\n{}",
+                self.name(),
+                spec.name,
+                spec.image,
+                tail(&output, 10)
+            );
+        }
+        Ok(())
+    }
+
+    fn exec(&self, container: &str, user: Option<&str>, script: &str) -> Result<ExecOutput> {
+        let mut cmd = self.command();
+        cmd.args(["exec", "-e", "DEBIAN_FRONTEND=noninteractive"]);
+        if let Some(user) = user {
+            cmd.args(["--user", user]);
+        }
+        let output = cmd.args([container, "bash", "-l", "-c", script]).output()?;
+        Ok(ExecOutput::from_output(&output))
+    }
+
+    fn image_exists(&self, reference: &str) -> bool {
+        self.command()
+            .args(["image", "inspect", reference])
+            .output()
+            .is_ok_and(|out| out.status.success())
+    }
+
+    fn build_image(&self, tag: &str, dockerfile: &Path, context: &Path) -> Result<()> {
+        let output = self
+            .command()
+            .args(["build", "-t", tag, "-f"])
+            .arg(dockerfile)
+            .arg(context)
+            .output()?;
+        if !output.status.success() {
+            bail!(
+                "Failed to build the sandbox image from {}:\n{}",
+                dockerfile.display(),
+                tail(&output, 20)
+            );
+        }
+        Ok(())
+    }
+
+    fn network_exists(&self, name: &str) -> bool {
+        self.command()
+            .args(["network", "inspect", name])
+            .output()
+            .is_ok_and(|out| out.status.success())
+    }
+
+    fn create_network(&self, name: &str, label: (&str, &str)) -> Result<()> {
+        let output = self
+            .command()
+            .args(["network", "create", "--internal", "--label"])
+            .arg(format!("{}={}", label.0, label.1))
+            .arg(name)
+            .output()?;
+        if !output.status.success() {
+            bail!("Failed to create network '{}':\n{}", name, tail(&output, 10));
+        }
+        Ok(())
+    }
+
+    fn connect_network(&self, network: &str, container: &str) -> Result<()> {
+        let output = self
+            .command()
+            .args(["network", "connect", network, container])
+            .output()?;
+        if !output.status.success() {
+            bail!("Failed to attach '{}' to '{}':\n{}", container, network, tail(&output, 10));
+        }
+        Ok(())
+    }
+
+    fn remove_network(&self, name: &str) -> Result<()> {
+        let output = self.command().args(["network", "rm", name]).output()?;
+        if !output.status.success() {
+            bail!("Failed to remove network '{}':\n{}", name, tail(&output, 10));
+        }
+        Ok(())
+    }
+
+    fn list_containers(&self, label: &str) -> Result<Vec<ContainerSummary>> {
+        let output = self
+            .command()
+            .args(["ps", "-a", "--filter", &format!("label={}", label), "--format"])
+            .arg(format!(
+                "{{{{.Names}}}}\t{{{{.State}}}}\t{{{{.Status}}}}\t{}",
+                self.label_template(label)
+            ))
+            .output()
+            .map_err(|e| anyhow!("Failed to run {}: {}", CliRuntime::program(self), e))?;
+        if !output.status.success() {
+            bail!("{} ps failed:\n{}", CliRuntime::program(self), tail(&output, 10));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(4, '\t');
+                Some(ContainerSummary {
+                    name: fields.next()?.to_string(),
+                    state: fields.next()?.to_string(),
+                    status: fields.next()?.to_string(),
+                    label: fields.next()?.to_string(),
+                })
+            })
+            .collect())
+    }
+}
+
+#[derive(Default)]
+pub struct Docker {
+    rootless: OnceLock<bool>,
+}
+
+impl CliRuntime for Docker {
+    fn name(&self) -> &'static str {
+        "Docker"
+    }
+
+    fn program(&self) -> &str {
+        "docker"
+    }
+
+    fn name_filter(&self, name: &str) -> String {
+        // Docker matches the filter against "/name"
+        format!("name=^/{}$", name)
+    }
+
+    fn label_template(&self, label: &str) -> String {
+        format!("{{{{.Label \"{}\"}}}}", label)
+    }
+
+    fn rootless(&self) -> bool {
+        *self.rootless.get_or_init(|| {
+            info(self, "{{.SecurityOptions}}").is_some_and(|out| out.contains("rootless"))
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct Podman {
+    rootless: OnceLock<bool>,
+}
+
+impl CliRuntime for Podman {
+    fn name(&self) -> &'static str {
+        "Podman"
+    }
+
+    fn program(&self) -> &str {
+        "podman"
+    }
+
+    fn name_filter(&self, name: &str) -> String {
+        format!("name=^{}$", name)
+    }
+
+    fn label_template(&self, label: &str) -> String {
+        // Labels is a map in Podman's ps output; Docker's has no index form
+        format!("{{{{index .Labels \"{}\"}}}}", label)
+    }
+
+    fn rootless(&self) -> bool {
+        *self
+            .rootless
+            .get_or_init(|| info(self, "{{.Host.Security.Rootless}}").is_some_and(|out| out.trim() == "true"))
+    }
+
+    fn run_args(&self) -> &'static [&'static str] {
+        // SELinux hosts (Fedora, RHEL) deny the workspace mount otherwise; unlike
+        // a `:z` mount this leaves the labels on the host's files alone
+        &["--security-opt", "label=disable"]
+    }
+}
+
+/// The runtime `[container] runtime` asks for, detecting one under `auto`.
+/// Falls back to Docker when neither is found, so the first command reports what's missing.
+pub fn select(config: &Config) -> Arc<dyn ContainerRuntime> {
+    match config.container.runtime {
+        RuntimeKind::Docker => Arc::new(Docker::default()),
+        RuntimeKind::Podman => Arc::new(Podman::default()),
+        RuntimeKind::Auto => detect(),
+    }
+}
+
+fn detect() -> Arc<dyn ContainerRuntime> {
+    let docker = Docker::default();
+    match version(&docker) {
+        // Podman's docker wrapper: talk to Podman directly, its output differs
+        Some(v) if v.to_lowercase().contains("podman") => Arc::new(Podman::default()),
+        Some(_) => Arc::new(docker),
+        None => {
+            let podman = Podman::default();
+            if version(&podman).is_some() {
+                Arc::new(podman)
+            } else {
+                Arc::new(docker)
+            }
+        }
+    }
+}
+
+fn version(runtime: &dyn ContainerRuntime) -> Option<String> {
+    let output = runtime.command().arg("--version").output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn info(runtime: &dyn ContainerRuntime, template: &str) -> Option<String> {
+    let output = runtime.command().args(["info", "--format", template]).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
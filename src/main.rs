@@ -5,13 +5,15 @@ mod config;
 mod docker_setup;
 mod markdown;
 mod mcp;
+mod processes;
+mod pty;
 mod session;
 mod shell;
 mod ui;
 
 use anyhow::Result;
 use app::{App, AppEvent};
-use config::Config;
+use config::{Config, SandboxMode};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
     execute,
@@ -25,8 +27,32 @@ use tokio::sync::mpsc;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let config = Config::load()?;
-    docker_setup::ensure_docker_env(&config)?;
+    let mut config = Config::load()?;
+    let runtime = docker_setup::runtime::select(&config);
+
+    // `agerus mcp-serve [workspace]`: expose the tools over stdio instead of the TUI
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("mcp-serve") {
+        if let Some(workspace) = args.get(1) {
+            config.workspace_path = workspace.into();
+        }
+        docker_setup::ensure_docker_env(&config, runtime.clone())?;
+        if config.sandbox == SandboxMode::None {
+            // stdout is the protocol; approving calls is left to the client
+            eprintln!(
+                "agerus: sandbox = \"none\": tools run directly on this machine in {}",
+                config.workspace_path.display()
+            );
+        }
+        return mcp::serve::run(config, runtime).await;
+    }
+
+    // `agerus sandbox ls|rm|prune`: manage the per-workspace containers
+    if args.first().map(String::as_str) == Some("sandbox") {
+        return docker_setup::manage::run(&args[1..], &config, runtime);
+    }
+
+    docker_setup::ensure_docker_env(&config, runtime.clone())?;
 
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -39,12 +65,15 @@ async fn main() -> Result<()> {
     let (tx_shell, rx_shell) = mpsc::channel::<ShellRequest>(100);
     let tx_shell_for_app = tx_shell.clone();
     let tx_shell_evt = tx_app_event.clone();
+    let shell_config = config.clone();
+    let shell_runtime = runtime.clone();
 
     tokio::spawn(async move {
-        ShellSession::run_actor(rx_shell, tx_shell_evt).await;
+        ShellSession::run_actor(rx_shell, tx_shell_evt, shell_config, shell_runtime).await;
     });
 
-    let tx_mcp = McpServer::start(tx_shell, config.clone()).await;
+    let (tx_mcp, processes) =
+        McpServer::start(tx_shell, config.clone(), runtime.clone(), tx_app_event.clone()).await;
 
     // Input loop
     let (tx_key_event, mut rx_key_event) = mpsc::unbounded_channel();
@@ -70,10 +99,11 @@ async fn main() -> Result<()> {
         }
     });
 
-    let mut app = App::new(tx_app_event.clone(), tx_shell_for_app, tx_mcp, config);
+    let mut app = App::new(tx_app_event.clone(), tx_shell_for_app, tx_mcp, processes, config, runtime);
 
     loop {
         terminal.draw(|f| ui::draw(f, &mut app))?;
+        app.sync_terminal_size();
 
         tokio::select! {
             Some(event) = rx_app_event.recv() => app.handle_internal_event(event),
@@ -81,7 +111,13 @@ async fn main() -> Result<()> {
                 match event {
                     Event::Key(key) => {
                         match key.code {
-                            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+                            // A focused terminal gets Ctrl+C itself
+                            KeyCode::Char('c')
+                                if key.modifiers.contains(KeyModifiers::CONTROL)
+                                    && !app.terminal_focused() =>
+                            {
+                                break
+                            }
                             _ => app.handle_key_event(key),
                         }
                     }
@@ -92,6 +128,10 @@ async fn main() -> Result<()> {
         }
     }
 
+    // Don't leave dev servers running in the sandbox. Directly rather than
+    // through the MCP queue, where a running command could hold it up.
+    let _ = tokio::time::timeout(Duration::from_secs(5), app.process_manager.kill_all()).await;
+
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
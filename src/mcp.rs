@@ -1,12 +1,66 @@
-use crate::shell::ShellRequest;
-use crate::config::Config;
+use crate::app::AppEvent;
+use crate::processes::ProcessManager;
+use crate::shell::{CommandStatus, ShellRequest, DEFAULT_SESSION};
+use crate::config::{Config, ToolApproval};
+use crate::docker_setup;
+use crate::docker_setup::runtime::ContainerRuntime;
 use anyhow::Result;
 use regex::Regex;
 use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot};
 
+// Declare the sub-modules so Rust knows to compile them
+pub mod client;
+pub mod edit;
+pub mod list;
+pub mod patch;
+pub mod paths;
+pub mod protocol;
+pub mod read;
+pub mod search;
+pub mod serve;
+
+use client::McpClient;
+use paths::resolve_in_workspace;
+
+/// Separates an external server's name from its tool names (`server__tool`).
+/// Double underscore because providers only accept `[a-zA-Z0-9_-]` in tool names.
+const NAMESPACE_SEP: &str = "__";
+
+/// Per-stream cap on `run_command` output handed back to the model
+const MAX_COMMAND_OUTPUT: usize = 5000;
+
+/// Bounds on a `run_command` timeout, whoever set it. A timeout of 0 would
+/// interrupt every command at once; servers belong in `start_process`.
+const MIN_COMMAND_TIMEOUT_SECS: u64 = 5;
+const MAX_COMMAND_TIMEOUT_SECS: u64 = 1800;
+
+/// Built-in tools that only look; everything else, external tools included,
+/// counts as a change for `tool_approval = "changes"`
+const READ_ONLY_TOOLS: &[&str] = &[
+    "process_output",
+    "list_processes",
+    "read_file",
+    "list_files",
+    "grep",
+    "glob",
+    "fetch_url",
+    "web_search",
+    "consult_documentation",
+];
+
+/// Whether the user has to approve a call to `name` before it runs.
+pub fn needs_approval(approval: ToolApproval, name: &str) -> bool {
+    match approval {
+        ToolApproval::Off => false,
+        ToolApproval::Changes => !READ_ONLY_TOOLS.contains(&name),
+        ToolApproval::All => true,
+    }
+}
+
 // --- MCP Protocol Definitions ---
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,21 +76,255 @@ pub enum McpRequest {
     CallTool {
         name: String,
         arguments: serde_json::Value,
-        response_tx: oneshot::Sender<Result<String>>,
+        response_tx: oneshot::Sender<Result<ToolOutput>>,
     },
 }
 
+/// What a tool call returns: the text for the model, and for `run_command`
+/// how the command ended.
+#[derive(Debug, Clone)]
+pub struct ToolOutput {
+    pub text: String,
+    pub command: Option<CommandStatus>,
+}
+
+impl From<String> for ToolOutput {
+    fn from(text: String) -> Self {
+        Self { text, command: None }
+    }
+}
+
+/// The tools implemented in-process by `McpServer::execute_tool`.
+pub fn builtin_tools() -> Vec<ToolDefinition> {
+    vec![
+        ToolDefinition {
+            name: "run_command".into(),
+            description: "Run a shell command in the sandbox container. The shell is persistent (cd and exports carry over) and stdin is closed. Returns the exit code and duration, then stdout and stderr separately.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "command": { "type": "string", "description": "Command to run" },
+                    "timeout": { "type": "integer", "description": format!("Seconds before the command is interrupted (default from config, 120; at least {}, at most {})", MIN_COMMAND_TIMEOUT_SECS, MAX_COMMAND_TIMEOUT_SECS) },
+                    "session": { "type": "string", "description": "Named shell to run in (default 'main'). Each session keeps its own cwd and environment and starts on first use, e.g. one per service." }
+                },
+                "required": ["command"]
+            }),
+        },
+        ToolDefinition {
+            name: "start_process".into(),
+            description: "Start a long-running command (dev server, file watcher, ...) in the background under a name, and return its first second of output. It runs in its own shell from the workspace root, not the run_command shell, so cd and exports there don't apply. Use process_output to follow it and kill_process to stop it.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "Short name to refer to the process, e.g. 'server'" },
+                    "command": { "type": "string", "description": "Command to run" }
+                },
+                "required": ["name", "command"]
+            }),
+        },
+        ToolDefinition {
+            name: "process_output".into(),
+            description: "Status of a background process and the output it printed since the last call (stdout and stderr interleaved).".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "Process name" },
+                    "tail": { "type": "integer", "description": "Instead of new output, return the last N lines" }
+                },
+                "required": ["name"]
+            }),
+        },
+        ToolDefinition {
+            name: "list_processes".into(),
+            description: "List background processes with their status and command.".into(),
+            input_schema: json!({ "type": "object", "properties": {} }),
+        },
+        ToolDefinition {
+            name: "kill_process".into(),
+            description: "Stop a background process and everything it started (SIGTERM, then SIGKILL after 3s). Returns its last output.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "Process name" }
+                },
+                "required": ["name"]
+            }),
+        },
+        ToolDefinition {
+            name: "write_file".into(),
+            description: "Write content to a file in the workspace.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Relative path" },
+                    "content": { "type": "string", "description": "Content" }
+                },
+                "required": ["path", "content"]
+            }),
+        },
+        ToolDefinition {
+            name: "edit_file".into(),
+            description: "Replace an exact snippet in an existing file. 'old_string' must match the file exactly (including indentation) and be unique unless 'replace_all' is set. Prefer this over write_file for changes to existing files.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Relative path" },
+                    "old_string": { "type": "string", "description": "Exact text to replace" },
+                    "new_string": { "type": "string", "description": "Replacement text" },
+                    "replace_all": { "type": "boolean", "description": "Replace every occurrence (default false)" }
+                },
+                "required": ["path", "old_string", "new_string"]
+            }),
+        },
+        ToolDefinition {
+            name: "apply_patch".into(),
+            description: "Apply a unified diff (as produced by `git diff` or `diff -u`) to the workspace. Supports several files at once, file creation (--- /dev/null), deletion (+++ /dev/null) and renames. Hunks are matched with some tolerance for shifted lines and whitespace. Either every hunk applies or nothing is written; the result lists each hunk as applied or rejected.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "patch": { "type": "string", "description": "Unified diff with '--- a/path' / '+++ b/path' headers and '@@' hunks" }
+                },
+                "required": ["patch"]
+            }),
+        },
+        ToolDefinition {
+            name: "read_file".into(),
+            description: "Read a text file, 300 lines at a time by default. Lines are prefixed with their number and a tab; the prefix is not part of the file. Use 'offset' to page through long files. Binary files are summarized instead of read.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Path" },
+                    "offset": { "type": "integer", "description": "First line to read, 1-based (default 1)" },
+                    "limit": { "type": "integer", "description": "Maximum number of lines (default 300)" },
+                    "line_numbers": { "type": "boolean", "description": "Prefix lines with their number (default true)" }
+                },
+                "required": ["path"]
+            }),
+        },
+        ToolDefinition {
+            name: "list_files".into(),
+            description: "List a directory recursively with file sizes, as an ASCII tree by default. Skips .git and anything in .gitignore or .agerusignore. Large directories are collapsed into '... N more entries'; list a subdirectory to see them.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Directory path" },
+                    "depth": { "type": "integer", "description": "How many levels to descend (default 2, 1 = only this directory)" },
+                    "tree": { "type": "boolean", "description": "Render as a tree (default true); false gives one [DIR]/[FILE] path per line" },
+                    "limit": { "type": "integer", "description": "Maximum entries shown (default 200, max 1000)" }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "grep".into(),
+            description: "Search file contents in the workspace with a regular expression (Rust regex syntax). Skips hidden, gitignored, binary and very large (over 2 MB) files. Output lines are 'path:line:text' for matches and 'path-line-text' for context. Results are paginated with 'offset'.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "pattern": { "type": "string", "description": "Regular expression" },
+                    "path": { "type": "string", "description": "Directory or file to search (default: workspace root)" },
+                    "glob": { "type": "string", "description": "Only search files matching this glob, e.g. '*.rs' or 'src/**/*.ts'" },
+                    "context": { "type": "integer", "description": "Lines of context around each match (default 0)" },
+                    "case_insensitive": { "type": "boolean", "description": "Ignore case (default false)" },
+                    "files_only": { "type": "boolean", "description": "Only list matching files with match counts (default false)" },
+                    "offset": { "type": "integer", "description": "Skip this many results (default 0)" },
+                    "limit": { "type": "integer", "description": "Results per page (default 50, max 200)" }
+                },
+                "required": ["pattern"]
+            }),
+        },
+        ToolDefinition {
+            name: "glob".into(),
+            description: "Find files by name pattern, recursively, e.g. '**/*.rs' or 'src/**/mod.rs'. Skips hidden and gitignored files. Results are paginated with 'offset'.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "pattern": { "type": "string", "description": "Glob pattern, relative to 'path'" },
+                    "path": { "type": "string", "description": "Directory to search (default: workspace root)" },
+                    "offset": { "type": "integer", "description": "Skip this many results (default 0)" },
+                    "limit": { "type": "integer", "description": "Results per page (default 50, max 200)" }
+                },
+                "required": ["pattern"]
+            }),
+        },
+        ToolDefinition {
+            name: "fetch_url".into(),
+            description: "Fetch and read a URL (web browsing).".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "url": { "type": "string", "description": "URL" }
+                },
+                "required": ["url"]
+            }),
+        },
+        ToolDefinition {
+            name: "web_search".into(),
+            description: "Search the web (DuckDuckGo). Returns title and URL.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "Search query" }
+                },
+                "required": ["query"]
+            }),
+        },
+        // --- NEW TOOL HERE ---
+        ToolDefinition {
+            name: "consult_documentation".into(),
+            description: "Lookup documentation/cheatsheets for commands or languages. Examples: 'rust/vectors', 'python/requests', 'tar', 'git/commit'.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "Subject to lookup (e.g. 'rust/hashmap' or 'tar')" }
+                },
+                "required": ["query"]
+            }),
+        },
+    ]
+}
+
+/// The `timeout` argument (or the configured default), clamped to sane bounds.
+fn command_timeout(args: &serde_json::Value, default: u64) -> u64 {
+    args.get("timeout")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(default)
+        .clamp(MIN_COMMAND_TIMEOUT_SECS, MAX_COMMAND_TIMEOUT_SECS)
+}
+
+/// Splits `server__tool` into its parts. Matched against the known server
+/// names, so either part may contain `__`; the longest matching server wins.
+fn split_namespaced<'a, 'b>(
+    name: &'a str,
+    servers: impl IntoIterator<Item = &'b str>,
+) -> Option<(&'b str, &'a str)> {
+    servers
+        .into_iter()
+        .filter_map(|server| Some((server, name.strip_prefix(server)?.strip_prefix(NAMESPACE_SEP)?)))
+        .max_by_key(|(server, _)| server.len())
+}
+
 // --- The Server Actor ---
 
 pub struct McpServer {
     shell_tx: mpsc::Sender<ShellRequest>,
     http_client: reqwest::Client,
     config: Config,
+    /// External servers that finished connecting
+    external: Vec<Arc<McpClient>>,
+    processes: ProcessManager,
 }
 
 impl McpServer {
-    pub async fn start(shell_tx: mpsc::Sender<ShellRequest>, config: Config) -> mpsc::Sender<McpRequest> {
+    /// Also returns the server's background processes, so they can be killed at
+    /// exit without waiting behind a tool call in the request queue.
+    pub async fn start(
+        shell_tx: mpsc::Sender<ShellRequest>,
+        config: Config,
+        runtime: Arc<dyn ContainerRuntime>,
+        app_tx: mpsc::Sender<AppEvent>,
+    ) -> (mpsc::Sender<McpRequest>, ProcessManager) {
         let (tx, mut rx) = mpsc::channel(32);
+        let processes = ProcessManager::new(docker_setup::Sandbox::new(&config, runtime), app_tx.clone());
         
         let mut server = Self { 
             shell_tx,
@@ -45,163 +333,244 @@ impl McpServer {
                 .build()
                 .unwrap_or_else(|_| reqwest::Client::new()),
             config,
+            external: Vec::new(),
+            processes: processes.clone(),
         };
 
+        // Each external server connects on its own; built-in tools are served
+        // right away and a server's tools appear once it is ready.
+        let mut rx_connected = server.connect_external(&app_tx);
         tokio::spawn(async move {
-            while let Some(req) = rx.recv().await {
-                server.handle_request(req).await;
+            loop {
+                tokio::select! {
+                    req = rx.recv() => match req {
+                        Some(req) => server.handle_request(req).await,
+                        None => break,
+                    },
+                    Some(client) = rx_connected.recv() => server.external.push(client),
+                }
             }
+            // Every sender is gone (e.g. the workspace changed)
+            server.processes.kill_all().await;
         });
 
-        tx
+        (tx, processes)
+    }
+
+    /// Starts connecting every configured server in the background. Clients
+    /// arrive on the returned channel as their servers finish initializing.
+    fn connect_external(&self, app_tx: &mpsc::Sender<AppEvent>) -> mpsc::Receiver<Arc<McpClient>> {
+        let (tx_connected, rx_connected) = mpsc::channel(self.config.mcp_servers.len().max(1));
+        for (name, server) in &self.config.mcp_servers {
+            let (name, server) = (name.clone(), server.clone());
+            let workspace = self.config.workspace_path.clone();
+            let (app_tx, tx_connected) = (app_tx.clone(), tx_connected.clone());
+            tokio::spawn(async move {
+                match McpClient::connect(&name, &server, &workspace).await {
+                    Ok(client) => {
+                        let _ = app_tx
+                            .send(AppEvent::Notice(format!(
+                                "MCP server '{}' connected ({} tools).",
+                                name,
+                                client.tools.len()
+                            )))
+                            .await;
+                        let _ = tx_connected.send(Arc::new(client)).await;
+                    }
+                    Err(e) => {
+                        let _ = app_tx
+                            .send(AppEvent::Notice(format!("⚠️ MCP server '{}': {:#}", name, e)))
+                            .await;
+                    }
+                }
+            });
+        }
+        rx_connected
+    }
+
+    /// The connected external server and tool behind a namespaced name.
+    fn external_tool(&self, name: &str) -> Option<(Arc<McpClient>, String)> {
+        let (server, tool) = split_namespaced(name, self.external.iter().map(|c| c.name.as_str()))?;
+        let client = self.external.iter().find(|c| c.name == server)?;
+        Some((client.clone(), tool.to_string()))
     }
 
     async fn handle_request(&mut self, req: McpRequest) {
         match req {
             McpRequest::ListTools(resp_tx) => {
-                let tools = vec![
-                    ToolDefinition {
-                        name: "run_command".into(),
-                        description: "Run a shell command in the Docker container.".into(),
-                        input_schema: json!({
-                            "type": "object",
-                            "properties": {
-                                "command": { "type": "string", "description": "Command to run" }
-                            },
-                            "required": ["command"]
-                        }),
-                    },
-                    ToolDefinition {
-                        name: "write_file".into(),
-                        description: "Write content to a file in the workspace.".into(),
-                        input_schema: json!({
-                            "type": "object",
-                            "properties": {
-                                "path": { "type": "string", "description": "Relative path" },
-                                "content": { "type": "string", "description": "Content" }
-                            },
-                            "required": ["path", "content"]
-                        }),
-                    },
-                    ToolDefinition {
-                        name: "read_file".into(),
-                        description: "Read a file.".into(),
-                        input_schema: json!({
-                            "type": "object",
-                            "properties": {
-                                "path": { "type": "string", "description": "Path" }
-                            },
-                            "required": ["path"]
-                        }),
-                    },
-                    ToolDefinition {
-                        name: "list_files".into(),
-                        description: "List files in a directory.".into(),
-                        input_schema: json!({
-                            "type": "object",
-                            "properties": {
-                                "path": { "type": "string", "description": "Directory path" }
-                            }
-                        }),
-                    },
-                    ToolDefinition {
-                        name: "fetch_url".into(),
-                        description: "Fetch and read a URL (web browsing).".into(),
-                        input_schema: json!({
-                            "type": "object",
-                            "properties": {
-                                "url": { "type": "string", "description": "URL" }
-                            },
-                            "required": ["url"]
-                        }),
-                    },
-                    ToolDefinition {
-                        name: "web_search".into(),
-                        description: "Search the web (DuckDuckGo). Returns title and URL.".into(),
-                        input_schema: json!({
-                            "type": "object",
-                            "properties": {
-                                "query": { "type": "string", "description": "Search query" }
-                            },
-                            "required": ["query"]
-                        }),
-                    },
-                    // --- NEW TOOL HERE ---
-                    ToolDefinition {
-                        name: "consult_documentation".into(),
-                        description: "Lookup documentation/cheatsheets for commands or languages. Examples: 'rust/vectors', 'python/requests', 'tar', 'git/commit'.".into(),
-                        input_schema: json!({
-                            "type": "object",
-                            "properties": {
-                                "query": { "type": "string", "description": "Subject to lookup (e.g. 'rust/hashmap' or 'tar')" }
-                            },
-                            "required": ["query"]
-                        }),
-                    },
-                ];
+                let mut tools = builtin_tools();
+                for client in &self.external {
+                    tools.extend(client.tools.iter().map(|t| ToolDefinition {
+                        name: format!("{}{}{}", client.name, NAMESPACE_SEP, t.name),
+                        description: format!("[{}] {}", client.name, t.description),
+                        input_schema: t.input_schema.clone(),
+                    }));
+                }
                 let _ = resp_tx.send(tools);
             }
             McpRequest::CallTool { name, arguments, response_tx } => {
-                let result = self.execute_tool(name, arguments).await;
+                // External calls can take minutes; answer them from their own task
+                // so they don't hold up the built-in tools
+                if let Some((client, tool)) = self.external_tool(&name) {
+                    tokio::spawn(async move {
+                        let result = client.call_tool(&tool, arguments).await.map(ToolOutput::from);
+                        let _ = response_tx.send(result);
+                    });
+                    return;
+                }
+                let result = if name == "run_command" {
+                    self.run_command(&arguments).await
+                } else {
+                    self.execute_tool(name, arguments).await.map(ToolOutput::from)
+                };
                 let _ = response_tx.send(result);
             }
         }
     }
 
+    async fn run_command(&self, args: &serde_json::Value) -> Result<ToolOutput> {
+        let cmd = args.get("command").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing 'command'"))?;
+        let timeout = command_timeout(args, self.config.command_timeout_secs);
+        let session = args.get("session").and_then(|v| v.as_str()).unwrap_or(DEFAULT_SESSION).to_string();
+        let (tx, mut rx) = oneshot::channel();
+        self.shell_tx.send(ShellRequest::RunCommand { session: session.clone(), cmd: cmd.to_string(), response_tx: tx }).await?;
+
+        let ended = || anyhow::anyhow!("Shell session ended before the command finished");
+        match tokio::time::timeout(std::time::Duration::from_secs(timeout), &mut rx).await {
+            Ok(output) => {
+                let output = output.map_err(|_| ended())??;
+                Ok(ToolOutput {
+                    text: output.to_tool_text(MAX_COMMAND_OUTPUT),
+                    command: Some(output.status()),
+                })
+            }
+            Err(_) => {
+                // Interrupt and wait for the shell to report back, so it is free for the next call
+                self.shell_tx.send(ShellRequest::Interrupt { session: Some(session) }).await?;
+                let output = rx.await.map_err(|_| ended())??;
+                let mut status = output.status();
+                status.timed_out = true;
+                Ok(ToolOutput {
+                    text: format!(
+                        "Command timed out after {}s and was interrupted. For servers or watchers, use start_process instead.\n{}",
+                        timeout,
+                        output.to_tool_text(MAX_COMMAND_OUTPUT)
+                    ),
+                    command: Some(status),
+                })
+            }
+        }
+    }
+
     async fn execute_tool(&self, name: String, args: serde_json::Value) -> Result<String> {
         match name.as_str() {
-            "run_command" => {
+            "start_process" => {
+                let name = args.get("name").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing name"))?;
                 let cmd = args.get("command").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing 'command'"))?;
-                let (tx, mut rx) = mpsc::channel(100);
-                self.shell_tx.send(ShellRequest::RunCommand { cmd: cmd.to_string(), response_tx: tx }).await?;
-                let mut output = String::new();
-                while let Some(chunk) = rx.recv().await { output.push_str(&chunk); output.push('\n'); }
-                if output.len() > 5000 { output = format!("{}\n...[Output Truncated]", &output[..5000]); }
-                Ok(output)
+                self.processes.start(name, cmd, MAX_COMMAND_OUTPUT).await
+            }
+            "process_output" => {
+                let name = args.get("name").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing name"))?;
+                let tail = args.get("tail").and_then(|v| v.as_u64()).map(|v| v as usize);
+                self.processes.output(name, tail, MAX_COMMAND_OUTPUT)
+            }
+            "list_processes" => Ok(self.processes.list()),
+            "kill_process" => {
+                let name = args.get("name").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing name"))?;
+                self.processes.kill(name, MAX_COMMAND_OUTPUT).await
             }
             "write_file" => {
                 let path = args.get("path").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing path"))?;
                 let content = args.get("content").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing content"))?;
                 
-                // Use workspace from Config
-                let target = self.config.workspace_path.join(path);
+                let target = resolve_in_workspace(&self.config.workspace_path, path)?;
                 
                 if let Some(p) = target.parent() { tokio::fs::create_dir_all(p).await?; }
                 tokio::fs::write(&target, content).await?;
                 Ok(format!("Successfully wrote to {}", path))
             }
+            "edit_file" => {
+                let path = args.get("path").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing path"))?;
+                let old = args.get("old_string").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing old_string"))?;
+                let new = args.get("new_string").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing new_string"))?;
+                let replace_all = args.get("replace_all").and_then(|v| v.as_bool()).unwrap_or(false);
+
+                let target = resolve_in_workspace(&self.config.workspace_path, path)?;
+
+                if !target.exists() { return Err(anyhow::anyhow!("File not found: {}. Use write_file to create it.", path)); }
+                let before = tokio::fs::read_to_string(&target).await?;
+                let (after, count) = edit::replace_exact(&before, old, new, replace_all)
+                    .map_err(|e| anyhow::anyhow!("{} ({})", e, path))?;
+                tokio::fs::write(&target, &after).await?;
+
+                Ok(format!("Edited {} ({} replacement{})\n{}", path, count, if count == 1 { "" } else { "s" }, edit::compact_diff(path, &before, &after)))
+            }
+            "apply_patch" => {
+                let patch = args.get("patch").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing patch"))?;
+                let workspace = self.config.workspace_path.clone();
+                let patch = patch.to_string();
+                tokio::task::spawn_blocking(move || patch::apply_patch(&workspace, &patch)).await?
+            }
             "read_file" => {
                 let path = args.get("path").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing path"))?;
                 
-                // Use workspace from Config
-                let target = self.config.workspace_path.join(path);
+                let target = resolve_in_workspace(&self.config.workspace_path, path)?;
                 
                 if !target.exists() { return Ok(format!("File not found: {}", path)); }
-                let content = tokio::fs::read_to_string(target).await?;
-                if content.lines().count() > 300 {
-                   let preview: String = content.lines().take(300).collect::<Vec<_>>().join("\n");
-                   Ok(format!("{}\n... [File too long, first 300 lines shown]", preview))
-                } else { Ok(content) }
+                let opts = read::ReadOptions {
+                    offset: args.get("offset").and_then(|v| v.as_u64()).unwrap_or(1) as usize,
+                    limit: args.get("limit").and_then(|v| v.as_u64()).map(|v| v as usize).unwrap_or(read::DEFAULT_LIMIT),
+                    line_numbers: args.get("line_numbers").and_then(|v| v.as_bool()).unwrap_or(true),
+                    max_bytes: self.config.read_max_bytes,
+                };
+                let display = path.to_string();
+                tokio::task::spawn_blocking(move || read::read_file(&target, &display, &opts)).await?
             }
             "list_files" => {
                 let path_str = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
                 
-                // Use workspace from Config
-                let target = self.config.workspace_path.join(path_str);
+                let target = resolve_in_workspace(&self.config.workspace_path, path_str)?;
                 
-                if !target.exists() {
+                if !target.is_dir() {
                      return Ok(format!("Directory not found: {}", path_str));
                 }
 
-                let mut entries = tokio::fs::read_dir(target).await?;
-                let mut list = Vec::new();
-                while let Some(entry) = entries.next_entry().await? {
-                    let name = entry.file_name().to_string_lossy().to_string();
-                    let meta = entry.metadata().await?;
-                    let type_str = if meta.is_dir() { "DIR" } else { "FILE" };
-                    list.push(format!("[{}] {}", type_str, name));
-                }
-                Ok(list.join("\n"))
+                let opts = list::ListOptions {
+                    depth: args.get("depth").and_then(|v| v.as_u64()).map(|v| v as usize).unwrap_or(list::DEFAULT_DEPTH),
+                    tree: args.get("tree").and_then(|v| v.as_bool()).unwrap_or(true),
+                    limit: args.get("limit").and_then(|v| v.as_u64()).map(|v| v as usize).unwrap_or(list::DEFAULT_ENTRIES),
+                };
+                let display = path_str.to_string();
+                tokio::task::spawn_blocking(move || list::list_files(&target, &display, &opts)).await?
+            }
+            "grep" => {
+                let pattern = args.get("pattern").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing pattern"))?;
+                let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
+                let root = resolve_in_workspace(&self.config.workspace_path, path)?;
+                if !root.exists() { return Ok(format!("Path not found: {}", path)); }
+
+                let opts = search::GrepOptions {
+                    pattern: pattern.to_string(),
+                    glob: args.get("glob").and_then(|v| v.as_str()).map(String::from),
+                    context: args.get("context").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+                    case_insensitive: args.get("case_insensitive").and_then(|v| v.as_bool()).unwrap_or(false),
+                    files_only: args.get("files_only").and_then(|v| v.as_bool()).unwrap_or(false),
+                    offset: args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+                    limit: args.get("limit").and_then(|v| v.as_u64()).map(|v| v as usize).unwrap_or(search::DEFAULT_RESULTS),
+                };
+                let workspace = self.config.workspace_path.clone();
+                tokio::task::spawn_blocking(move || search::grep(&workspace, &root, &opts)).await?
+            }
+            "glob" => {
+                let pattern = args.get("pattern").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing pattern"))?.to_string();
+                let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
+                let root = resolve_in_workspace(&self.config.workspace_path, path)?;
+                if !root.is_dir() { return Ok(format!("Directory not found: {}", path)); }
+
+                let offset = args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+                let limit = args.get("limit").and_then(|v| v.as_u64()).map(|v| v as usize).unwrap_or(search::DEFAULT_RESULTS);
+                let workspace = self.config.workspace_path.clone();
+                tokio::task::spawn_blocking(move || search::glob(&workspace, &root, &pattern, offset, limit)).await?
             }
             "fetch_url" => {
                 let url = args.get("url").and_then(|v| v.as_str()).ok_or_else(|| anyhow::anyhow!("Missing url"))?;
@@ -270,3 +639,43 @@ impl McpServer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_namespaced_names_on_known_servers() {
+        let servers = ["fs", "my__srv", "my"];
+        assert_eq!(split_namespaced("fs__read", servers), Some(("fs", "read")));
+        assert_eq!(split_namespaced("my__srv__read", servers), Some(("my__srv", "read")));
+        assert_eq!(split_namespaced("my__other__tool", servers), Some(("my", "other__tool")));
+        assert_eq!(split_namespaced("fsx__read", servers), None);
+        assert_eq!(split_namespaced("read_file", servers), None);
+    }
+
+    #[test]
+    fn approval_covers_changes_but_not_reads() {
+        let changes = ["run_command", "write_file", "apply_patch", "start_process", "fs__read", "web__search"];
+        for name in changes {
+            assert!(needs_approval(ToolApproval::Changes, name), "{}", name);
+            assert!(needs_approval(ToolApproval::All, name), "{}", name);
+            assert!(!needs_approval(ToolApproval::Off, name), "{}", name);
+        }
+        for name in READ_ONLY_TOOLS {
+            assert!(!needs_approval(ToolApproval::Changes, name), "{}", name);
+            assert!(needs_approval(ToolApproval::All, name), "{}", name);
+            assert!(!needs_approval(ToolApproval::Off, name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn clamps_command_timeouts() {
+        assert_eq!(command_timeout(&json!({ "command": "ls" }), 120), 120);
+        assert_eq!(command_timeout(&json!({ "timeout": 30 }), 120), 30);
+        assert_eq!(command_timeout(&json!({ "timeout": 0 }), 120), MIN_COMMAND_TIMEOUT_SECS);
+        assert_eq!(command_timeout(&json!({ "timeout": 86400 }), 120), MAX_COMMAND_TIMEOUT_SECS);
+        // A bad config value is clamped too
+        assert_eq!(command_timeout(&json!({}), 0), MIN_COMMAND_TIMEOUT_SECS);
+    }
+}
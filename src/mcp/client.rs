@@ -0,0 +1,306 @@
+use super::protocol::{self, JsonRpcMessage, PROTOCOL_VERSION};
+use super::ToolDefinition;
+use crate::config::ExternalMcpServer;
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::{oneshot, Mutex};
+
+const INIT_TIMEOUT: Duration = Duration::from_secs(60);
+const CALL_TIMEOUT: Duration = Duration::from_secs(300);
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value>>>>>;
+
+/// Client for one external MCP server speaking JSON-RPC over its stdin/stdout.
+pub struct McpClient {
+    pub name: String,
+    pub tools: Vec<ToolDefinition>,
+    _process: Child, // kill_on_drop: the server lives as long as the client
+    stdin: Arc<Mutex<ChildStdin>>,
+    pending: Pending,
+    next_id: AtomicU64,
+}
+
+impl McpClient {
+    pub async fn connect(name: &str, server: &ExternalMcpServer, workspace: &Path) -> Result<Self> {
+        let mut process = Command::new(&server.command)
+            .args(&server.args)
+            .envs(&server.env)
+            .current_dir(workspace)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            // stderr would scribble over the TUI
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to spawn MCP server '{}'", name))?;
+
+        let stdin = process
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("Failed to capture stdin"))?;
+        let stdout = process
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("Failed to capture stdout"))?;
+
+        let stdin = Arc::new(Mutex::new(stdin));
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+
+        tokio::spawn(Self::read_loop(
+            BufReader::new(stdout),
+            stdin.clone(),
+            pending.clone(),
+        ));
+
+        let mut client = Self {
+            name: name.to_string(),
+            tools: Vec::new(),
+            _process: process,
+            stdin,
+            pending,
+            next_id: AtomicU64::new(1),
+        };
+
+        client
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": { "name": "agerus", "version": env!("CARGO_PKG_VERSION") }
+                }),
+                INIT_TIMEOUT,
+            )
+            .await
+            .with_context(|| format!("MCP server '{}' failed to initialize", name))?;
+        client
+            .notify("notifications/initialized", json!({}))
+            .await?;
+
+        client.tools = client.list_tools().await?;
+        Ok(client)
+    }
+
+    async fn list_tools(&self) -> Result<Vec<ToolDefinition>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let params = match &cursor {
+                Some(c) => json!({ "cursor": c }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params, INIT_TIMEOUT).await?;
+
+            for tool in result
+                .get("tools")
+                .and_then(|t| t.as_array())
+                .cloned()
+                .unwrap_or_default()
+            {
+                let Some(name) = tool.get("name").and_then(|n| n.as_str()) else {
+                    continue;
+                };
+                tools.push(ToolDefinition {
+                    name: name.to_string(),
+                    description: tool
+                        .get("description")
+                        .and_then(|d| d.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    input_schema: tool
+                        .get("inputSchema")
+                        .cloned()
+                        .unwrap_or_else(|| json!({ "type": "object" })),
+                });
+            }
+
+            cursor = result
+                .get("nextCursor")
+                .and_then(|c| c.as_str())
+                .map(|c| c.to_string());
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        Ok(tools)
+    }
+
+    pub async fn call_tool(&self, tool: &str, arguments: Value) -> Result<String> {
+        let result = self
+            .request(
+                "tools/call",
+                json!({ "name": tool, "arguments": arguments }),
+                CALL_TIMEOUT,
+            )
+            .await?;
+
+        let text = protocol::tool_result_text(&result);
+        if result.get("isError").and_then(|e| e.as_bool()) == Some(true) {
+            return Err(anyhow!("{}", text));
+        }
+        Ok(text)
+    }
+
+    async fn request(&self, method: &str, params: Value, timeout: Duration) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(id, tx);
+
+        if let Err(e) = self.send(protocol::request(id, method, params)).await {
+            self.pending.lock().await.remove(&id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(anyhow!("MCP server '{}' exited", self.name)),
+            Err(_) => {
+                self.pending.lock().await.remove(&id);
+                Err(anyhow!(
+                    "MCP server '{}' timed out on '{}'",
+                    self.name,
+                    method
+                ))
+            }
+        }
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<()> {
+        self.send(protocol::notification(method, params)).await
+    }
+
+    async fn send(&self, message: Value) -> Result<()> {
+        write_message(&self.stdin, &message).await
+    }
+
+    async fn read_loop(
+        mut reader: BufReader<tokio::process::ChildStdout>,
+        stdin: Arc<Mutex<ChildStdin>>,
+        pending: Pending,
+    ) {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match reader.read_line(&mut line).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+
+            let Ok(msg) = serde_json::from_str::<JsonRpcMessage>(line.trim()) else {
+                continue;
+            };
+
+            if msg.is_response() {
+                let Some(id) = msg.id.as_ref().and_then(|id| id.as_u64()) else {
+                    continue;
+                };
+                if let Some(tx) = pending.lock().await.remove(&id) {
+                    let result = match msg.error {
+                        Some(err) => Err(anyhow!("{} (code {})", err.message, err.code)),
+                        None => Ok(msg.result.unwrap_or(Value::Null)),
+                    };
+                    let _ = tx.send(result);
+                }
+            } else if let (Some(id), Some(method)) = (msg.id, msg.method) {
+                // Server-initiated request. We only implement `ping`.
+                let reply = if method == "ping" {
+                    protocol::response(id, json!({}))
+                } else {
+                    protocol::error_response(id, protocol::METHOD_NOT_FOUND, "Method not found")
+                };
+                let _ = write_message(&stdin, &reply).await;
+            }
+        }
+
+        // Fail everything still waiting, the server is gone
+        pending.lock().await.clear();
+    }
+}
+
+async fn write_message(stdin: &Mutex<ChildStdin>, message: &Value) -> Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(line.as_bytes()).await?;
+    stdin.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays an MCP server: checks each request's method and answers by id.
+    /// Anything out of order makes it exit, which fails the pending request.
+    const PEER: &str = r#"
+expect() { read -r line; case "$line" in *"\"method\":\"$1\""*) ;; *) exit 1 ;; esac; }
+expect initialize
+echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{}},"serverInfo":{"name":"peer"}}}'
+expect notifications/initialized
+expect tools/list
+echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"echo","description":"Echoes","inputSchema":{"type":"object"}}],"nextCursor":"p2"}}'
+expect tools/list
+echo '{"jsonrpc":"2.0","id":3,"result":{"tools":[{"name":"add"}]}}'
+expect tools/call
+echo '{"jsonrpc":"2.0","id":"srv-1","method":"ping"}'
+read -r pong; case "$pong" in *'"id":"srv-1"'*'"result"'*) ;; *) exit 1 ;; esac
+echo '{"jsonrpc":"2.0","id":4,"result":{"content":[{"type":"text","text":"hello"},{"type":"image","mimeType":"image/png","data":""}]}}'
+expect tools/call
+echo '{"jsonrpc":"2.0","id":5,"error":{"code":-32602,"message":"Unknown tool: nope"}}'
+expect tools/call
+echo '{"jsonrpc":"2.0","id":6,"result":{"content":[{"type":"text","text":"division by zero"}],"isError":true}}'
+read -r line
+"#;
+
+    fn peer() -> ExternalMcpServer {
+        ExternalMcpServer {
+            command: "sh".into(),
+            args: vec!["-c".into(), PEER.into()],
+            env: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn talks_to_a_stdio_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = McpClient::connect("peer", &peer(), dir.path()).await.unwrap();
+
+        // Both pages of tools/list, with defaults for what the second one leaves out
+        let names: Vec<_> = client.tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["echo", "add"]);
+        assert_eq!(client.tools[0].description, "Echoes");
+        assert_eq!(client.tools[1].input_schema, json!({ "type": "object" }));
+
+        // The server pings us before answering
+        let text = client.call_tool("echo", json!({ "text": "hello" })).await.unwrap();
+        assert_eq!(text, "hello\n[image content: image/png]");
+
+        let err = client.call_tool("nope", json!({})).await.unwrap_err();
+        assert_eq!(err.to_string(), "Unknown tool: nope (code -32602)");
+
+        let err = client.call_tool("add", json!({ "a": 1, "b": 0 })).await.unwrap_err();
+        assert_eq!(err.to_string(), "division by zero");
+    }
+
+    #[tokio::test]
+    async fn fails_pending_requests_when_the_server_exits() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = ExternalMcpServer {
+            command: "sh".into(),
+            args: vec!["-c".into(), "read -r line".into()],
+            env: Default::default(),
+        };
+        let err = McpClient::connect("quitter", &server, dir.path()).await.err().unwrap();
+        assert!(format!("{:#}", err).contains("MCP server 'quitter' exited"));
+    }
+}
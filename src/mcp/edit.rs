@@ -0,0 +1,126 @@
+use anyhow::{anyhow, Result};
+use similar::TextDiff;
+
+// Exact search/replace edits for the `edit_file` tool
+
+const MAX_DIFF_CHARS: usize = 4000;
+
+/// Replaces `old` with `new` in `content`. Fails when `old` is missing, or when it
+/// is ambiguous and `replace_all` is not set. Returns the new content and the
+/// number of replacements.
+pub fn replace_exact(
+    content: &str,
+    old: &str,
+    new: &str,
+    replace_all: bool,
+) -> Result<(String, usize)> {
+    if old.is_empty() {
+        return Err(anyhow!("'old_string' must not be empty. Use write_file to create files."));
+    }
+    if old == new {
+        return Err(anyhow!("'old_string' and 'new_string' are identical, nothing to change."));
+    }
+
+    // Models almost always send `\n`; match CRLF files anyway and keep their line endings
+    let (old, new) = if !content.contains(old) && content.contains("\r\n") {
+        (old.replace('\n', "\r\n"), new.replace('\n', "\r\n"))
+    } else {
+        (old.to_string(), new.to_string())
+    };
+
+    let count = content.matches(&old).count();
+    match count {
+        0 => Err(anyhow!(
+            "'old_string' was not found. It must match the file exactly, including whitespace and indentation. Re-read the file with read_file and try again."
+        )),
+        1 => Ok((content.replacen(&old, &new, 1), 1)),
+        n if replace_all => Ok((content.replace(&old, &new), n)),
+        n => Err(anyhow!(
+            "'old_string' matches {} locations. Include more surrounding lines to make it unique, or set replace_all to true.",
+            n
+        )),
+    }
+}
+
+/// Unified diff with two lines of context, truncated for the model.
+pub fn compact_diff(path: &str, before: &str, after: &str) -> String {
+    let diff = TextDiff::from_lines(before, after)
+        .unified_diff()
+        .context_radius(2)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string();
+
+    if diff.chars().count() > MAX_DIFF_CHARS {
+        let clipped: String = diff.chars().take(MAX_DIFF_CHARS).collect();
+        format!("{}\n...[Diff truncated]", clipped)
+    } else {
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = "fn a() {\n    x();\n}\n\nfn b() {\n    x();\n}\n";
+
+    #[test]
+    fn replaces_a_unique_match() {
+        let (after, count) = replace_exact(SRC, "fn a() {\n    x();", "fn a() {\n    y();", false).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(after, "fn a() {\n    y();\n}\n\nfn b() {\n    x();\n}\n");
+    }
+
+    #[test]
+    fn missing_old_string_fails() {
+        let err = replace_exact(SRC, "fn c()", "fn d()", false).unwrap_err();
+        assert!(err.to_string().contains("was not found"));
+
+        // Indentation has to match too
+        let err = replace_exact(SRC, "fn a() {\n  x();", "fn a() {\n  y();", false).unwrap_err();
+        assert!(err.to_string().contains("was not found"));
+    }
+
+    #[test]
+    fn ambiguous_match_needs_replace_all() {
+        let err = replace_exact(SRC, "    x();", "    y();", false).unwrap_err();
+        assert!(err.to_string().contains("matches 2 locations"));
+
+        let (after, count) = replace_exact(SRC, "    x();", "    y();", true).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(after, SRC.replace("x()", "y()"));
+    }
+
+    #[test]
+    fn rejects_empty_and_no_op_edits() {
+        assert!(replace_exact(SRC, "", "x", false).unwrap_err().to_string().contains("must not be empty"));
+        assert!(replace_exact(SRC, "fn a", "fn a", false).unwrap_err().to_string().contains("identical"));
+    }
+
+    #[test]
+    fn matches_crlf_files_and_keeps_their_line_endings() {
+        let crlf = SRC.replace('\n', "\r\n");
+        let (after, count) = replace_exact(&crlf, "fn b() {\n    x();", "fn b() {\n    z();\n    w();", false).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(after, "fn a() {\r\n    x();\r\n}\r\n\r\nfn b() {\r\n    z();\r\n    w();\r\n}\r\n");
+
+        // Single-line edits match as-is and don't get converted
+        let (after, _) = replace_exact(&crlf, "fn a()", "fn first()", false).unwrap();
+        assert!(after.starts_with("fn first() {\r\n"));
+    }
+
+    #[test]
+    fn diff_shows_the_change_and_is_truncated() {
+        let (after, _) = replace_exact(SRC, "fn b() {\n    x();", "fn b() {\n    y();", false).unwrap();
+        let diff = compact_diff("src/lib.rs", SRC, &after);
+        assert!(diff.starts_with("--- a/src/lib.rs\n+++ b/src/lib.rs\n"));
+        assert!(diff.contains("\n-    x();\n+    y();\n"));
+        assert!(!diff.contains("fn a()"), "context radius is two lines");
+
+        let big_before = "old line\n".repeat(1000);
+        let big_after = "new line\n".repeat(1000);
+        let diff = compact_diff("big.txt", &big_before, &big_after);
+        assert!(diff.ends_with("...[Diff truncated]"));
+        assert!(diff.chars().count() <= MAX_DIFF_CHARS + "\n...[Diff truncated]".len());
+    }
+}
@@ -0,0 +1,267 @@
+use super::read::human_size;
+use super::search::walker;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+// Directory listings for the `list_files` tool, flat or as an ASCII tree
+
+pub const DEFAULT_DEPTH: usize = 2;
+pub const DEFAULT_ENTRIES: usize = 200;
+pub const MAX_ENTRIES: usize = 1000;
+
+/// Children shown per directory before collapsing the rest into "N more"
+const PER_DIR: usize = 50;
+/// Stop walking huge trees; the output could never show them anyway
+const SCAN_CAP: usize = 50_000;
+
+pub struct ListOptions {
+    pub depth: usize,
+    pub tree: bool,
+    pub limit: usize,
+}
+
+struct Node {
+    name: String,
+    /// `None` for directories
+    size: Option<u64>,
+    children: Vec<usize>,
+}
+
+pub fn list_files(root: &Path, display: &str, opts: &ListOptions) -> Result<String> {
+    let mut nodes = vec![Node {
+        name: display.trim_end_matches('/').to_string(),
+        size: None,
+        children: Vec::new(),
+    }];
+    let mut index: HashMap<PathBuf, usize> = HashMap::from([(root.to_path_buf(), 0)]);
+    let (mut dirs, mut files) = (0, 0);
+    let mut truncated = false;
+
+    let walk = walker(root)
+        // Dotfiles like .github or .env.example matter for orientation, .git never does
+        .hidden(false)
+        .filter_entry(|e| e.file_name() != ".git")
+        .max_depth(Some(opts.depth.max(1)))
+        .build();
+
+    for entry in walk.filter_map(|e| e.ok()).filter(|e| e.depth() > 0) {
+        if dirs + files >= SCAN_CAP {
+            truncated = true;
+            break;
+        }
+        let Some(&parent) = entry.path().parent().and_then(|p| index.get(p)) else {
+            continue;
+        };
+        let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+        let size = if is_dir {
+            dirs += 1;
+            None
+        } else {
+            files += 1;
+            Some(entry.metadata().map(|m| m.len()).unwrap_or(0))
+        };
+
+        let id = nodes.len();
+        nodes.push(Node {
+            name: entry.file_name().to_string_lossy().to_string(),
+            size,
+            children: Vec::new(),
+        });
+        nodes[parent].children.push(id);
+        if is_dir {
+            index.insert(entry.into_path(), id);
+        }
+    }
+
+    let mut out = String::new();
+    let mut budget = opts.limit.clamp(1, MAX_ENTRIES);
+    if opts.tree {
+        let _ = writeln!(out, "{}/", nodes[0].name);
+    }
+    render(&nodes, 0, "", "", opts.tree, &mut budget, &mut out);
+
+    let _ = write!(
+        out,
+        "[{} director{}, {} file{}, depth {}",
+        dirs,
+        if dirs == 1 { "y" } else { "ies" },
+        files,
+        if files == 1 { "" } else { "s" },
+        opts.depth.max(1)
+    );
+    if truncated {
+        let _ = write!(out, "; stopped after {} entries", SCAN_CAP);
+    }
+    out.push(']');
+    Ok(out)
+}
+
+/// Writes the children of `id`. `prefix` is the tree indentation, `rel` the
+/// path used in flat mode.
+fn render(
+    nodes: &[Node],
+    id: usize,
+    prefix: &str,
+    rel: &str,
+    tree: bool,
+    budget: &mut usize,
+    out: &mut String,
+) {
+    let children = &nodes[id].children;
+    let shown = children.len().min(PER_DIR).min(*budget);
+    // Nested directories may use up the budget before all of `shown` is written
+    let mut written = 0;
+
+    for (i, &child) in children.iter().take(shown).enumerate() {
+        if *budget == 0 {
+            break;
+        }
+        *budget -= 1;
+        written += 1;
+        let node = &nodes[child];
+        let last = i + 1 == children.len();
+        let path = if rel.is_empty() {
+            node.name.clone()
+        } else {
+            format!("{}/{}", rel, node.name)
+        };
+        let size = node
+            .size
+            .map(|s| format!(" ({})", human_size(s)))
+            .unwrap_or_default();
+
+        if tree {
+            let branch = if last { "└── " } else { "├── " };
+            let slash = if node.size.is_none() { "/" } else { "" };
+            let _ = writeln!(out, "{}{}{}{}{}", prefix, branch, node.name, slash, size);
+            let nested = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            render(nodes, child, &nested, &path, tree, budget, out);
+        } else if node.size.is_none() {
+            let _ = writeln!(out, "[DIR]  {}/", path);
+            render(nodes, child, prefix, &path, tree, budget, out);
+        } else {
+            let _ = writeln!(out, "[FILE] {}{}", path, size);
+        }
+    }
+
+    let hidden = children.len() - written;
+    if hidden > 0 {
+        let more = format!("... {} more entr{}", hidden, if hidden == 1 { "y" } else { "ies" });
+        if tree {
+            let _ = writeln!(out, "{}└── {}", prefix, more);
+        } else {
+            let location = if rel.is_empty() { "." } else { rel };
+            let _ = writeln!(out, "{} in {}", more, location);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn workspace(files: &[(&str, &str)]) -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn list(dir: &TempDir, depth: usize, tree: bool, limit: usize) -> String {
+        list_files(dir.path(), "ws", &ListOptions { depth, tree, limit }).unwrap()
+    }
+
+    fn project() -> TempDir {
+        workspace(&[
+            (".git/HEAD", "ref: refs/heads/main\n"),
+            (".github/ci.yml", "on: push\n"),
+            ("a/b/c/deep.txt", ""),
+            ("src/main.rs", "fn main() {}\n"),
+        ])
+    }
+
+    #[test]
+    fn flat_listing_stops_at_depth_and_skips_git() {
+        let dir = project();
+
+        assert_eq!(
+            list(&dir, 2, false, DEFAULT_ENTRIES),
+            "[DIR]  .github/\n\
+             [FILE] .github/ci.yml (9 bytes)\n\
+             [DIR]  a/\n\
+             [DIR]  a/b/\n\
+             [DIR]  src/\n\
+             [FILE] src/main.rs (13 bytes)\n\
+             [4 directories, 2 files, depth 2]"
+        );
+        assert_eq!(
+            list(&dir, 0, false, DEFAULT_ENTRIES),
+            "[DIR]  .github/\n[DIR]  a/\n[DIR]  src/\n[3 directories, 0 files, depth 1]"
+        );
+    }
+
+    #[test]
+    fn tree_listing_draws_branches() {
+        let dir = project();
+
+        assert_eq!(
+            list(&dir, 4, true, DEFAULT_ENTRIES),
+            "ws/\n\
+             ├── .github/\n\
+             │   └── ci.yml (9 bytes)\n\
+             ├── a/\n\
+             │   └── b/\n\
+             │       └── c/\n\
+             │           └── deep.txt (0 bytes)\n\
+             └── src/\n\
+             \x20   └── main.rs (13 bytes)\n\
+             [5 directories, 3 files, depth 4]"
+        );
+    }
+
+    #[test]
+    fn collapses_large_directories() {
+        let names: Vec<String> = (0..PER_DIR + 2).map(|i| format!("f{:02}", i)).collect();
+        let files: Vec<(&str, &str)> = names.iter().map(|n| (n.as_str(), "")).collect();
+        let dir = workspace(&files);
+
+        let out = list(&dir, 1, false, MAX_ENTRIES);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), PER_DIR + 2);
+        assert_eq!(lines[PER_DIR - 1], "[FILE] f49 (0 bytes)");
+        assert_eq!(lines[PER_DIR], "... 2 more entries in .");
+        assert_eq!(lines[PER_DIR + 1], "[0 directories, 52 files, depth 1]");
+    }
+
+    #[test]
+    fn entry_budget_spans_the_whole_listing() {
+        let dir = workspace(&[("a/x1", ""), ("a/x2", ""), ("a/x3", ""), ("b.txt", "")]);
+
+        assert_eq!(
+            list(&dir, 2, false, 3),
+            "[DIR]  a/\n\
+             [FILE] a/x1 (0 bytes)\n\
+             [FILE] a/x2 (0 bytes)\n\
+             ... 1 more entry in a\n\
+             ... 1 more entry in .\n\
+             [1 directory, 4 files, depth 2]"
+        );
+        assert_eq!(
+            list(&dir, 2, true, 3),
+            "ws/\n\
+             ├── a/\n\
+             │   ├── x1 (0 bytes)\n\
+             │   ├── x2 (0 bytes)\n\
+             │   └── ... 1 more entry\n\
+             └── ... 1 more entry\n\
+             [1 directory, 4 files, depth 2]"
+        );
+    }
+}
@@ -0,0 +1,720 @@
+use super::paths::resolve_in_workspace;
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+// Multi-file unified diffs for the `apply_patch` tool.
+// Every hunk is applied in memory first; nothing touches the disk unless all succeed.
+
+/// How many context lines may be dropped from each end of a hunk (like `patch --fuzz`)
+const MAX_FUZZ: usize = 2;
+
+#[derive(Debug, Clone)]
+enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+#[derive(Debug, Default)]
+struct Hunk {
+    header: String,
+    /// 1-based line in the original file, 0 when the header carried no numbers
+    old_start: usize,
+    /// Lines the hunk covers in the original; 0 for a pure insertion after `old_start`
+    old_count: usize,
+    lines: Vec<HunkLine>,
+}
+
+#[derive(Debug, Default)]
+struct FilePatch {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    hunks: Vec<Hunk>,
+    /// `\ No newline at end of file` after a new-side line
+    new_missing_eol: bool,
+    /// `\ No newline at end of file` after a removed line only
+    old_missing_eol: bool,
+}
+
+impl FilePatch {
+    fn display_path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or("?")
+    }
+}
+
+enum FileOp {
+    Write { path: PathBuf, content: String },
+    Delete { path: PathBuf },
+}
+
+pub fn apply_patch(workspace: &Path, patch: &str) -> Result<String> {
+    let files = parse_patch(patch)?;
+    let mut report = String::new();
+    let mut ops: Vec<FileOp> = Vec::new();
+    let mut failed = false;
+    // What earlier entries for the same path left behind (`None`: deleted),
+    // so a file patched twice gets both rather than just the last
+    let mut staged: HashMap<PathBuf, Option<String>> = HashMap::new();
+
+    for file in &files {
+        match plan_file(workspace, file, &staged, &mut report) {
+            Ok(mut file_ops) => {
+                for op in &file_ops {
+                    match op {
+                        FileOp::Write { path, content } => staged.insert(path.clone(), Some(content.clone())),
+                        FileOp::Delete { path } => staged.insert(path.clone(), None),
+                    };
+                }
+                ops.append(&mut file_ops);
+            }
+            Err(e) => {
+                failed = true;
+                let _ = writeln!(report, "  ✗ {}", e);
+            }
+        }
+    }
+
+    if failed {
+        return Err(anyhow!(
+            "Patch NOT applied, no files were changed.\n{}Fix the rejected hunks (re-read the files first) and send the whole patch again.",
+            report
+        ));
+    }
+
+    commit(ops)?;
+    Ok(format!(
+        "Patch applied: {} file{} changed.\n{}",
+        files.len(),
+        if files.len() == 1 { "" } else { "s" },
+        report
+    ))
+}
+
+// --- Parsing ---
+
+fn parse_patch(patch: &str) -> Result<Vec<FilePatch>> {
+    let re_hunk = Regex::new(r"^@@ -(\d+)(?:,(\d+))? \+\d+(?:,\d+)? @@").unwrap();
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut current: Option<FilePatch> = None;
+    let mut last_side: Option<char> = None;
+
+    // Models often wrap the diff in a fence. Only the outermost lines can be one:
+    // context lines like " ```bash" belong to the patch.
+    let mut lines: Vec<&str> = patch.lines().collect();
+    if let Some(last) = lines.iter().rposition(|l| !l.trim().is_empty())
+        && lines[last].starts_with("```")
+    {
+        lines.remove(last);
+    }
+    if let Some(first) = lines.iter().position(|l| !l.trim().is_empty())
+        && lines[first].starts_with("```")
+    {
+        lines.remove(first);
+    }
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            files.extend(current.take());
+            let mut file = FilePatch::default();
+            if let Some((a, b)) = paths.split_once(" b/") {
+                file.old_path = Some(strip_prefix(a).to_string());
+                file.new_path = Some(b.to_string());
+            }
+            current = Some(file);
+        } else if let Some(rest) = line.strip_prefix("rename from ") {
+            if let Some(file) = current.as_mut() {
+                file.old_path = Some(rest.trim().to_string());
+            }
+        } else if let Some(rest) = line.strip_prefix("rename to ") {
+            if let Some(file) = current.as_mut() {
+                file.new_path = Some(rest.trim().to_string());
+            }
+        } else if line.starts_with("--- ") && lines.get(i + 1).is_some_and(|n| n.starts_with("+++ ")) {
+            // A `---` header starts a new file unless `diff --git` already did
+            let file = match current.take() {
+                Some(f) if f.hunks.is_empty() => f,
+                other => {
+                    files.extend(other);
+                    FilePatch::default()
+                }
+            };
+            let mut file = file;
+            file.old_path = header_path(&line[4..]);
+            file.new_path = header_path(&lines[i + 1][4..]);
+            current = Some(file);
+            i += 1;
+        } else if line.starts_with("@@") {
+            let file = current
+                .as_mut()
+                .ok_or_else(|| anyhow!("Hunk found before any file header (--- / +++)"))?;
+            let numbers = re_hunk.captures(line);
+            let old_start = numbers
+                .as_ref()
+                .and_then(|c| c[1].parse().ok())
+                .unwrap_or(0);
+            // A missing count means 1
+            let old_count = numbers
+                .as_ref()
+                .and_then(|c| c.get(2))
+                .and_then(|m| m.as_str().parse().ok())
+                .unwrap_or(1);
+            file.hunks.push(Hunk {
+                header: line.to_string(),
+                old_start,
+                old_count,
+                lines: Vec::new(),
+            });
+        } else if let Some(hunk) = current.as_mut().and_then(|f| f.hunks.last_mut()) {
+            if let Some(rest) = line.strip_prefix('+') {
+                hunk.lines.push(HunkLine::Add(rest.to_string()));
+                last_side = Some('+');
+            } else if let Some(rest) = line.strip_prefix('-') {
+                hunk.lines.push(HunkLine::Remove(rest.to_string()));
+                last_side = Some('-');
+            } else if let Some(rest) = line.strip_prefix(' ') {
+                hunk.lines.push(HunkLine::Context(rest.to_string()));
+                last_side = Some(' ');
+            } else if line.is_empty() {
+                // Editors and models strip the leading space from blank context lines
+                hunk.lines.push(HunkLine::Context(String::new()));
+                last_side = Some(' ');
+            } else if line.starts_with('\\')
+                && let Some(file) = current.as_mut()
+            {
+                match last_side {
+                    Some('-') => file.old_missing_eol = true,
+                    _ => file.new_missing_eol = true,
+                }
+            }
+        }
+        i += 1;
+    }
+    files.extend(current);
+
+    // Trailing blank lines after the last hunk are not context
+    for file in &mut files {
+        for hunk in &mut file.hunks {
+            while matches!(hunk.lines.last(), Some(HunkLine::Context(l)) if l.is_empty()) {
+                hunk.lines.pop();
+            }
+        }
+    }
+
+    files.retain(|f| f.old_path.is_some() || f.new_path.is_some());
+    if files.is_empty() {
+        return Err(anyhow!(
+            "No file changes found. Expected a unified diff with '--- a/path' / '+++ b/path' headers and '@@' hunks."
+        ));
+    }
+    Ok(files)
+}
+
+fn strip_prefix(path: &str) -> &str {
+    path.strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path)
+}
+
+/// `--- a/src/main.rs\t2024-01-01 ...` -> `Some("src/main.rs")`, `/dev/null` -> `None`
+fn header_path(raw: &str) -> Option<String> {
+    let path = raw.split('\t').next().unwrap_or(raw).trim();
+    if path == "/dev/null" {
+        None
+    } else {
+        Some(strip_prefix(path).to_string())
+    }
+}
+
+// --- Application ---
+
+fn plan_file(
+    workspace: &Path,
+    file: &FilePatch,
+    staged: &HashMap<PathBuf, Option<String>>,
+    report: &mut String,
+) -> Result<Vec<FileOp>> {
+    let name = file.display_path().to_string();
+    let old_target = match &file.old_path {
+        Some(p) => Some(resolve_in_workspace(workspace, p).map_err(|e| anyhow!("{}: {}", name, e))?),
+        None => None,
+    };
+    let new_target = match &file.new_path {
+        Some(p) => Some(resolve_in_workspace(workspace, p).map_err(|e| anyhow!("{}: {}", name, e))?),
+        None => None,
+    };
+
+    let exists = |path: &PathBuf| staged.get(path).map_or_else(|| path.exists(), Option::is_some);
+
+    let original = match &old_target {
+        Some(path) => match staged.get(path) {
+            Some(Some(content)) => content.clone(),
+            Some(None) => return Err(anyhow!("{}: file was deleted earlier in this patch", name)),
+            None => {
+                if !path.exists() {
+                    return Err(anyhow!("{}: file not found", name));
+                }
+                std::fs::read_to_string(path).map_err(|e| anyhow!("{}: {}", name, e))?
+            }
+        },
+        None => {
+            if new_target.as_ref().is_some_and(exists) {
+                return Err(anyhow!("{}: cannot create, file already exists", name));
+            }
+            String::new()
+        }
+    };
+
+    let crlf = original.contains("\r\n");
+    let had_eol = original.is_empty() || original.ends_with('\n');
+    let mut lines: Vec<String> = original
+        .lines()
+        .map(|l| l.trim_end_matches('\r').to_string())
+        .collect();
+
+    let mut hunk_report = Vec::new();
+    let mut rejected = false;
+    // Line offset introduced by earlier hunks, and where the next hunk may start
+    let mut offset: isize = 0;
+    let mut floor = 0;
+
+    for (n, hunk) in file.hunks.iter().enumerate() {
+        match apply_hunk(&mut lines, hunk, offset, floor) {
+            Some((at, fuzz, shift)) => {
+                let added = hunk.lines.iter().filter(|l| !matches!(l, HunkLine::Remove(_))).count();
+                let removed = hunk.lines.iter().filter(|l| !matches!(l, HunkLine::Add(_))).count();
+                offset += added as isize - removed as isize;
+                floor = at + added;
+                let mut note = format!("hunk {} applied at line {}", n + 1, at + 1);
+                if shift != 0 {
+                    note.push_str(&format!(" (offset {:+})", shift));
+                }
+                if fuzz > 0 {
+                    note.push_str(&format!(" (fuzz {})", fuzz));
+                }
+                hunk_report.push(note);
+            }
+            None => {
+                rejected = true;
+                hunk_report.push(format!("hunk {} REJECTED {}: context not found", n + 1, hunk.header));
+            }
+        }
+    }
+
+    let kind = match (&file.old_path, &file.new_path) {
+        (None, _) => "A",
+        (_, None) => "D",
+        (Some(a), Some(b)) if a != b => "R",
+        _ => "M",
+    };
+    if rejected {
+        return Err(anyhow!("{} {}: {}", kind, name, hunk_report.join("; ")));
+    }
+    let _ = writeln!(
+        report,
+        "  ✓ {} {}{}",
+        kind,
+        name,
+        if hunk_report.is_empty() { String::new() } else { format!(": {}", hunk_report.join("; ")) }
+    );
+
+    let mut ops = Vec::new();
+    match new_target {
+        None => {
+            if let Some(path) = old_target {
+                ops.push(FileOp::Delete { path });
+            }
+        }
+        Some(new_path) => {
+            let eol = if crlf { "\r\n" } else { "\n" };
+            let mut content = lines.join(eol);
+            let keep_eol = if file.new_missing_eol {
+                false
+            } else if file.old_missing_eol {
+                true
+            } else {
+                had_eol
+            };
+            if keep_eol && !lines.is_empty() {
+                content.push_str(eol);
+            }
+            if let Some(old_path) = old_target.filter(|p| *p != new_path) {
+                if exists(&new_path) {
+                    return Err(anyhow!("{}: rename target already exists", name));
+                }
+                ops.push(FileOp::Write { path: new_path, content });
+                ops.push(FileOp::Delete { path: old_path });
+            } else {
+                ops.push(FileOp::Write { path: new_path, content });
+            }
+        }
+    }
+    Ok(ops)
+}
+
+/// Returns `(index, fuzz, offset from the header position)` on success.
+fn apply_hunk(
+    lines: &mut Vec<String>,
+    hunk: &Hunk,
+    offset: isize,
+    floor: usize,
+) -> Option<(usize, usize, isize)> {
+    let start = if hunk.old_start == 0 {
+        floor
+    } else if hunk.old_count == 0 {
+        // `@@ -5,0 +6,2 @@` (e.g. `diff -U0`) inserts after line 5
+        (hunk.old_start as isize + offset).max(0) as usize
+    } else {
+        ((hunk.old_start as isize - 1) + offset).max(0) as usize
+    };
+
+    for fuzz in 0..=MAX_FUZZ {
+        let Some((trimmed, dropped)) = trim_context(&hunk.lines, fuzz) else {
+            break;
+        };
+        // Dropped leading context moves the rest of the hunk down
+        let expected = start + dropped;
+        let before: Vec<&str> = trimmed
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(s) | HunkLine::Remove(s) => Some(s.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect();
+        let after: Vec<String> = trimmed
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(s) | HunkLine::Add(s) => Some(s.clone()),
+                HunkLine::Remove(_) => None,
+            })
+            .collect();
+
+        let found = if before.is_empty() {
+            // Pure insertion (e.g. new file): trust the header
+            Some(expected.min(lines.len()))
+        } else {
+            find_block(lines, &before, expected, floor)
+        };
+
+        if let Some(at) = found {
+            lines.splice(at..at + before.len(), after);
+            return Some((at.saturating_sub(dropped), fuzz, at as isize - expected as isize));
+        }
+    }
+    None
+}
+
+/// Drops up to `fuzz` context lines from each end, returning what is left and
+/// how many leading lines went. `None` once nothing is left to drop.
+fn trim_context(lines: &[HunkLine], fuzz: usize) -> Option<(&[HunkLine], usize)> {
+    if fuzz == 0 {
+        return Some((lines, 0));
+    }
+    let lead = lines.iter().take_while(|l| matches!(l, HunkLine::Context(_))).count();
+    let trail = lines.iter().rev().take_while(|l| matches!(l, HunkLine::Context(_))).count();
+    if lead == lines.len() || (lead < fuzz && trail < fuzz) {
+        return None;
+    }
+    let dropped = lead.min(fuzz);
+    Some((&lines[dropped..lines.len() - trail.min(fuzz)], dropped))
+}
+
+/// Finds `block` at or after `floor`, preferring the position closest to `expected`.
+/// Tries an exact match first, then ignoring trailing whitespace, then all
+/// surrounding whitespace.
+fn find_block(lines: &[String], block: &[&str], expected: usize, floor: usize) -> Option<usize> {
+    let matchers: [fn(&str, &str) -> bool; 3] = [
+        |a, b| a == b,
+        |a, b| a.trim_end() == b.trim_end(),
+        |a, b| a.trim() == b.trim(),
+    ];
+    if block.len() > lines.len() {
+        return None;
+    }
+    let last = lines.len() - block.len();
+
+    for matches in matchers {
+        let is_match = |at: usize| block.iter().enumerate().all(|(i, b)| matches(&lines[at + i], b));
+        let mut candidates: Vec<usize> = (floor.min(last + 1)..=last).collect();
+        candidates.sort_by_key(|at| at.abs_diff(expected));
+        if let Some(at) = candidates.into_iter().find(|&at| is_match(at)) {
+            return Some(at);
+        }
+    }
+    None
+}
+
+// --- Commit ---
+
+/// Writes every staged change, restoring the originals if any step fails.
+fn commit(ops: Vec<FileOp>) -> Result<()> {
+    let mut undo: Vec<(PathBuf, Option<Vec<u8>>)> = Vec::new();
+
+    for op in &ops {
+        let result = match op {
+            FileOp::Write { path, content } => {
+                undo.push((path.clone(), std::fs::read(path).ok()));
+                write_atomic(path, content)
+            }
+            FileOp::Delete { path } => {
+                undo.push((path.clone(), std::fs::read(path).ok()));
+                std::fs::remove_file(path).map_err(Into::into)
+            }
+        };
+
+        if let Err(e) = result {
+            for (path, original) in undo.into_iter().rev() {
+                let _ = match original {
+                    Some(bytes) => std::fs::write(&path, bytes),
+                    None => std::fs::remove_file(&path),
+                };
+            }
+            return Err(anyhow!("Failed to write patch, changes rolled back: {}", e));
+        }
+    }
+    Ok(())
+}
+
+fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| anyhow!("Invalid path {:?}", path))?;
+    std::fs::create_dir_all(dir)?;
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    tmp.write_all(content.as_bytes())?;
+    tmp.persist(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn workspace(files: &[(&str, &str)]) -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn read(dir: &TempDir, path: &str) -> String {
+        fs::read_to_string(dir.path().join(path)).unwrap()
+    }
+
+    const TEN: &str = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+
+    #[test]
+    fn parses_git_and_plain_headers() {
+        let files = parse_patch(
+            "```diff\n\
+             diff --git a/old.rs b/new.rs\n\
+             similarity index 90%\n\
+             rename from old.rs\n\
+             rename to new.rs\n\
+             --- a/old.rs\n\
+             +++ b/new.rs\n\
+             @@ -1,2 +1,2 @@\n\
+             \x20a\n\
+             -b\n\
+             +c\n\
+             \\ No newline at end of file\n\
+             --- /dev/null\t2024-01-01 00:00:00\n\
+             +++ b/added.txt\n\
+             @@ -0,0 +1 @@\n\
+             +hello\n\
+             ```\n",
+        )
+        .unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].old_path.as_deref(), Some("old.rs"));
+        assert_eq!(files[0].new_path.as_deref(), Some("new.rs"));
+        assert_eq!(files[0].hunks[0].lines.len(), 3);
+        assert!(files[0].new_missing_eol);
+        assert_eq!(files[1].old_path, None);
+        assert_eq!(files[1].new_path.as_deref(), Some("added.txt"));
+        assert_eq!((files[1].hunks[0].old_start, files[1].hunks[0].old_count), (0, 0));
+
+        assert!(parse_patch("just some text").is_err());
+    }
+
+    #[test]
+    fn keeps_code_fences_inside_the_patch() {
+        let dir = workspace(&[("README.md", "run\n```bash\nrun\n```\n")]);
+        let out = apply_patch(
+            dir.path(),
+            "```diff\n\
+             --- a/README.md\n\
+             +++ b/README.md\n\
+             @@ -2,3 +2,3 @@\n\
+             \x20```bash\n\
+             -run\n\
+             +go\n\
+             \x20```\n\
+             ```\n",
+        )
+        .unwrap();
+        assert!(!out.contains("offset"), "{}", out);
+        assert_eq!(read(&dir, "README.md"), "run\n```bash\ngo\n```\n");
+    }
+
+    #[test]
+    fn applies_hunks_with_shifted_line_numbers() {
+        let dir = workspace(&[("f.txt", TEN)]);
+        // Header says line 2, the context is really at 6
+        let out = apply_patch(dir.path(), "--- a/f.txt\n+++ b/f.txt\n@@ -2,3 +2,3 @@\n 6\n-7\n+seven\n 8\n").unwrap();
+        assert!(out.contains("offset +4"), "{}", out);
+        assert_eq!(read(&dir, "f.txt"), "1\n2\n3\n4\n5\n6\nseven\n8\n9\n10\n");
+    }
+
+    #[test]
+    fn fuzz_drops_stale_context() {
+        let dir = workspace(&[("f.txt", TEN)]);
+        let out = apply_patch(
+            dir.path(),
+            "--- a/f.txt\n+++ b/f.txt\n@@ -3,5 +3,5 @@\n three\n 4\n-5\n+five\n 6\n SEVEN\n",
+        )
+        .unwrap();
+        assert!(out.contains("fuzz 1"), "{}", out);
+        assert_eq!(read(&dir, "f.txt"), "1\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n");
+    }
+
+    #[test]
+    fn zero_context_insertion_goes_after_the_header_line() {
+        let dir = workspace(&[("f.txt", TEN)]);
+        apply_patch(dir.path(), "--- a/f.txt\n+++ b/f.txt\n@@ -0,0 +1 @@\n+top\n@@ -5,0 +7,2 @@\n+a\n+b\n").unwrap();
+        assert_eq!(read(&dir, "f.txt"), "top\n1\n2\n3\n4\n5\na\nb\n6\n7\n8\n9\n10\n");
+    }
+
+    #[test]
+    fn zero_context_hunks_after_earlier_changes() {
+        let dir = workspace(&[("f.txt", TEN)]);
+        // As `diff -U0` writes it: the second header counts from the original file
+        apply_patch(
+            dir.path(),
+            "--- a/f.txt\n+++ b/f.txt\n@@ -2 +2,2 @@\n-2\n+two\n+two-b\n@@ -8,0 +10 @@\n+after8\n",
+        )
+        .unwrap();
+        assert_eq!(read(&dir, "f.txt"), "1\ntwo\ntwo-b\n3\n4\n5\n6\n7\n8\nafter8\n9\n10\n");
+    }
+
+    #[test]
+    fn creates_renames_and_deletes() {
+        let dir = workspace(&[("old.txt", "a\nb\n"), ("gone.txt", "x\n")]);
+        apply_patch(
+            dir.path(),
+            "diff --git a/old.txt b/dir/new.txt\n\
+             rename from old.txt\n\
+             rename to dir/new.txt\n\
+             --- a/old.txt\n\
+             +++ b/dir/new.txt\n\
+             @@ -1,2 +1,2 @@\n\
+             \x20a\n\
+             -b\n\
+             +c\n\
+             --- a/gone.txt\n\
+             +++ /dev/null\n\
+             @@ -1 +0,0 @@\n\
+             -x\n\
+             --- /dev/null\n\
+             +++ b/made.txt\n\
+             @@ -0,0 +1,2 @@\n\
+             +one\n\
+             +two\n",
+        )
+        .unwrap();
+        assert!(!dir.path().join("old.txt").exists());
+        assert!(!dir.path().join("gone.txt").exists());
+        assert_eq!(read(&dir, "dir/new.txt"), "a\nc\n");
+        assert_eq!(read(&dir, "made.txt"), "one\ntwo\n");
+    }
+
+    #[test]
+    fn keeps_crlf_and_a_missing_final_newline() {
+        let dir = workspace(&[("w.txt", "a\r\nb\r\n"), ("n.txt", "a\nb")]);
+        apply_patch(
+            dir.path(),
+            "--- a/w.txt\n+++ b/w.txt\n@@ -1,2 +1,2 @@\n a\n-b\n+B\n\
+             --- a/n.txt\n+++ b/n.txt\n@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+B\n\\ No newline at end of file\n",
+        )
+        .unwrap();
+        assert_eq!(read(&dir, "w.txt"), "a\r\nB\r\n");
+        assert_eq!(read(&dir, "n.txt"), "a\nB");
+    }
+
+    #[test]
+    fn a_failed_hunk_changes_nothing() {
+        let dir = workspace(&[("a.txt", "a\n"), ("b.txt", "b\n")]);
+        let err = apply_patch(
+            dir.path(),
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-a\n+A\n\
+             --- a/b.txt\n+++ b/b.txt\n@@ -1 +1 @@\n-not there\n+B\n\
+             --- /dev/null\n+++ b/c.txt\n@@ -0,0 +1 @@\n+c\n",
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("NOT applied"), "{}", err);
+        assert!(err.contains("hunk 1 REJECTED"), "{}", err);
+        assert_eq!(read(&dir, "a.txt"), "a\n");
+        assert_eq!(read(&dir, "b.txt"), "b\n");
+        assert!(!dir.path().join("c.txt").exists());
+    }
+
+    #[test]
+    fn a_failed_write_rolls_back_earlier_ones() {
+        let dir = workspace(&[("a.txt", "a\n"), ("blocker", "a file, not a directory\n")]);
+        let err = apply_patch(
+            dir.path(),
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-a\n+A\n\
+             --- /dev/null\n+++ b/blocker/new.txt\n@@ -0,0 +1 @@\n+x\n",
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("rolled back"), "{}", err);
+        assert_eq!(read(&dir, "a.txt"), "a\n");
+    }
+
+    #[test]
+    fn the_same_path_twice_applies_both() {
+        let dir = workspace(&[("f.txt", TEN)]);
+        apply_patch(
+            dir.path(),
+            "--- a/f.txt\n+++ b/f.txt\n@@ -1,2 +1,2 @@\n-1\n+one\n 2\n\
+             --- a/f.txt\n+++ b/f.txt\n@@ -9,2 +9,2 @@\n 9\n-10\n+ten\n",
+        )
+        .unwrap();
+        assert_eq!(read(&dir, "f.txt"), "one\n2\n3\n4\n5\n6\n7\n8\n9\nten\n");
+
+        let err = apply_patch(
+            dir.path(),
+            "--- a/f.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-one\n\
+             --- a/f.txt\n+++ b/f.txt\n@@ -1 +1 @@\n-one\n+1\n",
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("deleted earlier"), "{}", err);
+        assert!(dir.path().join("f.txt").exists());
+    }
+
+    #[test]
+    fn paths_outside_the_workspace_are_rejected() {
+        let dir = workspace(&[]);
+        let err = apply_patch(dir.path(), "--- /dev/null\n+++ b/../escape.txt\n@@ -0,0 +1 @@\n+x\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("outside the workspace"), "{}", err);
+    }
+}
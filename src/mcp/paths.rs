@@ -0,0 +1,157 @@
+use anyhow::{anyhow, Result};
+use std::path::{Component, Path, PathBuf};
+
+/// Where the workspace is mounted inside the sandbox. Models often use it verbatim.
+const CONTAINER_WORKSPACE: &str = "/workspace";
+
+/// Resolves a model-supplied path to a host path inside the workspace.
+///
+/// Relative paths are joined onto the workspace; absolute paths are accepted only
+/// when they point into the workspace (host path or the `/workspace` mount).
+/// `..` components are resolved lexically and the deepest existing ancestor is
+/// canonicalized, so neither traversal nor symlinks can escape the workspace.
+pub fn resolve_in_workspace(workspace: &Path, requested: &str) -> Result<PathBuf> {
+    let root = std::fs::canonicalize(workspace)
+        .map_err(|e| anyhow!("Workspace {:?} is not accessible: {}", workspace, e))?;
+    let outside = || {
+        anyhow!(
+            "Access denied: '{}' is outside the workspace. Use a path relative to the workspace root.",
+            requested
+        )
+    };
+
+    let requested_path = Path::new(requested);
+    let relative: PathBuf = if requested_path.is_absolute() {
+        if let Ok(rest) = requested_path.strip_prefix(CONTAINER_WORKSPACE) {
+            rest.to_path_buf()
+        } else if let Ok(rest) = requested_path.strip_prefix(&root) {
+            rest.to_path_buf()
+        } else if let Ok(rest) = requested_path.strip_prefix(workspace) {
+            rest.to_path_buf()
+        } else {
+            return Err(outside());
+        }
+    } else {
+        requested_path.to_path_buf()
+    };
+
+    // Lexical normalization: `..` may not climb above the root
+    let mut normalized = root.clone();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if normalized == root {
+                    return Err(outside());
+                }
+                normalized.pop();
+            }
+            Component::RootDir | Component::Prefix(_) => return Err(outside()),
+        }
+    }
+
+    // Symlinks: whatever already exists along the path must resolve inside the root.
+    // The missing tail (e.g. a file about to be created) is plain names by now.
+    let mut existing = normalized.as_path();
+    loop {
+        if existing.symlink_metadata().is_ok() {
+            let canonical = std::fs::canonicalize(existing).map_err(|_| outside())?;
+            if !canonical.starts_with(&root) {
+                return Err(outside());
+            }
+            break;
+        }
+        match existing.parent() {
+            Some(parent) => existing = parent,
+            None => return Err(outside()),
+        }
+    }
+
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use tempfile::TempDir;
+
+    /// A temp dir holding `ws/` (the workspace, with `src/lib.rs`) and `outside/secret`.
+    fn setup() -> (TempDir, PathBuf, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let ws = dir.path().join("ws");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(ws.join("src")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(ws.join("src/lib.rs"), "").unwrap();
+        fs::write(outside.join("secret"), "").unwrap();
+        (dir, ws, outside)
+    }
+
+    fn denied(result: Result<PathBuf>) -> bool {
+        matches!(result, Err(e) if e.to_string().contains("outside the workspace"))
+    }
+
+    #[test]
+    fn relative_paths_resolve_into_the_workspace() {
+        let (_dir, ws, _) = setup();
+        let root = fs::canonicalize(&ws).unwrap();
+        assert_eq!(resolve_in_workspace(&ws, "src/lib.rs").unwrap(), root.join("src/lib.rs"));
+        assert_eq!(resolve_in_workspace(&ws, "./src/../src/new.rs").unwrap(), root.join("src/new.rs"));
+        assert_eq!(resolve_in_workspace(&ws, ".").unwrap(), root);
+    }
+
+    #[test]
+    fn parent_traversal_is_denied() {
+        let (_dir, ws, _) = setup();
+        assert!(denied(resolve_in_workspace(&ws, "../../etc/passwd")));
+        assert!(denied(resolve_in_workspace(&ws, "src/../../outside/secret")));
+        assert!(denied(resolve_in_workspace(&ws, "..")));
+    }
+
+    #[test]
+    fn absolute_paths_must_point_into_the_workspace() {
+        let (_dir, ws, outside) = setup();
+        let root = fs::canonicalize(&ws).unwrap();
+        let host = ws.join("src/lib.rs");
+        assert_eq!(resolve_in_workspace(&ws, host.to_str().unwrap()).unwrap(), root.join("src/lib.rs"));
+        assert_eq!(resolve_in_workspace(&ws, "/workspace/src/lib.rs").unwrap(), root.join("src/lib.rs"));
+        assert_eq!(resolve_in_workspace(&ws, "/workspace").unwrap(), root);
+        assert!(denied(resolve_in_workspace(&ws, "/etc/passwd")));
+        assert!(denied(resolve_in_workspace(&ws, outside.join("secret").to_str().unwrap())));
+        assert!(denied(resolve_in_workspace(&ws, "/workspace/../etc/passwd")));
+        // Only the whole component counts, `/workspace2` is not the mount
+        assert!(denied(resolve_in_workspace(&ws, "/workspace2/file")));
+    }
+
+    #[test]
+    fn symlinked_directory_pointing_outside_is_denied() {
+        let (_dir, ws, outside) = setup();
+        symlink(&outside, ws.join("link")).unwrap();
+        assert!(denied(resolve_in_workspace(&ws, "link/secret")));
+        assert!(denied(resolve_in_workspace(&ws, "link")));
+    }
+
+    #[test]
+    fn dangling_symlink_to_outside_is_denied() {
+        let (_dir, ws, outside) = setup();
+        symlink(outside.join("not-yet"), ws.join("dangling")).unwrap();
+        assert!(denied(resolve_in_workspace(&ws, "dangling")));
+    }
+
+    #[test]
+    fn new_file_under_a_symlinked_parent_is_checked() {
+        let (_dir, ws, outside) = setup();
+        symlink(&outside, ws.join("out")).unwrap();
+        symlink(ws.join("src"), ws.join("inside")).unwrap();
+        assert!(denied(resolve_in_workspace(&ws, "out/new/file.txt")));
+        // A symlink that stays in the workspace is fine
+        let root = fs::canonicalize(&ws).unwrap();
+        assert_eq!(
+            resolve_in_workspace(&ws, "inside/new.rs").unwrap(),
+            root.join("inside/new.rs")
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+// JSON-RPC 2.0 framing for the Model Context Protocol (newline-delimited over stdio)
+
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcMessage {
+    #[serde(default)]
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl JsonRpcMessage {
+    pub fn is_response(&self) -> bool {
+        self.id.is_some() && self.method.is_none()
+    }
+}
+
+pub fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+pub fn response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+pub fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// Flattens a `tools/call` result into the plain text the agent loop works with.
+pub fn tool_result_text(result: &Value) -> String {
+    let Some(content) = result.get("content").and_then(|c| c.as_array()) else {
+        return result.to_string();
+    };
+
+    content
+        .iter()
+        .map(|block| match block.get("type").and_then(|t| t.as_str()) {
+            Some("text") => block
+                .get("text")
+                .and_then(|t| t.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            Some("resource") => {
+                let resource = block.get("resource").cloned().unwrap_or_default();
+                resource
+                    .get("text")
+                    .or_else(|| resource.get("uri"))
+                    .and_then(|t| t.as_str())
+                    .unwrap_or_default()
+                    .to_string()
+            }
+            Some(other) => {
+                let mime = block
+                    .get("mimeType")
+                    .and_then(|m| m.as_str())
+                    .unwrap_or("unknown");
+                format!("[{} content: {}]", other, mime)
+            }
+            None => block.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_responses_from_requests() {
+        let parse = |v: Value| serde_json::from_value::<JsonRpcMessage>(v).unwrap();
+        assert!(parse(response(json!(1), json!({}))).is_response());
+        assert!(parse(error_response(json!(1), PARSE_ERROR, "bad")).is_response());
+        assert!(!parse(request(1, "ping", json!({}))).is_response());
+        assert!(!parse(notification("notifications/initialized", json!({}))).is_response());
+    }
+
+    #[test]
+    fn flattens_tool_results_to_text() {
+        let result = json!({ "content": [
+            { "type": "text", "text": "line one" },
+            { "type": "resource", "resource": { "uri": "file:///a.rs", "text": "fn main() {}" } },
+            { "type": "resource", "resource": { "uri": "file:///b.bin" } },
+            { "type": "audio", "mimeType": "audio/wav", "data": "" },
+        ]});
+        assert_eq!(
+            tool_result_text(&result),
+            "line one\nfn main() {}\nfile:///b.bin\n[audio content: audio/wav]"
+        );
+
+        // Not the content-block shape: hand the raw JSON back
+        assert_eq!(tool_result_text(&json!({ "value": 3 })), "{\"value\":3}");
+    }
+}
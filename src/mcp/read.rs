@@ -0,0 +1,276 @@
+use anyhow::{anyhow, Result};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+// Paged, line-numbered reads for the `read_file` tool
+
+pub const DEFAULT_LIMIT: usize = 300;
+
+/// Bytes sniffed for NUL bytes / invalid UTF-8 before deciding a file is binary
+const SNIFF_BYTES: usize = 8192;
+
+pub struct ReadOptions {
+    /// 1-based first line
+    pub offset: usize,
+    pub limit: usize,
+    pub line_numbers: bool,
+    /// Cap on the returned text, whatever `limit` says
+    pub max_bytes: usize,
+}
+
+pub fn read_file(target: &Path, display: &str, opts: &ReadOptions) -> Result<String> {
+    let meta = std::fs::metadata(target)?;
+    if meta.is_dir() {
+        return Err(anyhow!("{} is a directory. Use list_files instead.", display));
+    }
+
+    let mut file = File::open(target)?;
+    let mut head = vec![0u8; SNIFF_BYTES.min(meta.len() as usize)];
+    file.read_exact(&mut head)?;
+    if let Some(kind) = binary_kind(&head) {
+        return Ok(format!(
+            "{} is a binary file ({}, {}). Its contents were not read.",
+            display,
+            kind,
+            human_size(meta.len())
+        ));
+    }
+    if meta.len() == 0 {
+        return Ok(format!("{} is empty.", display));
+    }
+
+    let mut reader = BufReader::new(head.as_slice().chain(file));
+    let first = opts.offset.max(1);
+    let last = first.saturating_add(opts.limit.max(1)) - 1;
+
+    let mut body = String::new();
+    let mut total = 0;
+    let mut shown_to = 0;
+    let mut capped = false;
+    let mut buf = Vec::new();
+
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        total += 1;
+        if total < first || total > last || capped {
+            continue;
+        }
+
+        let line = String::from_utf8_lossy(&buf);
+        let line = line.trim_end_matches(['\n', '\r']);
+        let mut entry = String::new();
+        if opts.line_numbers {
+            let _ = write!(entry, "{:>6}\t", total);
+        }
+        entry.push_str(line);
+        entry.push('\n');
+
+        if body.len() + entry.len() > opts.max_bytes {
+            capped = true;
+            // A single huge line (minified code) still shows its start
+            if body.is_empty() {
+                let mut cut = opts.max_bytes;
+                while !entry.is_char_boundary(cut) {
+                    cut -= 1;
+                }
+                body.push_str(&entry[..cut]);
+                body.push_str("...[line truncated]\n");
+                shown_to = total;
+            }
+            continue;
+        }
+        body.push_str(&entry);
+        shown_to = total;
+    }
+
+    if first > total {
+        return Ok(format!(
+            "{} has {} lines; offset {} is past the end of the file.",
+            display, total, first
+        ));
+    }
+
+    let mut out = format!(
+        "{} (lines {}-{} of {}, {})\n",
+        display,
+        first,
+        shown_to,
+        total,
+        human_size(meta.len())
+    );
+    out.push_str(&body);
+    if shown_to < total {
+        let reason = if capped {
+            format!("Output capped at {} bytes", opts.max_bytes)
+        } else {
+            "More lines follow".to_string()
+        };
+        let _ = write!(
+            out,
+            "...[{}. Use offset={} to continue.]",
+            reason,
+            shown_to + 1
+        );
+    }
+    Ok(out)
+}
+
+/// `Some(description)` when the sniffed bytes don't look like text.
+fn binary_kind(head: &[u8]) -> Option<&'static str> {
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"\x89PNG", "PNG image"),
+        (b"\xFF\xD8\xFF", "JPEG image"),
+        (b"GIF8", "GIF image"),
+        (b"%PDF", "PDF document"),
+        (b"PK\x03\x04", "ZIP archive"),
+        (b"\x1F\x8B", "gzip archive"),
+        (b"\x7FELF", "ELF executable"),
+        (b"\0asm", "WebAssembly module"),
+    ];
+    if let Some((_, kind)) = MAGIC.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return Some(kind);
+    }
+    if head.contains(&0) {
+        return Some("binary data");
+    }
+    // The sniff window may cut a multi-byte character in half
+    match std::str::from_utf8(head) {
+        Ok(_) => None,
+        Err(e) if e.error_len().is_none() => None,
+        Err(_) => Some("non-UTF-8 data"),
+    }
+}
+
+pub(super) fn human_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 20 => format!("{:.1} MB", b as f64 / (1 << 20) as f64),
+        b if b >= 1 << 10 => format!("{:.1} KB", b as f64 / (1 << 10) as f64),
+        b => format!("{} bytes", b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn workspace(files: &[(&str, &[u8])]) -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, content) in files {
+            fs::write(dir.path().join(path), content).unwrap();
+        }
+        dir
+    }
+
+    fn read(dir: &TempDir, path: &str, opts: &ReadOptions) -> String {
+        read_file(&dir.path().join(path), path, opts).unwrap()
+    }
+
+    fn opts(offset: usize, limit: usize, max_bytes: usize) -> ReadOptions {
+        ReadOptions {
+            offset,
+            limit,
+            line_numbers: false,
+            max_bytes,
+        }
+    }
+
+    const TEN: &[u8] = b"1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+
+    #[test]
+    fn pages_with_offset_and_limit() {
+        let dir = workspace(&[("f.txt", TEN)]);
+        let numbered = ReadOptions {
+            line_numbers: true,
+            ..opts(3, 4, 10_000)
+        };
+
+        assert_eq!(
+            read(&dir, "f.txt", &numbered),
+            "f.txt (lines 3-6 of 10, 21 bytes)\n\
+             \x20    3\t3\n     4\t4\n     5\t5\n     6\t6\n\
+             ...[More lines follow. Use offset=7 to continue.]"
+        );
+        // The last page has no continuation hint
+        assert_eq!(
+            read(&dir, "f.txt", &opts(9, 4, 10_000)),
+            "f.txt (lines 9-10 of 10, 21 bytes)\n9\n10\n"
+        );
+    }
+
+    #[test]
+    fn offset_past_the_end_says_how_long_the_file_is() {
+        let dir = workspace(&[("f.txt", TEN)]);
+        assert_eq!(
+            read(&dir, "f.txt", &opts(20, 5, 10_000)),
+            "f.txt has 10 lines; offset 20 is past the end of the file."
+        );
+    }
+
+    #[test]
+    fn max_bytes_caps_the_page() {
+        let dir = workspace(&[("f.txt", TEN)]);
+        assert_eq!(
+            read(&dir, "f.txt", &opts(1, 300, 5)),
+            "f.txt (lines 1-2 of 10, 21 bytes)\n1\n2\n\
+             ...[Output capped at 5 bytes. Use offset=3 to continue.]"
+        );
+    }
+
+    #[test]
+    fn one_huge_line_is_cut_on_a_char_boundary() {
+        let line = format!("{}\n", "é".repeat(10));
+        let dir = workspace(&[("min.js", line.as_bytes())]);
+
+        // Byte 5 falls inside the third "é"
+        assert_eq!(
+            read(&dir, "min.js", &opts(1, 300, 5)),
+            "min.js (lines 1-1 of 1, 21 bytes)\néé...[line truncated]\n"
+        );
+    }
+
+    #[test]
+    fn detects_binary_files() {
+        let dir = workspace(&[
+            ("logo.png", b"\x89PNG\r\n\x1a\n"),
+            ("data.bin", b"abc\0def"),
+            ("latin1.txt", b"caf\xe9 au lait"),
+        ]);
+        let opts = opts(1, 300, 10_000);
+
+        assert_eq!(
+            read(&dir, "logo.png", &opts),
+            "logo.png is a binary file (PNG image, 8 bytes). Its contents were not read."
+        );
+        assert!(read(&dir, "data.bin", &opts).contains("(binary data, 7 bytes)"));
+        assert!(read(&dir, "latin1.txt", &opts).contains("(non-UTF-8 data, 12 bytes)"));
+    }
+
+    #[test]
+    fn multibyte_char_split_by_the_sniff_window_is_still_text() {
+        let mut content = "a".repeat(SNIFF_BYTES - 1);
+        content.push_str("é\n");
+        let dir = workspace(&[("long.txt", content.as_bytes())]);
+
+        let out = read(&dir, "long.txt", &opts(1, 300, 100_000));
+        assert!(out.starts_with("long.txt (lines 1-1 of 1, 8.0 KB)\n"));
+        assert!(out.ends_with("aé\n"));
+    }
+
+    #[test]
+    fn empty_files_and_directories() {
+        let dir = workspace(&[("empty.txt", b"")]);
+        fs::create_dir(dir.path().join("src")).unwrap();
+        let opts = opts(1, 300, 10_000);
+
+        assert_eq!(read(&dir, "empty.txt", &opts), "empty.txt is empty.");
+        let err = read_file(&dir.path().join("src"), "src", &opts).unwrap_err();
+        assert_eq!(err.to_string(), "src is a directory. Use list_files instead.");
+    }
+}
@@ -0,0 +1,367 @@
+use super::read::human_size;
+use anyhow::{anyhow, Result};
+use globset::{Glob, GlobMatcher};
+use ignore::WalkBuilder;
+use regex::RegexBuilder;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+// Workspace search for the `grep` and `glob` tools.
+// Both walk the tree in path order so `offset` pages are stable between calls.
+
+pub const DEFAULT_RESULTS: usize = 50;
+pub const MAX_RESULTS: usize = 200;
+
+/// Longer lines are clipped in grep output (minified files, lock files)
+const MAX_LINE_CHARS: usize = 300;
+/// grep reads whole files; bigger ones (logs, dumps, datasets) are skipped
+const MAX_GREP_FILE_BYTES: u64 = 2 << 20;
+
+pub struct GrepOptions {
+    pub pattern: String,
+    /// Only search files whose path (relative to the search root) matches
+    pub glob: Option<String>,
+    pub context: usize,
+    pub case_insensitive: bool,
+    /// List matching files instead of matching lines
+    pub files_only: bool,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+pub fn grep(workspace: &Path, root: &Path, opts: &GrepOptions) -> Result<String> {
+    let re = RegexBuilder::new(&opts.pattern)
+        .case_insensitive(opts.case_insensitive)
+        .build()
+        .map_err(|e| anyhow!("Invalid regex: {}", e))?;
+    let filter = opts.glob.as_deref().map(compile_glob).transpose()?;
+    let workspace = std::fs::canonicalize(workspace)?;
+    let limit = opts.limit.clamp(1, MAX_RESULTS);
+
+    let mut out = String::new();
+    let mut seen = 0; // results before and inside the page
+    let mut files = 0;
+    let mut skipped = 0;
+    let mut more = false;
+
+    'files: for path in walk_files(root) {
+        if filter.as_ref().is_some_and(|g| !g.is_match(relative(root, &path))) {
+            continue;
+        }
+        let rel = relative(&workspace, &path);
+        match std::fs::metadata(&path) {
+            Ok(meta) if meta.len() > MAX_GREP_FILE_BYTES => {
+                skipped += 1;
+                continue;
+            }
+            Ok(_) => {}
+            Err(_) => continue,
+        }
+        let Ok(bytes) = std::fs::read(&path) else {
+            continue;
+        };
+        if bytes.iter().take(8192).any(|b| *b == 0) {
+            continue;
+        }
+        let text = String::from_utf8_lossy(&bytes);
+        let lines: Vec<&str> = text.lines().collect();
+        let hits: Vec<usize> = (0..lines.len()).filter(|&i| re.is_match(lines[i])).collect();
+        if hits.is_empty() {
+            continue;
+        }
+        files += 1;
+
+        if opts.files_only {
+            seen += 1;
+            if seen > opts.offset + limit {
+                more = true;
+                break;
+            }
+            if seen > opts.offset {
+                let _ = writeln!(out, "{} ({} match{})", rel, hits.len(), if hits.len() == 1 { "" } else { "es" });
+            }
+            continue;
+        }
+
+        // Matches on this page, then their context windows merged into groups
+        let mut page = Vec::new();
+        for &hit in &hits {
+            seen += 1;
+            if seen > opts.offset + limit {
+                more = true;
+                break;
+            }
+            if seen > opts.offset {
+                page.push(hit);
+            }
+        }
+        let mut last_printed: Option<usize> = None;
+        for &hit in &page {
+            let start = hit.saturating_sub(opts.context);
+            let end = (hit + opts.context).min(lines.len() - 1);
+            let from = match last_printed {
+                Some(prev) if start <= prev + 1 => prev + 1,
+                _ => {
+                    // `--` separates context groups, like grep -C
+                    if opts.context > 0 && (last_printed.is_some() || !out.is_empty()) {
+                        out.push_str("--\n");
+                    }
+                    start
+                }
+            };
+            for (i, line) in lines.iter().enumerate().take(end + 1).skip(from) {
+                let sep = if hits.binary_search(&i).is_ok() { ':' } else { '-' };
+                let _ = writeln!(out, "{}{}{}{}{}", rel, sep, i + 1, sep, clip(line));
+            }
+            last_printed = Some(end);
+        }
+        if more {
+            break 'files;
+        }
+    }
+
+    let unit = if opts.files_only { "files" } else { "matches" };
+    if seen == 0 {
+        out = format!("No matches for /{}/.", opts.pattern);
+    } else if seen <= opts.offset {
+        out = format!("Only {} {} found; offset {} is past the end.", seen, unit, opts.offset);
+    } else {
+        let shown = seen.min(opts.offset + limit);
+        let _ = write!(out, "[{} {}-{}", unit, opts.offset + 1, shown);
+        if more {
+            let _ = write!(out, ", more available. Use offset={} to continue.]", shown);
+        } else if opts.files_only {
+            let _ = write!(out, " of {}]", seen);
+        } else {
+            let _ = write!(out, " of {} in {} file{}]", seen, files, if files == 1 { "" } else { "s" });
+        }
+    }
+    if skipped > 0 {
+        let _ = write!(
+            out,
+            "\n[Skipped {} file{} over {}; page through large files with read_file.]",
+            skipped,
+            if skipped == 1 { "" } else { "s" },
+            human_size(MAX_GREP_FILE_BYTES)
+        );
+    }
+    Ok(out)
+}
+
+pub fn glob(workspace: &Path, root: &Path, pattern: &str, offset: usize, limit: usize) -> Result<String> {
+    let matcher = compile_glob(pattern)?;
+    let workspace = std::fs::canonicalize(workspace)?;
+    let limit = limit.clamp(1, MAX_RESULTS);
+
+    let mut out = String::new();
+    let mut seen = 0;
+    let mut more = false;
+    for path in walk_files(root) {
+        // Match relative to the search root so `*.rs` works from any directory
+        let from_root = relative(root, &path);
+        if !matcher.is_match(&from_root) {
+            continue;
+        }
+        seen += 1;
+        if seen > offset + limit {
+            more = true;
+            break;
+        }
+        if seen > offset {
+            let _ = writeln!(out, "{}", relative(&workspace, &path));
+        }
+    }
+
+    if seen == 0 {
+        return Ok(format!("No files match '{}'.", pattern));
+    }
+    if seen <= offset {
+        return Ok(format!("Only {} files match; offset {} is past the end.", seen, offset));
+    }
+    let shown = seen.min(offset + limit);
+    if more {
+        let _ = write!(out, "[files {}-{}, more available. Use offset={} to continue.]", offset + 1, shown, shown);
+    } else {
+        let _ = write!(out, "[files {}-{} of {}]", offset + 1, shown, seen);
+    }
+    Ok(out)
+}
+
+/// Walks `root` in path order, honouring `.gitignore` and `.agerusignore`.
+pub(super) fn walker(root: &Path) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(root);
+    builder
+        // The workspace may not be a git repo; honour its .gitignore anyway
+        .require_git(false)
+        .add_custom_ignore_filename(".agerusignore")
+        .sort_by_file_path(|a, b| a.cmp(b));
+    builder
+}
+
+/// Files under `root` in path order, skipping hidden and ignored entries.
+fn walk_files(root: &Path) -> impl Iterator<Item = PathBuf> {
+    walker(root)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+}
+
+fn compile_glob(pattern: &str) -> Result<GlobMatcher> {
+    Glob::new(pattern)
+        .map(|g| g.compile_matcher())
+        .map_err(|e| anyhow!("Invalid glob '{}': {}", pattern, e))
+}
+
+fn relative(base: &Path, path: &Path) -> String {
+    path.strip_prefix(base)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+fn clip(line: &str) -> String {
+    if line.chars().count() > MAX_LINE_CHARS {
+        let clipped: String = line.chars().take(MAX_LINE_CHARS).collect();
+        format!("{}...", clipped)
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn workspace(files: &[(&str, &str)]) -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        for (path, content) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        (dir, root)
+    }
+
+    fn opts(pattern: &str) -> GrepOptions {
+        GrepOptions {
+            pattern: pattern.into(),
+            glob: None,
+            context: 0,
+            case_insensitive: false,
+            files_only: false,
+            offset: 0,
+            limit: DEFAULT_RESULTS,
+        }
+    }
+
+    #[test]
+    fn merges_overlapping_context_and_separates_groups() {
+        let (_dir, root) = workspace(&[
+            ("f.rs", "fn a\nx\nhit\ny\nhit\nz\nz\nz\nw\nhit\nend\n"),
+            ("g.rs", "hit\n"),
+        ]);
+        let opts = GrepOptions {
+            context: 1,
+            ..opts("hit")
+        };
+
+        assert_eq!(
+            grep(&root, &root, &opts).unwrap(),
+            "f.rs-2-x\nf.rs:3:hit\nf.rs-4-y\nf.rs:5:hit\nf.rs-6-z\n\
+             --\n\
+             f.rs-9-w\nf.rs:10:hit\nf.rs-11-end\n\
+             --\n\
+             g.rs:1:hit\n\
+             [matches 1-4 of 4 in 2 files]"
+        );
+    }
+
+    #[test]
+    fn pages_matches_with_offset_and_limit() {
+        let (_dir, root) = workspace(&[("f.txt", "hit\nhit\nhit\nhit\nhit\n")]);
+        let page = |offset, limit| {
+            grep(&root, &root, &GrepOptions { offset, limit, ..opts("hit") }).unwrap()
+        };
+
+        assert_eq!(
+            page(0, 2),
+            "f.txt:1:hit\nf.txt:2:hit\n[matches 1-2, more available. Use offset=2 to continue.]"
+        );
+        assert_eq!(page(4, 2), "f.txt:5:hit\n[matches 5-5 of 5 in 1 file]");
+        assert_eq!(page(10, 2), "Only 5 matches found; offset 10 is past the end.");
+        assert_eq!(
+            grep(&root, &root, &opts("miss")).unwrap(),
+            "No matches for /miss/."
+        );
+    }
+
+    #[test]
+    fn glob_filter_is_relative_to_the_search_root() {
+        let (_dir, root) = workspace(&[
+            ("nested/top.rs", "hit\n"),
+            ("src/lib.rs", "hit\n"),
+            ("src/nested/b.rs", "hit\n"),
+        ]);
+        let opts = GrepOptions {
+            glob: Some("nested/*.rs".into()),
+            files_only: true,
+            ..opts("hit")
+        };
+
+        // Paths are still reported relative to the workspace
+        assert_eq!(
+            grep(&root, &root.join("src"), &opts).unwrap(),
+            "src/nested/b.rs (1 match)\n[files 1-1 of 1]"
+        );
+        assert_eq!(
+            grep(&root, &root, &opts).unwrap(),
+            "nested/top.rs (1 match)\n[files 1-1 of 1]"
+        );
+    }
+
+    #[test]
+    fn skips_ignored_hidden_and_oversized_files() {
+        let big = format!("hit\n{}", "x".repeat(MAX_GREP_FILE_BYTES as usize));
+        let (_dir, root) = workspace(&[
+            (".gitignore", "target/\n"),
+            (".agerusignore", "secrets.txt\n"),
+            (".env", "hit\n"),
+            ("target/out.rs", "hit\n"),
+            ("secrets.txt", "hit\n"),
+            ("big.log", &big),
+            ("src/main.rs", "hit\n"),
+        ]);
+        let opts = GrepOptions {
+            files_only: true,
+            ..opts("hit")
+        };
+
+        assert_eq!(
+            grep(&root, &root, &opts).unwrap(),
+            "src/main.rs (1 match)\n[files 1-1 of 1]\n\
+             [Skipped 1 file over 2.0 MB; page through large files with read_file.]"
+        );
+    }
+
+    #[test]
+    fn glob_lists_files_in_path_order() {
+        let (_dir, root) = workspace(&[
+            ("src/b.rs", ""),
+            ("src/a.rs", ""),
+            ("src/notes.md", ""),
+        ]);
+
+        assert_eq!(
+            glob(&root, &root.join("src"), "*.rs", 0, 1).unwrap(),
+            "src/a.rs\n[files 1-1, more available. Use offset=1 to continue.]"
+        );
+        assert_eq!(
+            glob(&root, &root.join("src"), "*.rs", 0, 10).unwrap(),
+            "src/a.rs\nsrc/b.rs\n[files 1-2 of 2]"
+        );
+    }
+}
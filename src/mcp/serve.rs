@@ -0,0 +1,243 @@
+use super::protocol::{self, JsonRpcMessage, PROTOCOL_VERSION};
+use super::{builtin_tools, McpRequest, McpServer};
+use crate::app::AppEvent;
+use crate::config::{Config, SandboxMode};
+use crate::docker_setup::runtime::ContainerRuntime;
+use crate::shell::{ShellRequest, ShellSession};
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+// `agerus mcp-serve`: the built-in tools as an MCP server over stdio, no TUI.
+// stdout carries JSON-RPC only; diagnostics go to stderr.
+
+pub async fn run(config: Config, runtime: Arc<dyn ContainerRuntime>) -> Result<()> {
+    // Don't re-export external servers, a client can connect to those directly
+    let mut config = config;
+    config.mcp_servers.clear();
+    let instructions = match config.sandbox {
+        SandboxMode::Container => {
+            "Tools run inside the Agerus container sandbox. Paths are relative to the mounted workspace."
+        }
+        SandboxMode::None => {
+            "Tools run directly on this machine, without a sandbox. Paths are relative to the workspace."
+        }
+    };
+
+    // Nothing renders AppEvents in this mode, drain them
+    let (tx_app_event, mut rx_app_event) = mpsc::channel::<AppEvent>(100);
+    tokio::spawn(async move { while rx_app_event.recv().await.is_some() {} });
+
+    let (tx_shell, rx_shell) = mpsc::channel::<ShellRequest>(100);
+    let tx_shell_evt = tx_app_event.clone();
+    let shell_config = config.clone();
+    let shell_runtime = runtime.clone();
+    tokio::spawn(async move {
+        ShellSession::run_actor(rx_shell, tx_shell_evt, shell_config, shell_runtime).await;
+    });
+
+    let (mcp_tx, processes) = McpServer::start(tx_shell, config, runtime, tx_app_event).await;
+    let stdout = Arc::new(Mutex::new(tokio::io::stdout()));
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let msg = match serde_json::from_str::<JsonRpcMessage>(&line) {
+            Ok(msg) => msg,
+            Err(e) => {
+                let reply = protocol::error_response(Value::Null, protocol::PARSE_ERROR, &e.to_string());
+                write_message(&stdout, &reply).await?;
+                continue;
+            }
+        };
+
+        // Notifications (no id) need no reply
+        let (Some(id), Some(method)) = (msg.id, msg.method) else {
+            continue;
+        };
+
+        // Handle each request on its own task so `ping` is answered during long tool calls
+        let mcp_tx = mcp_tx.clone();
+        let stdout = stdout.clone();
+        tokio::spawn(async move {
+            let params = msg.params.unwrap_or(Value::Null);
+            let reply = handle_request(id, &method, params, instructions, &mcp_tx).await;
+            if let Err(e) = write_message(&stdout, &reply).await {
+                eprintln!("agerus mcp-serve: failed to write reply: {}", e);
+            }
+        });
+    }
+
+    // Client hung up; don't leave its background processes running
+    processes.kill_all().await;
+
+    Ok(())
+}
+
+async fn handle_request(
+    id: Value,
+    method: &str,
+    params: Value,
+    instructions: &str,
+    mcp_tx: &mpsc::Sender<McpRequest>,
+) -> Value {
+    match method {
+        "initialize" => {
+            // Echo the client's version when it asks for an older one we can still speak
+            let version = params
+                .get("protocolVersion")
+                .and_then(|v| v.as_str())
+                .filter(|v| *v <= PROTOCOL_VERSION)
+                .unwrap_or(PROTOCOL_VERSION);
+            protocol::response(
+                id,
+                json!({
+                    "protocolVersion": version,
+                    "capabilities": { "tools": { "listChanged": false } },
+                    "serverInfo": { "name": "agerus", "version": env!("CARGO_PKG_VERSION") },
+                    "instructions": instructions
+                }),
+            )
+        }
+        "ping" => protocol::response(id, json!({})),
+        "tools/list" => {
+            let tools: Vec<Value> = builtin_tools()
+                .into_iter()
+                .map(|t| {
+                    json!({
+                        "name": t.name,
+                        "description": t.description,
+                        "inputSchema": t.input_schema
+                    })
+                })
+                .collect();
+            protocol::response(id, json!({ "tools": tools }))
+        }
+        "tools/call" => {
+            let Some(name) = params.get("name").and_then(|n| n.as_str()) else {
+                return protocol::error_response(id, protocol::INVALID_PARAMS, "Missing tool name");
+            };
+            if !builtin_tools().iter().any(|t| t.name == name) {
+                let message = format!("Unknown tool: {}", name);
+                return protocol::error_response(id, protocol::INVALID_PARAMS, &message);
+            }
+            let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+
+            let (tx, rx) = oneshot::channel();
+            let sent = mcp_tx
+                .send(McpRequest::CallTool {
+                    name: name.to_string(),
+                    arguments,
+                    response_tx: tx,
+                })
+                .await;
+
+            // A failed send hands back the request, reply sender and all, so don't wait on it
+            let reply = match sent {
+                Ok(()) => rx.await.ok(),
+                Err(_) => None,
+            };
+            let (text, is_error) = match reply {
+                Some(Ok(out)) => (out.text, false),
+                Some(Err(e)) => (format!("Tool Execution Error: {}", e), true),
+                None => ("Tool server unavailable".to_string(), true),
+            };
+            protocol::response(
+                id,
+                json!({
+                    "content": [{ "type": "text", "text": text }],
+                    "isError": is_error
+                }),
+            )
+        }
+        _ => protocol::error_response(id, protocol::METHOD_NOT_FOUND, "Method not found"),
+    }
+}
+
+async fn write_message(stdout: &Mutex<tokio::io::Stdout>, message: &Value) -> Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    let mut stdout = stdout.lock().await;
+    stdout.write_all(line.as_bytes()).await?;
+    stdout.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn call(method: &str, params: Value) -> Value {
+        // No tool server behind it: calls that reach it report it unavailable
+        let (mcp_tx, _) = mpsc::channel(1);
+        handle_request(json!(1), method, params, "instructions", &mcp_tx).await
+    }
+
+    #[tokio::test]
+    async fn initialize_negotiates_the_protocol_version() {
+        let reply = call("initialize", json!({ "protocolVersion": "2024-11-05" })).await;
+        assert_eq!(reply["result"]["protocolVersion"], "2024-11-05");
+        assert_eq!(reply["result"]["serverInfo"]["name"], "agerus");
+        assert_eq!(reply["result"]["instructions"], "instructions");
+
+        let reply = call("initialize", json!({ "protocolVersion": "2999-01-01" })).await;
+        assert_eq!(reply["result"]["protocolVersion"], PROTOCOL_VERSION);
+
+        let reply = call("initialize", json!({})).await;
+        assert_eq!(reply["result"]["protocolVersion"], PROTOCOL_VERSION);
+    }
+
+    #[tokio::test]
+    async fn tools_list_gives_each_builtin_tool_an_input_schema() {
+        let reply = call("tools/list", Value::Null).await;
+        let tools = reply["result"]["tools"].as_array().unwrap();
+        assert_eq!(tools.len(), builtin_tools().len());
+        for tool in tools {
+            assert!(tool["name"].is_string());
+            assert!(tool["description"].is_string());
+            assert_eq!(tool["inputSchema"]["type"], "object");
+        }
+    }
+
+    #[tokio::test]
+    async fn bad_requests_get_json_rpc_errors() {
+        let reply = call("tools/call", json!({ "name": "launch_rockets" })).await;
+        assert_eq!(reply["error"]["code"], protocol::INVALID_PARAMS);
+        assert_eq!(reply["error"]["message"], "Unknown tool: launch_rockets");
+
+        let reply = call("tools/call", json!({ "arguments": {} })).await;
+        assert_eq!(reply["error"]["code"], protocol::INVALID_PARAMS);
+        assert_eq!(reply["error"]["message"], "Missing tool name");
+
+        let reply = call("resources/list", Value::Null).await;
+        assert_eq!(reply["error"]["code"], protocol::METHOD_NOT_FOUND);
+        assert_eq!(reply["id"], 1);
+    }
+
+    #[tokio::test]
+    async fn tool_calls_go_to_the_tool_server() {
+        let (mcp_tx, mut mcp_rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            if let Some(McpRequest::CallTool { name, response_tx, .. }) = mcp_rx.recv().await {
+                let _ = response_tx.send(Ok(format!("called {}", name).into()));
+            }
+        });
+        let params = json!({ "name": "read_file", "arguments": { "path": "a.txt" } });
+        let reply = handle_request(json!(7), "tools/call", params, "", &mcp_tx).await;
+        assert_eq!(
+            reply,
+            protocol::response(
+                json!(7),
+                json!({ "content": [{ "type": "text", "text": "called read_file" }], "isError": false })
+            )
+        );
+
+        let reply = call("tools/call", json!({ "name": "read_file" })).await;
+        assert_eq!(reply["result"]["isError"], true);
+        assert_eq!(reply["result"]["content"][0]["text"], "Tool server unavailable");
+    }
+}
@@ -0,0 +1,497 @@
+use crate::app::AppEvent;
+use crate::docker_setup::Sandbox;
+use crate::shell::DEFAULT_SESSION;
+use anyhow::{anyhow, bail, Result};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::{mpsc, oneshot};
+
+// Long-running commands (dev servers, watchers) started by the agent. Each gets
+// its own `docker exec` and process group, so `run_command` stays free and
+// interrupting the agent's shell never reaches them.
+
+/// Printed by the wrapper before the command starts; the PID is also the process group
+const PGID_MARKER: &str = "__AGERUS_PGID__:";
+
+/// Lines kept per process; older output is dropped and counted
+const MAX_LOG_LINES: usize = 2000;
+
+/// SIGTERM gets this long before the group is killed outright
+const KILL_GRACE: Duration = Duration::from_secs(3);
+
+/// How long `start` waits so immediate failures show up in its result
+const STARTUP_WAIT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcessStatus {
+    Running,
+    /// `None` when docker exec reported no code (e.g. killed by a signal)
+    Exited(Option<i32>),
+    Killed,
+}
+
+/// Snapshot of one process for the UI.
+#[derive(Debug, Clone)]
+pub struct ProcessInfo {
+    pub name: String,
+    pub command: String,
+    pub status: ProcessStatus,
+    pub started: Instant,
+}
+
+struct BackgroundProcess {
+    /// Tells a restarted process apart from the old one with the same name
+    id: u64,
+    command: String,
+    started: Instant,
+    /// Process group inside the container, once the wrapper has printed it
+    pgid: Option<u32>,
+    /// Kills the process we spawned, for when there is no `pgid` to signal yet
+    kill_tx: Option<oneshot::Sender<()>>,
+    status: ProcessStatus,
+    ended: Option<Instant>,
+    log: VecDeque<String>,
+    /// Lines dropped from the front of `log`
+    dropped: usize,
+    /// Absolute line index the agent has read up to
+    cursor: usize,
+}
+
+impl BackgroundProcess {
+    fn total_lines(&self) -> usize {
+        self.dropped + self.log.len()
+    }
+
+    fn status_line(&self, name: &str) -> String {
+        let elapsed = self.ended.unwrap_or_else(Instant::now) - self.started;
+        let secs = elapsed.as_secs_f64();
+        match &self.status {
+            ProcessStatus::Running => format!("'{}' is running ({:.1}s)", name, secs),
+            ProcessStatus::Exited(Some(code)) => {
+                format!("'{}' exited with code {} after {:.1}s", name, code, secs)
+            }
+            ProcessStatus::Exited(None) => format!("'{}' exited after {:.1}s", name, secs),
+            ProcessStatus::Killed => format!("'{}' was killed after {:.1}s", name, secs),
+        }
+    }
+
+    /// Lines from absolute index `from`, noting any that were already dropped.
+    fn lines_from(&self, from: usize, max_bytes: usize) -> String {
+        let mut out = String::new();
+        if from < self.dropped {
+            let _ = writeln!(out, "...[{} earlier lines dropped]", self.dropped - from);
+        }
+        let start = from.saturating_sub(self.dropped);
+
+        // Keep the newest lines that fit
+        let mut taken = Vec::new();
+        let mut bytes = 0;
+        for line in self.log.iter().skip(start).rev() {
+            if bytes + line.len() + 1 > max_bytes && !taken.is_empty() {
+                break;
+            }
+            bytes += line.len() + 1;
+            taken.push(line.as_str());
+        }
+        let skipped = self.log.len().saturating_sub(start) - taken.len();
+        if skipped > 0 {
+            let _ = writeln!(out, "...[{} lines skipped]", skipped);
+        }
+        for line in taken.iter().rev() {
+            out.push_str(line);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+type Table = Arc<Mutex<BTreeMap<String, BackgroundProcess>>>;
+
+#[derive(Clone)]
+pub struct ProcessManager {
+    /// Where the processes run
+    sandbox: Arc<Sandbox>,
+    procs: Table,
+    app_tx: mpsc::Sender<AppEvent>,
+}
+
+impl ProcessManager {
+    pub fn new(sandbox: Sandbox, app_tx: mpsc::Sender<AppEvent>) -> Self {
+        Self {
+            sandbox: Arc::new(sandbox),
+            procs: Arc::new(Mutex::new(BTreeMap::new())),
+            app_tx,
+        }
+    }
+
+    fn table(&self) -> MutexGuard<'_, BTreeMap<String, BackgroundProcess>> {
+        // A panic elsewhere can't leave the table half-updated in a way that matters
+        self.procs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub async fn start(&self, name: &str, command: &str, max_bytes: usize) -> Result<String> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)) {
+            bail!("Invalid process name '{}': use letters, digits, '-', '_' or '.'", name);
+        }
+        if self
+            .table()
+            .get(name)
+            .is_some_and(|p| p.status == ProcessStatus::Running)
+        {
+            bail!("A process named '{}' is already running. Kill it first or pick another name.", name);
+        }
+
+        // setsid makes the wrapper a new session and process group leader;
+        // `exec` keeps that PID for the command itself
+        let wrapper = format!(
+            r#"echo "{}$$"; exec bash -lc "$1" 2>&1"#,
+            PGID_MARKER
+        );
+        let mut child = self
+            .sandbox
+            .command(&[], &["setsid", "-w", "bash", "-c"])
+            .arg(&wrapper)
+            .arg("agerus-bg")
+            .arg(command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start '{}': {}", name, e))?;
+
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let (kill_tx, kill_rx) = oneshot::channel();
+        self.table().insert(
+            name.to_string(),
+            BackgroundProcess {
+                id,
+                command: command.to_string(),
+                started: Instant::now(),
+                pgid: None,
+                kill_tx: Some(kill_tx),
+                status: ProcessStatus::Running,
+                ended: None,
+                log: VecDeque::new(),
+                dropped: 0,
+                cursor: 0,
+            },
+        );
+        let _ = self
+            .app_tx
+            .send(AppEvent::TerminalLine {
+                session: DEFAULT_SESSION.into(),
+                line: format!("$ {} &  # {}", command, name),
+            })
+            .await;
+        self.notify().await;
+
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("Failed to capture stdout"))?;
+        let stderr = child.stderr.take().ok_or_else(|| anyhow!("Failed to capture stderr"))?;
+        let readers = [
+            self.collect_lines(name, id, stdout),
+            self.collect_lines(name, id, stderr),
+        ];
+
+        let manager = self.clone();
+        let key = name.to_string();
+        tokio::spawn(async move {
+            let status = tokio::select! {
+                status = child.wait() => status,
+                Ok(()) = kill_rx => {
+                    let _ = child.start_kill();
+                    child.wait().await
+                }
+            };
+            // Let the readers drain what the process printed last
+            for reader in readers {
+                let _ = reader.await;
+            }
+            if let Some(proc) = manager.table().get_mut(&key).filter(|p| p.id == id) {
+                if proc.status == ProcessStatus::Running {
+                    proc.status = ProcessStatus::Exited(status.ok().and_then(|s| s.code()));
+                }
+                proc.ended.get_or_insert_with(Instant::now);
+            }
+            manager.notify().await;
+        });
+
+        tokio::time::sleep(STARTUP_WAIT).await;
+        self.output(name, None, max_bytes)
+    }
+
+    fn collect_lines<R>(&self, name: &str, id: u64, stream: R) -> tokio::task::JoinHandle<()>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let procs = self.procs.clone();
+        let key = name.to_string();
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(stream);
+            let mut buf = Vec::new();
+            loop {
+                buf.clear();
+                match reader.read_until(b'\n', &mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        let line = String::from_utf8_lossy(&buf).trim_end().to_string();
+                        let mut table = procs.lock().unwrap_or_else(|e| e.into_inner());
+                        let Some(proc) = table.get_mut(&key).filter(|p| p.id == id) else {
+                            break;
+                        };
+                        if proc.pgid.is_none()
+                            && let Some(pid) = line.strip_prefix(PGID_MARKER)
+                        {
+                            proc.pgid = pid.trim().parse().ok();
+                            continue;
+                        }
+                        proc.log.push_back(line);
+                        if proc.log.len() > MAX_LOG_LINES {
+                            proc.log.pop_front();
+                            proc.dropped += 1;
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Output since the last call, or the last `tail` lines when given.
+    pub fn output(&self, name: &str, tail: Option<usize>, max_bytes: usize) -> Result<String> {
+        let mut table = self.table();
+        let proc = table
+            .get_mut(name)
+            .ok_or_else(|| anyhow!("No background process named '{}'", name))?;
+
+        let total = proc.total_lines();
+        let from = match tail {
+            Some(n) => total.saturating_sub(n),
+            None => proc.cursor,
+        };
+        let mut text = proc.status_line(name);
+        if from >= total {
+            text.push_str("\n(no new output)");
+        } else {
+            text.push_str(&format!("\n--- output ---\n{}", proc.lines_from(from, max_bytes).trim_end()));
+        }
+        proc.cursor = total;
+        Ok(text)
+    }
+
+    pub fn list(&self) -> String {
+        let table = self.table();
+        if table.is_empty() {
+            return "No background processes.".into();
+        }
+        table
+            .iter()
+            .map(|(name, p)| format!("{} | {}", p.status_line(name), p.command))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// SIGTERM to the whole process group, SIGKILL if it is still around after the grace period.
+    pub async fn kill(&self, name: &str, max_bytes: usize) -> Result<String> {
+        let pgid = {
+            let table = self.table();
+            let proc = table
+                .get(name)
+                .ok_or_else(|| anyhow!("No background process named '{}'", name))?;
+            if proc.status != ProcessStatus::Running {
+                drop(table);
+                return self.output(name, Some(20), max_bytes);
+            }
+            proc.pgid
+                .ok_or_else(|| anyhow!("'{}' has not reported its PID yet; try again shortly", name))?
+        };
+
+        signal_group(&self.sandbox, pgid, "TERM").await;
+        let deadline = Instant::now() + KILL_GRACE;
+        while self.is_running(name) && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        // Children that ignored TERM or left the leader behind
+        signal_group(&self.sandbox, pgid, "KILL").await;
+
+        if let Some(proc) = self.table().get_mut(name) {
+            proc.status = ProcessStatus::Killed;
+            proc.ended.get_or_insert_with(Instant::now);
+        }
+        self.notify().await;
+        self.output(name, Some(20), max_bytes)
+    }
+
+    /// Stops everything still running; used when the session ends.
+    pub async fn kill_all(&self) {
+        let mut groups = Vec::new();
+        for p in self
+            .table()
+            .values_mut()
+            .filter(|p| p.status == ProcessStatus::Running)
+        {
+            p.status = ProcessStatus::Killed;
+            match p.pgid {
+                Some(pgid) => groups.push(pgid),
+                // Hasn't printed its PGID yet: kill what we spawned instead
+                None => {
+                    if let Some(kill_tx) = p.kill_tx.take() {
+                        let _ = kill_tx.send(());
+                    }
+                }
+            }
+        }
+        for pgid in groups {
+            signal_group(&self.sandbox, pgid, "KILL").await;
+        }
+    }
+
+    fn is_running(&self, name: &str) -> bool {
+        self.table()
+            .get(name)
+            .is_some_and(|p| p.status == ProcessStatus::Running)
+    }
+
+    pub fn snapshot(&self) -> Vec<ProcessInfo> {
+        self.table()
+            .iter()
+            .map(|(name, p)| ProcessInfo {
+                name: name.clone(),
+                command: p.command.clone(),
+                status: p.status.clone(),
+                started: p.started,
+            })
+            .collect()
+    }
+
+    async fn notify(&self) {
+        let _ = self.app_tx.send(AppEvent::ProcessesChanged(self.snapshot())).await;
+    }
+}
+
+pub(crate) async fn signal_group(sandbox: &Sandbox, pgid: u32, signal: &str) {
+    let _ = sandbox
+        // bash's builtin kill; the base image has no procps
+        .root_command(&["bash", "-c"])
+        .arg(format!("kill -s {} -- -{} 2>/dev/null; true", signal, pgid))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(lines: &[&str], dropped: usize) -> BackgroundProcess {
+        let started = Instant::now();
+        BackgroundProcess {
+            id: 0,
+            command: "npm run dev".into(),
+            started,
+            pgid: None,
+            kill_tx: None,
+            status: ProcessStatus::Running,
+            ended: Some(started + Duration::from_millis(2500)),
+            log: lines.iter().map(|l| l.to_string()).collect(),
+            dropped,
+            cursor: 0,
+        }
+    }
+
+    fn manager() -> (ProcessManager, mpsc::Receiver<AppEvent>) {
+        let (tx, rx) = mpsc::channel(16);
+        let sandbox = Sandbox::Local {
+            workspace: std::env::temp_dir(),
+        };
+        (ProcessManager::new(sandbox, tx), rx)
+    }
+
+    #[test]
+    fn status_lines() {
+        let mut proc = process(&[], 0);
+        assert_eq!(proc.status_line("web"), "'web' is running (2.5s)");
+        proc.status = ProcessStatus::Exited(Some(1));
+        assert_eq!(proc.status_line("web"), "'web' exited with code 1 after 2.5s");
+        proc.status = ProcessStatus::Exited(None);
+        assert_eq!(proc.status_line("web"), "'web' exited after 2.5s");
+        proc.status = ProcessStatus::Killed;
+        assert_eq!(proc.status_line("web"), "'web' was killed after 2.5s");
+    }
+
+    #[test]
+    fn lines_from_notes_dropped_lines_and_keeps_the_newest_that_fit() {
+        // Lines 0-9 were dropped; the log holds 10-13
+        let proc = process(&["ten", "eleven", "twelve", "thirteen"], 10);
+        assert_eq!(proc.total_lines(), 14);
+
+        assert_eq!(proc.lines_from(12, 1000), "twelve\nthirteen\n");
+        assert_eq!(
+            proc.lines_from(4, 1000),
+            "...[6 earlier lines dropped]\nten\neleven\ntwelve\nthirteen\n"
+        );
+        // "twelve\nthirteen\n" is 16 bytes
+        assert_eq!(proc.lines_from(10, 16), "...[2 lines skipped]\ntwelve\nthirteen\n");
+        // One line always shows, however long
+        assert_eq!(proc.lines_from(10, 1), "...[3 lines skipped]\nthirteen\n");
+    }
+
+    #[test]
+    fn output_follows_a_cursor_or_shows_the_tail() {
+        let (manager, _rx) = manager();
+        manager.table().insert("web".into(), process(&["a", "b", "c"], 0));
+
+        assert_eq!(
+            manager.output("web", None, 1000).unwrap(),
+            "'web' is running (2.5s)\n--- output ---\na\nb\nc"
+        );
+        assert_eq!(
+            manager.output("web", None, 1000).unwrap(),
+            "'web' is running (2.5s)\n(no new output)"
+        );
+
+        manager.table().get_mut("web").unwrap().log.push_back("d".into());
+        assert_eq!(
+            manager.output("web", None, 1000).unwrap(),
+            "'web' is running (2.5s)\n--- output ---\nd"
+        );
+        assert_eq!(
+            manager.output("web", Some(2), 1000).unwrap(),
+            "'web' is running (2.5s)\n--- output ---\nc\nd"
+        );
+        assert!(manager.output("db", None, 1000).is_err());
+    }
+
+    #[tokio::test]
+    async fn kill_all_kills_processes_that_have_no_pgid_yet() {
+        let (manager, _rx) = manager();
+        let (kill_tx, mut kill_rx) = oneshot::channel();
+        let mut proc = process(&[], 0);
+        proc.kill_tx = Some(kill_tx);
+        manager.table().insert("web".into(), proc);
+
+        manager.kill_all().await;
+
+        assert!(kill_rx.try_recv().is_ok());
+        assert_eq!(manager.table()["web"].status, ProcessStatus::Killed);
+    }
+
+    #[tokio::test]
+    async fn kill_all_stops_a_real_process() {
+        let (manager, _rx) = manager();
+        manager.start("sleeper", "sleep 30", 1000).await.unwrap();
+        assert!(manager.is_running("sleeper"));
+
+        manager.kill_all().await;
+        // The wait task sees the exit, but the status stays Killed
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(manager.table()["sleeper"].status, ProcessStatus::Killed);
+        assert!(manager.table()["sleeper"].ended.is_some());
+    }
+}
@@ -0,0 +1,266 @@
+use crate::app::AppEvent;
+use crate::docker_setup::Sandbox;
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::mpsc;
+
+// Interactive shell for the Terminal tab: `exec -it` into the sandbox on a pseudo-terminal,
+// with output fed through a VT100 emulator that `ui::terminal` renders.
+
+const SCROLLBACK_LINES: usize = 5000;
+
+pub struct PtyTerminal {
+    parser: Arc<Mutex<vt100::Parser>>,
+    writer: Box<dyn Write + Send>,
+    master: Box<dyn MasterPty + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    size: (u16, u16),
+}
+
+impl PtyTerminal {
+    pub fn spawn(
+        sandbox: &Sandbox,
+        rows: u16,
+        cols: u16,
+        app_tx: mpsc::Sender<AppEvent>,
+    ) -> Result<Self> {
+        let pair = native_pty_system()
+            .openpty(pty_size(rows, cols))
+            .map_err(|e| anyhow!("Failed to open pty: {}", e))?;
+
+        // Same user as the agent's shell, so files made here aren't root's either
+        let (program, args, cwd) =
+            sandbox.invocation(&["-it", "-e", "TERM=xterm-256color"], &["bash", "-l"]);
+        let mut cmd = CommandBuilder::new(program);
+        cmd.cwd(cwd);
+        cmd.args(args);
+        // What the vt100 parser emulates; a local shell would inherit ours otherwise
+        cmd.env("TERM", "xterm-256color");
+        let child = pair
+            .slave
+            .spawn_command(cmd)
+            .map_err(|e| anyhow!("Failed to start terminal: {}", e))?;
+        // Only the child holds the slave end, so reads hit EOF when it exits
+        drop(pair.slave);
+
+        let mut reader = pair
+            .master
+            .try_clone_reader()
+            .map_err(|e| anyhow!("Failed to read pty: {}", e))?;
+        let writer = pair
+            .master
+            .take_writer()
+            .map_err(|e| anyhow!("Failed to write pty: {}", e))?;
+
+        let parser = Arc::new(Mutex::new(vt100::Parser::new(rows, cols, SCROLLBACK_LINES)));
+        let parser_for_reader = parser.clone();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 8192];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if let Ok(mut parser) = parser_for_reader.lock() {
+                            parser.process(&buf[..n]);
+                        }
+                        // Just a redraw nudge; dropping it when the queue is full is fine
+                        let _ = app_tx.try_send(AppEvent::TerminalOutput);
+                    }
+                }
+            }
+            let _ = app_tx.blocking_send(AppEvent::TerminalExited);
+        });
+
+        Ok(Self {
+            parser,
+            writer,
+            master: pair.master,
+            child,
+            size: (rows, cols),
+        })
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        let _ = self.writer.write_all(bytes);
+        let _ = self.writer.flush();
+    }
+
+    /// Propagates a new view size to the emulator and the process (SIGWINCH).
+    pub fn resize(&mut self, rows: u16, cols: u16) {
+        if (rows, cols) == self.size || rows == 0 || cols == 0 {
+            return;
+        }
+        self.size = (rows, cols);
+        if let Ok(mut parser) = self.parser.lock() {
+            parser.set_size(rows, cols);
+        }
+        let _ = self.master.resize(pty_size(rows, cols));
+    }
+
+    /// Moves the view into the scrollback; positive is further back.
+    pub fn scroll(&mut self, delta: i32) {
+        if let Ok(mut parser) = self.parser.lock() {
+            let current = parser.screen().scrollback() as i32;
+            parser.set_scrollback((current + delta).max(0) as usize);
+        }
+    }
+
+    pub fn scroll_to_bottom(&mut self) {
+        if let Ok(mut parser) = self.parser.lock() {
+            parser.set_scrollback(0);
+        }
+    }
+
+    pub fn parser(&self) -> Option<MutexGuard<'_, vt100::Parser>> {
+        self.parser.lock().ok()
+    }
+}
+
+impl Drop for PtyTerminal {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+fn pty_size(rows: u16, cols: u16) -> PtySize {
+    PtySize {
+        rows,
+        cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+/// Translates a key press into the bytes a terminal would send.
+/// `app_cursor` is the emulator's application cursor mode (vim, less, ...).
+pub fn key_to_bytes(key: KeyEvent, app_cursor: bool) -> Option<Vec<u8>> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+
+    let cursor = |c: char| {
+        if app_cursor {
+            format!("\x1bO{}", c).into_bytes()
+        } else {
+            format!("\x1b[{}", c).into_bytes()
+        }
+    };
+
+    let mut bytes = match key.code {
+        KeyCode::Char(c) if ctrl => match c.to_ascii_lowercase() {
+            c @ 'a'..='z' => vec![c as u8 - b'a' + 1],
+            ' ' | '@' | '2' => vec![0],
+            '[' | '3' => vec![0x1b],
+            '\\' | '4' => vec![0x1c],
+            ']' | '5' => vec![0x1d],
+            '^' | '6' => vec![0x1e],
+            '_' | '7' | '/' => vec![0x1f],
+            _ => return None,
+        },
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => cursor('A'),
+        KeyCode::Down => cursor('B'),
+        KeyCode::Right => cursor('C'),
+        KeyCode::Left => cursor('D'),
+        KeyCode::Home => cursor('H'),
+        KeyCode::End => cursor('F'),
+        KeyCode::Insert => b"\x1b[2~".to_vec(),
+        KeyCode::Delete => b"\x1b[3~".to_vec(),
+        KeyCode::PageUp => b"\x1b[5~".to_vec(),
+        KeyCode::PageDown => b"\x1b[6~".to_vec(),
+        KeyCode::F(n @ 1..=4) => format!("\x1bO{}", (b'P' + n - 1) as char).into_bytes(),
+        KeyCode::F(n @ 5..=12) => {
+            let code = [15, 17, 18, 19, 20, 21, 23, 24][(n - 5) as usize];
+            format!("\x1b[{}~", code).into_bytes()
+        }
+        _ => return None,
+    };
+
+    // Alt/Meta prefixes ESC
+    if alt {
+        bytes.insert(0, 0x1b);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(code: KeyCode, modifiers: KeyModifiers, app_cursor: bool) -> Option<Vec<u8>> {
+        key_to_bytes(KeyEvent::new(code, modifiers), app_cursor)
+    }
+
+    #[test]
+    fn keys_map_to_terminal_bytes() {
+        let none = KeyModifiers::NONE;
+        let ctrl = KeyModifiers::CONTROL;
+        let alt = KeyModifiers::ALT;
+        let cases: &[(KeyCode, KeyModifiers, &[u8])] = &[
+            (KeyCode::Char('x'), none, b"x"),
+            (KeyCode::Char('é'), none, "é".as_bytes()),
+            (KeyCode::Char('c'), ctrl, b"\x03"),
+            (KeyCode::Char('A'), ctrl, b"\x01"),
+            (KeyCode::Char('z'), ctrl, b"\x1a"),
+            (KeyCode::Char(' '), ctrl, b"\x00"),
+            (KeyCode::Char('['), ctrl, b"\x1b"),
+            (KeyCode::Char('\\'), ctrl, b"\x1c"),
+            (KeyCode::Char(']'), ctrl, b"\x1d"),
+            (KeyCode::Char('^'), ctrl, b"\x1e"),
+            (KeyCode::Char('/'), ctrl, b"\x1f"),
+            (KeyCode::Char('b'), alt, b"\x1bb"),
+            (KeyCode::Char('c'), ctrl | alt, b"\x1b\x03"),
+            (KeyCode::Enter, none, b"\r"),
+            (KeyCode::Backspace, none, b"\x7f"),
+            (KeyCode::Backspace, alt, b"\x1b\x7f"),
+            (KeyCode::Tab, none, b"\t"),
+            (KeyCode::BackTab, none, b"\x1b[Z"),
+            (KeyCode::Esc, none, b"\x1b"),
+            (KeyCode::Delete, none, b"\x1b[3~"),
+            (KeyCode::PageDown, none, b"\x1b[6~"),
+            (KeyCode::F(1), none, b"\x1bOP"),
+            (KeyCode::F(4), none, b"\x1bOS"),
+            (KeyCode::F(5), none, b"\x1b[15~"),
+            (KeyCode::F(6), none, b"\x1b[17~"),
+            (KeyCode::F(11), none, b"\x1b[23~"),
+            (KeyCode::F(12), none, b"\x1b[24~"),
+        ];
+        for (code, modifiers, expected) in cases {
+            assert_eq!(
+                bytes(*code, *modifiers, false).as_deref(),
+                Some(*expected),
+                "{:?} with {:?}",
+                code,
+                modifiers
+            );
+        }
+
+        assert_eq!(bytes(KeyCode::Char('1'), ctrl, false), None);
+        assert_eq!(bytes(KeyCode::F(13), none, false), None);
+    }
+
+    #[test]
+    fn cursor_keys_follow_the_cursor_mode() {
+        let keys = [
+            (KeyCode::Up, 'A'),
+            (KeyCode::Down, 'B'),
+            (KeyCode::Right, 'C'),
+            (KeyCode::Left, 'D'),
+            (KeyCode::Home, 'H'),
+            (KeyCode::End, 'F'),
+        ];
+        for (code, c) in keys {
+            let normal = format!("\x1b[{}", c).into_bytes();
+            let app = format!("\x1bO{}", c).into_bytes();
+            assert_eq!(bytes(code, KeyModifiers::NONE, false), Some(normal));
+            assert_eq!(bytes(code, KeyModifiers::NONE, true), Some(app));
+        }
+    }
+}
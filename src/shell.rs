@@ -1,45 +1,192 @@
 use crate::app::AppEvent;
+use crate::config::Config;
+use crate::docker_setup::runtime::ContainerRuntime;
+use crate::docker_setup::{self, Sandbox};
+use crate::processes;
 use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::hash::{BuildHasher, Hasher, RandomState};
 use std::process::Stdio;
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::{Child, ChildStdin, ChildStdout, Command};
-use tokio::sync::{mpsc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin};
+use tokio::sync::{mpsc, oneshot};
 
-// Match the new name in docker_setup.rs
-const CONTAINER_NAME: &str = "agerus_sandbox";
+/// How long each signal gets before escalating: SIGINT -> SIGTERM -> SIGKILL ->
+/// kill bash's whole process group and start a new shell
+const INTERRUPT_GRACE: Duration = Duration::from_secs(3);
 
-// Request types for our persistent shell
+/// Printed once at startup so the actor knows which processes belong to the current command
+const PID_MARKER: &str = "__SHELL_PID__:";
+
+/// Session used when `run_command` names none
+pub const DEFAULT_SESSION: &str = "main";
+
+/// Each session is a bash process in the container; keep that bounded
+const MAX_SESSIONS: usize = 8;
+
+/// A shell that dies by itself sooner than this after starting counts towards a crash loop
+const MIN_HEALTHY_UPTIME: Duration = Duration::from_secs(10);
+/// Consecutive quick deaths before a session stops restarting itself
+const MAX_QUICK_RESTARTS: u32 = 3;
+
+/// Sessions that die together (container restart) must not each recreate the container
+static DOCKER_SETUP: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+// Request types for the agent's persistent shells (the Terminal tab has its own pty)
 pub enum ShellRequest {
     RunCommand {
+        /// Named shell to run in, started on first use
+        session: String,
         cmd: String,
-        response_tx: mpsc::Sender<String>,
+        /// Errs when the session can't be started
+        response_tx: oneshot::Sender<Result<CommandOutput>>,
     },
-    UserInput(String),
+    /// Ctrl+C for whatever is running in the foreground; bash itself survives.
+    /// `None` interrupts every session.
+    Interrupt { session: Option<String> },
+}
+
+/// Result of one command run through the persistent shell.
+#[derive(Debug, Clone, Default)]
+pub struct CommandOutput {
+    /// `None` when the shell went away before reporting one
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration: Duration,
+    /// Stopped by `ShellRequest::Interrupt` (timeout or user abort)
+    pub interrupted: bool,
+}
+
+/// How a command ended, without its output. Kept on the chat message so the
+/// view doesn't have to read it back out of the text.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandStatus {
+    pub exit_code: Option<i32>,
+    pub duration: Duration,
+    pub interrupted: bool,
+    /// Interrupted because it outran its timeout
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+impl CommandStatus {
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0) && !self.interrupted
+    }
+
+    pub fn summary(&self) -> String {
+        let secs = self.duration.as_secs_f64();
+        let summary = match self.exit_code {
+            Some(code) => format!("exit {} in {:.2}s", code, secs),
+            None => format!("no exit code, shell ended after {:.2}s", secs),
+        };
+        if self.timed_out {
+            format!("{} (timed out)", summary)
+        } else if self.interrupted {
+            format!("{} (interrupted)", summary)
+        } else {
+            summary
+        }
+    }
+}
+
+impl CommandOutput {
+    pub fn status(&self) -> CommandStatus {
+        CommandStatus {
+            exit_code: self.exit_code,
+            duration: self.duration,
+            interrupted: self.interrupted,
+            timed_out: false,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        self.status().summary()
+    }
+
+    /// What the model sees: the summary line, then each non-empty stream
+    /// clipped to `max_bytes`.
+    pub fn to_tool_text(&self, max_bytes: usize) -> String {
+        let mut text = self.summary();
+        for (label, stream) in [("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            if !stream.trim().is_empty() {
+                text.push_str(&format!("\n--- {} ---\n{}", label, clip_middle(stream, max_bytes)));
+            }
+        }
+        if self.stdout.trim().is_empty() && self.stderr.trim().is_empty() {
+            text.push_str("\n(no output)");
+        }
+        text
+    }
+}
+
+/// Keeps the head and tail of long output; errors tend to sit at either end.
+fn clip_middle(text: &str, max_bytes: usize) -> String {
+    let text = text.trim_end();
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let mut head = max_bytes / 2;
+    while !text.is_char_boundary(head) {
+        head -= 1;
+    }
+    let mut tail = text.len() - max_bytes / 2;
+    while !text.is_char_boundary(tail) {
+        tail += 1;
+    }
+    format!(
+        "{}\n...[{} bytes omitted]...\n{}",
+        &text[..head],
+        tail - head,
+        &text[tail..]
+    )
+}
+
+enum ShellLine {
+    Stdout(String),
+    Stderr(String),
+}
+
+struct QueuedCommand {
+    cmd: String,
+    responder: oneshot::Sender<Result<CommandOutput>>,
+}
+
+/// The command currently running in bash, collecting output until both
+/// streams have printed its end marker.
+struct PendingCommand {
+    responder: oneshot::Sender<Result<CommandOutput>>,
+    started: Instant,
+    output: CommandOutput,
+    stdout_done: bool,
+    stderr_done: bool,
+    nonce: String,
 }
 
 pub struct ShellSession {
+    /// Where bash runs
+    sandbox: Sandbox,
     process: Child,
     stdin: Option<ChildStdin>,
-    reader: Arc<Mutex<BufReader<ChildStdout>>>,
-    delimiter: String,
+    /// bash's PID inside the container, once it has reported it. bash runs
+    /// under setsid, so this is also its process group.
+    shell_pid: Option<u32>,
+    started: Instant,
 }
 
 impl ShellSession {
-    fn new_internal() -> Result<Self> {
+    fn new_internal(sandbox: &Sandbox) -> Result<(Self, mpsc::Receiver<ShellLine>)> {
         // Fix: Explicitly set working directory (-w /workspace)
         // Fix: Set host current_dir to "/" to avoid OCI namespace path issues
-        let mut process = Command::new("docker")
-            .current_dir("/") // Critical fix for "outside of container mount namespace" error
-            .args([
-                "exec",
-                "-i",
-                "-w",
-                "/workspace",
-                CONTAINER_NAME,
-                "bash",
-                "-l",
-            ])
+        // (both done by `Sandbox::command`; a local bash starts in the workspace instead)
+        // setsid gives bash its own process group, to kill it by when a command won't stop
+        let mut process = sandbox
+            .command(&["-i"], &["setsid", "-w", "bash", "-l"])
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -53,71 +200,314 @@ impl ShellSession {
             .stdout
             .take()
             .ok_or_else(|| anyhow!("Failed to capture stdout"))?;
+        let stderr = process
+            .stderr
+            .take()
+            .ok_or_else(|| anyhow!("Failed to capture stderr"))?;
+
+        // Dedicated readers keep the actor's select! cancel-safe
+        let (tx_lines, rx_lines) = mpsc::channel(256);
+        forward_lines(stdout, tx_lines.clone(), ShellLine::Stdout);
+        forward_lines(stderr, tx_lines, ShellLine::Stderr);
+
+        Ok((
+            Self {
+                sandbox: sandbox.clone(),
+                process,
+                stdin: Some(stdin),
+                shell_pid: None,
+                started: Instant::now(),
+            },
+            rx_lines,
+        ))
+    }
+
+    async fn start(sandbox: &Sandbox) -> Result<(Self, mpsc::Receiver<ShellLine>)> {
+        let (mut session, rx_lines) = Self::new_internal(sandbox)?;
+        if let Some(stdin) = session.stdin.as_mut() {
+            stdin.write_all(format!("echo {}$$\n", PID_MARKER).as_bytes()).await?;
+            stdin.flush().await?;
+        }
+        Ok((session, rx_lines))
+    }
+
+    /// Makes sure the container is up (it may have been restarted or removed), then starts bash.
+    async fn restart(
+        config: &Config,
+        runtime: &Arc<dyn ContainerRuntime>,
+    ) -> Result<(Self, mpsc::Receiver<ShellLine>)> {
+        let _setup = DOCKER_SETUP.lock().await;
+        let config = config.clone();
+        let runtime = runtime.clone();
+        let sandbox = Sandbox::new(&config, runtime.clone());
+        tokio::task::spawn_blocking(move || docker_setup::ensure_docker_env(&config, runtime)).await??;
+        Self::start(&sandbox).await
+    }
+
+    /// Sends `signal` to every descendant of bash, leaving bash itself alone.
+    /// Runs through a separate `docker exec`, so it works while bash is busy.
+    fn signal_children(&self, signal: &'static str) {
+        let Some(pid) = self.shell_pid else {
+            return;
+        };
+        // The base image has no procps, so walk /proc for the process tree
+        let script = format!(
+            r#"desc() {{ for f in /proc/[0-9]*/status; do
+  if grep -q "^PPid:[[:space:]]*$1\$" "$f" 2>/dev/null; then p=${{f#/proc/}}; p=${{p%/status}}; echo "$p"; desc "$p"; fi
+done; }}
+pids=$(desc {pid}); [ -n "$pids" ] && kill -s {signal} $pids 2>/dev/null; true"#
+        );
+        let mut cmd = self.sandbox.root_command(&["sh", "-c", &script]);
+        tokio::spawn(async move {
+            let _ = cmd
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .await;
+        });
+    }
 
-        let delimiter = "__END_OF_CMD__".to_string();
+    /// Writes `cmd` to bash, wrapped so both streams end with its own nonce.
+    async fn write_command(
+        &mut self,
+        cmd: QueuedCommand,
+        log: &SessionLog,
+    ) -> PendingCommand {
+        let nonce = new_nonce();
+        log.line(format!("$ {}", cmd.cmd)).await;
+        let full_cmd = wrap_command(&cmd.cmd, &nonce);
+        if let Some(stdin) = self.stdin.as_mut() {
+            if let Err(e) = stdin.write_all(full_cmd.as_bytes()).await {
+                let _ = log.tx.send(AppEvent::Error(format!("Stdin error: {}", e))).await;
+            }
+            let _ = stdin.flush().await;
+        }
 
-        Ok(Self {
-            process,
-            stdin: Some(stdin),
-            reader: Arc::new(Mutex::new(BufReader::new(stdout))),
-            delimiter,
-        })
+        PendingCommand {
+            responder: cmd.responder,
+            started: Instant::now(),
+            output: CommandOutput::default(),
+            stdout_done: false,
+            stderr_done: false,
+            nonce,
+        }
     }
 
+    /// Routes requests to the named sessions, starting each on first use.
     pub async fn run_actor(
         mut rx_request: mpsc::Receiver<ShellRequest>,
         tx_app_event: mpsc::Sender<AppEvent>,
+        config: Config,
+        runtime: Arc<dyn ContainerRuntime>,
     ) {
-        let mut session = match Self::new_internal() {
+        let mut sessions: HashMap<String, mpsc::Sender<ShellRequest>> = HashMap::new();
+
+        while let Some(req) = rx_request.recv().await {
+            match req {
+                ShellRequest::RunCommand { session, cmd, response_tx } => {
+                    // A session whose bash died is started again
+                    sessions.retain(|_, tx| !tx.is_closed());
+
+                    if !sessions.contains_key(&session) {
+                        if let Err(e) = check_new_session(&session, &sessions) {
+                            let _ = response_tx.send(Err(e));
+                            continue;
+                        }
+                        let (tx, rx) = mpsc::channel(32);
+                        let log = SessionLog { name: session.clone(), tx: tx_app_event.clone() };
+                        tokio::spawn(Self::run_session(rx, log, config.clone(), runtime.clone()));
+                        sessions.insert(session.clone(), tx);
+                    }
+                    if let Some(tx) = sessions.get(&session) {
+                        let _ = tx.send(ShellRequest::RunCommand { session, cmd, response_tx }).await;
+                    }
+                }
+                ShellRequest::Interrupt { session } => {
+                    for (name, tx) in &sessions {
+                        if session.as_ref().is_none_or(|s| s == name) {
+                            let _ = tx.send(ShellRequest::Interrupt { session: None }).await;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// One named bash, running its queued commands one at a time.
+    async fn run_session(
+        mut rx_request: mpsc::Receiver<ShellRequest>,
+        log: SessionLog,
+        config: Config,
+        runtime: Arc<dyn ContainerRuntime>,
+    ) {
+        let tx_app_event = log.tx.clone();
+        let sandbox = Sandbox::new(&config, runtime.clone());
+        let (mut session, mut rx_lines) = match Self::start(&sandbox).await {
             Ok(s) => s,
             Err(e) => {
                 let _ = tx_app_event
-                    .send(AppEvent::Error(format!("Failed to start shell: {}", e)))
+                    .send(AppEvent::Error(format!("Failed to start shell '{}': {}", log.name, e)))
                     .await;
                 return;
             }
         };
 
-        let mut current_responder: Option<mpsc::Sender<String>> = None;
+        // One command runs at a time; the rest wait here so output always has one owner
+        let mut queue: VecDeque<QueuedCommand> = VecDeque::new();
+        let mut pending: Option<PendingCommand> = None;
+        // Next escalation step while an interrupted command refuses to stop
+        let mut escalation: Option<(tokio::time::Instant, u8)> = None;
+        let mut quick_restarts = 0;
 
         loop {
+            if pending.is_none()
+                && let Some(next) = queue.pop_front()
+            {
+                pending = Some(session.write_command(next, &log).await);
+            }
+
+            let deadline = escalation.map(|(at, _)| at);
             tokio::select! {
                 Some(req) = rx_request.recv() => {
-                    let cmd_str = match req {
-                        ShellRequest::RunCommand { cmd, response_tx } => {
-                            current_responder = Some(response_tx);
-                            cmd
-                        },
-                        ShellRequest::UserInput(input) => {
-                            current_responder = None;
-                            input
+                    match req {
+                        ShellRequest::RunCommand { cmd, response_tx, .. } => {
+                            queue.push_back(QueuedCommand { cmd, responder: response_tx });
                         }
-                    };
-
-                    if let Some(stdin) = session.stdin.as_mut() {
-                        let full_cmd = format!("{{ {}; }} 2>&1; echo {}\n", cmd_str, session.delimiter);
-                        if let Err(e) = stdin.write_all(full_cmd.as_bytes()).await {
-                            let _ = tx_app_event.send(AppEvent::Error(format!("Stdin error: {}", e))).await;
+                        ShellRequest::Interrupt { .. } => {
+                            if let Some(cmd) = pending.as_mut()
+                                && escalation.is_none()
+                            {
+                                cmd.output.interrupted = true;
+                                session.signal_children("INT");
+                                log.line("^C".into()).await;
+                                escalation = Some((tokio::time::Instant::now() + INTERRUPT_GRACE, 1));
+                            }
                         }
-                        let _ = stdin.flush().await;
                     }
                 }
 
-                result = read_next_line(&session.reader) => {
-                    match result {
-                        Ok(Some(line)) => {
-                            if line.contains(&session.delimiter) {
-                                current_responder = None;
-                            } else {
-                                let clean_line = line.trim_end().to_string();
-                                let _ = tx_app_event.send(AppEvent::TerminalLine(clean_line.clone())).await;
-                                if let Some(tx) = &current_responder {
-                                    let _ = tx.send(clean_line).await;
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(tokio::time::Instant::now)), if deadline.is_some() => {
+                    let stage = escalation.map(|(_, stage)| stage).unwrap_or(0);
+                    let next = tokio::time::Instant::now() + INTERRUPT_GRACE;
+                    match stage {
+                        1 => {
+                            session.signal_children("TERM");
+                            escalation = Some((next, 2));
+                        }
+                        2 => {
+                            session.signal_children("KILL");
+                            escalation = Some((next, 3));
+                        }
+                        _ => {
+                            // Nothing stopped it (e.g. a bash builtin blocking); replace the shell.
+                            // Killing the group takes bash and anything that escaped the /proc walk
+                            // with it; start_process commands have their own groups and survive.
+                            escalation = None;
+                            if let Some(pgid) = session.shell_pid {
+                                processes::signal_group(&session.sandbox, pgid, "KILL").await;
+                            }
+                            let _ = session.process.start_kill();
+                            match Self::start(&sandbox).await {
+                                Ok((new_session, new_lines)) => {
+                                    session = new_session;
+                                    rx_lines = new_lines;
+                                }
+                                Err(e) => {
+                                    let _ = tx_app_event.send(AppEvent::Error(format!("Failed to restart shell '{}': {}", log.name, e))).await;
                                 }
                             }
+                            if let Some(mut cmd) = pending.take() {
+                                cmd.output.exit_code = None;
+                                cmd.output.stderr.push_str("[The command ignored every signal; the shell was restarted, so cwd and environment were reset.]\n");
+                                finish(cmd, &log).await;
+                            }
+                            let _ = tx_app_event.send(AppEvent::Notice(format!("Shell '{}' restarted after a command could not be interrupted.", log.name))).await;
+                        }
+                    }
+                }
+
+                line = rx_lines.recv() => {
+                    let Some(line) = line else {
+                        // Both streams closed: bash exited or the container went away
+                        escalation = None;
+                        // Dying on its own shortly after starting looks like a crash loop;
+                        // a command that ended the shell (`exit`) does not
+                        let quick = pending.is_none() && session.started.elapsed() < MIN_HEALTHY_UPTIME;
+                        if let Some(cmd) = pending.take() {
+                            let _ = cmd.responder.send(Err(anyhow!(
+                                "Shell '{}' died while the command was running (bash exited or the container was restarted). It is being restarted with a fresh cwd and environment; check whether the command took effect before retrying.",
+                                log.name
+                            )));
+                        }
+                        // Queued commands expected the old shell's state; don't run them blind
+                        for cmd in queue.drain(..) {
+                            let _ = cmd.responder.send(Err(anyhow!(
+                                "Shell '{}' died before the command could run; it was not executed.",
+                                log.name
+                            )));
+                        }
+
+                        quick_restarts = if quick { quick_restarts + 1 } else { 0 };
+                        if quick_restarts > MAX_QUICK_RESTARTS {
+                            log.line("--- shell keeps exiting, not restarting ---".into()).await;
+                            let _ = tx_app_event.send(AppEvent::Notice(format!("⚠️ Shell '{}' exited {} times in a row right after starting; giving up. The next command will try again.", log.name, quick_restarts))).await;
+                            break;
+                        }
+
+                        log.line("--- shell exited, restarting ---".into()).await;
+                        let _ = session.process.start_kill();
+                        match Self::restart(&config, &runtime).await {
+                            Ok((new_session, new_lines)) => {
+                                session = new_session;
+                                rx_lines = new_lines;
+                                let _ = tx_app_event.send(AppEvent::Notice(format!("Shell '{}' exited and was restarted; its cwd and environment were reset.", log.name))).await;
+                            }
+                            Err(e) => {
+                                let _ = tx_app_event.send(AppEvent::Notice(format!("⚠️ Shell '{}' exited and could not be restarted: {:#}", log.name, e))).await;
+                                break;
+                            }
+                        }
+                        continue;
+                    };
+
+                    let (text, is_stdout) = match line {
+                        ShellLine::Stdout(l) => (l, true),
+                        ShellLine::Stderr(l) => (l, false),
+                    };
+
+                    if session.shell_pid.is_none()
+                        && let Some(pid) = text.strip_prefix(PID_MARKER)
+                    {
+                        session.shell_pid = pid.trim().parse().ok();
+                        continue;
+                    }
+
+                    // Output from background jobs between commands belongs to nobody
+                    let Some(cmd) = pending.as_mut() else {
+                        log.line(text).await;
+                        continue;
+                    };
+
+                    let (content, end) = split_end_marker(&text, &cmd.nonce, is_stdout);
+                    if end.is_none() || !content.is_empty() {
+                        log.line(content.to_string()).await;
+                        let stream = if is_stdout { &mut cmd.output.stdout } else { &mut cmd.output.stderr };
+                        stream.push_str(content);
+                        stream.push('\n');
+                    }
+
+                    if let Some(exit_code) = end {
+                        if is_stdout {
+                            cmd.output.exit_code = exit_code;
+                            cmd.stdout_done = true;
+                        } else {
+                            cmd.stderr_done = true;
+                        }
+                        if cmd.stdout_done && cmd.stderr_done && let Some(cmd) = pending.take() {
+                            escalation = None;
+                            finish(cmd, &log).await;
                         }
-                        Ok(None) => break,
-                        Err(_) => break,
                     }
                 }
             }
@@ -125,12 +515,423 @@ impl ShellSession {
     }
 }
 
-async fn read_next_line(reader: &Arc<Mutex<BufReader<ChildStdout>>>) -> Result<Option<String>> {
-    let mut reader = reader.lock().await;
-    let mut line = String::new();
-    let bytes = reader.read_line(&mut line).await?;
-    if bytes == 0 {
-        return Ok(None);
+/// Where a session's output shows up in the Terminal view.
+struct SessionLog {
+    name: String,
+    tx: mpsc::Sender<AppEvent>,
+}
+
+impl SessionLog {
+    async fn line(&self, line: String) {
+        let _ = self
+            .tx
+            .send(AppEvent::TerminalLine { session: self.name.clone(), line })
+            .await;
+    }
+}
+
+fn check_new_session(
+    name: &str,
+    sessions: &HashMap<String, mpsc::Sender<ShellRequest>>,
+) -> Result<()> {
+    if name.is_empty()
+        || name.len() > 32
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+    {
+        return Err(anyhow!(
+            "Invalid session name '{}': use up to 32 letters, digits, '-', '_' or '.'",
+            name
+        ));
+    }
+    if sessions.len() >= MAX_SESSIONS {
+        let mut names: Vec<&str> = sessions.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        return Err(anyhow!(
+            "Too many shell sessions ({}); reuse one of: {}",
+            MAX_SESSIONS,
+            names.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Unguessable end marker for one command, so file contents or logs can't fake it.
+fn new_nonce() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    hasher.write_u128(nanos);
+    format!("__AGERUS_END_{:016x}__", hasher.finish())
+}
+
+/// `cmd` as written to bash, followed by the end markers for `nonce`.
+fn wrap_command(cmd: &str, nonce: &str) -> String {
+    // The command arrives as a quoted heredoc and runs through `eval`, so an
+    // unbalanced quote or brace is a syntax error (exit 2) rather than bash
+    // reading on into the marker lines. Nothing inside the heredoc is
+    // expanded, and the nonce can't be guessed to end it early.
+    // Commands never wait on stdin, it carries our next command.
+    // After a syntax error in eval, bash misreads a `{` at the start of the next
+    // line; the blank line resets its parser.
+    // `$?` is eval's status; stderr gets its own end marker. Under `set -x`
+    // bash would trace `echo <nonce>` to stderr, which reads like the marker,
+    // so tracing is paused (quietly) around the markers.
+    format!(
+        "eval \"$(cat <<'{n}'\n{cmd}\n{n}\n)\" < /dev/null\n\n\
+         {{ __agerus_status=$?; __agerus_x=${{-//[^x]/}}; set +x; }} 2>/dev/null\n\
+         echo \"{n}:$__agerus_status\"; echo {n} >&2; if [ -n \"$__agerus_x\" ]; then set -x; fi\n",
+        cmd = cmd,
+        n = nonce
+    )
+}
+
+/// Splits the end marker off `line`. Only a marker at the very end of the line
+/// counts: `<nonce>:<exit code>` on stdout, the bare nonce on stderr.
+/// Returns the output part and, for a marker, the exit code it carried.
+fn split_end_marker<'a>(line: &'a str, nonce: &str, is_stdout: bool) -> (&'a str, Option<Option<i32>>) {
+    let Some(pos) = line.rfind(nonce) else {
+        return (line, None);
+    };
+    let rest = &line[pos + nonce.len()..];
+    let end = if is_stdout {
+        rest.strip_prefix(':')
+            .filter(|code| !code.is_empty() && code.bytes().all(|b| b.is_ascii_digit()))
+            .map(|code| code.parse().ok())
+    } else {
+        rest.is_empty().then_some(None)
+    };
+    match end {
+        Some(code) => (&line[..pos], Some(code)),
+        None => (line, None),
+    }
+}
+
+async fn finish(mut cmd: PendingCommand, log: &SessionLog) {
+    cmd.output.duration = cmd.started.elapsed();
+    if cmd.output.exit_code != Some(0) {
+        log.line(format!("[{}]", cmd.output.summary())).await;
+    }
+    let _ = cmd.responder.send(Ok(cmd.output));
+}
+
+fn forward_lines<R>(stream: R, tx: mpsc::Sender<ShellLine>, wrap: fn(String) -> ShellLine)
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(stream);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&buf).trim_end().to_string();
+                    if tx.send(wrap(line)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_status_summaries() {
+        let ok = CommandStatus {
+            exit_code: Some(0),
+            duration: Duration::from_millis(1500),
+            ..Default::default()
+        };
+        assert!(ok.succeeded());
+        assert_eq!(ok.summary(), "exit 0 in 1.50s");
+
+        let failed = CommandStatus {
+            exit_code: Some(2),
+            ..ok.clone()
+        };
+        assert!(!failed.succeeded());
+        assert_eq!(failed.summary(), "exit 2 in 1.50s");
+
+        let timed_out = CommandStatus {
+            exit_code: Some(130),
+            interrupted: true,
+            timed_out: true,
+            ..ok.clone()
+        };
+        assert!(!timed_out.succeeded());
+        assert_eq!(timed_out.summary(), "exit 130 in 1.50s (timed out)");
+
+        let died = CommandStatus {
+            exit_code: None,
+            ..ok
+        };
+        assert!(!died.succeeded());
+        assert!(died.summary().starts_with("no exit code"));
+    }
+
+    #[test]
+    fn new_sessions_need_a_valid_name_and_a_free_slot() {
+        let mut sessions = HashMap::new();
+        for name in ["main", "build-2", "web_server", "v1.2"] {
+            assert!(check_new_session(name, &sessions).is_ok(), "{}", name);
+        }
+        for name in ["", "has space", "a/b", "ünïcode", &"x".repeat(33)] {
+            let err = check_new_session(name, &sessions).unwrap_err().to_string();
+            assert!(err.starts_with("Invalid session name"), "{}", name);
+        }
+        assert!(check_new_session(&"x".repeat(32), &sessions).is_ok());
+
+        for i in (0..MAX_SESSIONS).rev() {
+            sessions.insert(format!("s{}", i), mpsc::channel(1).0);
+        }
+        let err = check_new_session("one-more", &sessions).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "Too many shell sessions (8); reuse one of: s0, s1, s2, s3, s4, s5, s6, s7"
+        );
+    }
+
+    /// A session running a real bash on the host, with the events it sends the app.
+    struct TestSession {
+        tx: mpsc::Sender<ShellRequest>,
+        events: mpsc::Receiver<AppEvent>,
+        task: tokio::task::JoinHandle<()>,
+        workspace: tempfile::TempDir,
+    }
+
+    impl TestSession {
+        fn start() -> Self {
+            let workspace = tempfile::tempdir().unwrap();
+            let config = Config {
+                workspace_path: workspace.path().to_path_buf(),
+                sandbox: crate::config::SandboxMode::None,
+                ..Config::default()
+            };
+            // Never called: there is no container under `sandbox = "none"`
+            let runtime: Arc<dyn ContainerRuntime> = Arc::new(docker_setup::runtime::Docker::default());
+            let (tx, rx) = mpsc::channel(32);
+            let (tx_events, events) = mpsc::channel(256);
+            let log = SessionLog { name: "test".into(), tx: tx_events };
+            let task = tokio::spawn(ShellSession::run_session(rx, log, config, runtime));
+            Self { tx, events, task, workspace }
+        }
+
+        async fn run(&self, cmd: &str) -> Result<CommandOutput> {
+            let (response_tx, response_rx) = oneshot::channel();
+            let request = ShellRequest::RunCommand {
+                session: "test".into(),
+                cmd: cmd.into(),
+                response_tx,
+            };
+            self.tx.send(request).await.unwrap();
+            tokio::time::timeout(Duration::from_secs(10), response_rx)
+                .await
+                .unwrap()
+                .unwrap()
+        }
+
+        async fn next_notice(&mut self) -> String {
+            loop {
+                let event = tokio::time::timeout(Duration::from_secs(10), self.events.recv())
+                    .await
+                    .unwrap()
+                    .unwrap();
+                if let AppEvent::Notice(notice) = event {
+                    return notice;
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn a_shell_that_dies_fails_its_command_and_is_restarted() {
+        let mut session = TestSession::start();
+        assert_eq!(session.run("cd /tmp && echo hi").await.unwrap().stdout, "hi\n");
+
+        let err = session.run("exit").await.unwrap_err().to_string();
+        assert!(err.starts_with("Shell 'test' died while the command was running"), "{}", err);
+        assert!(session.next_notice().await.contains("exited and was restarted"));
+
+        // A fresh bash, back in the workspace
+        let out = session.run("pwd").await.unwrap();
+        let workspace = std::fs::canonicalize(session.workspace.path()).unwrap();
+        assert_eq!(out.exit_code, Some(0));
+        assert_eq!(out.stdout, format!("{}\n", workspace.display()));
+    }
+
+    #[tokio::test]
+    async fn a_shell_that_keeps_dying_on_its_own_is_given_up_on() {
+        let mut session = TestSession::start();
+        for _ in 0..MAX_QUICK_RESTARTS {
+            // Dies right after the command, with nothing running
+            session.run("(sleep 0.2; kill -9 $$) &").await.unwrap();
+            assert!(session.next_notice().await.contains("exited and was restarted"));
+        }
+        session.run("(sleep 0.2; kill -9 $$) &").await.unwrap();
+        assert!(session.next_notice().await.contains("giving up"));
+        tokio::time::timeout(Duration::from_secs(10), session.task).await.unwrap().unwrap();
+    }
+
+    const NONCE: &str = "__AGERUS_END_0123456789abcdef__";
+
+    #[test]
+    fn nonces_are_unique_and_well_formed() {
+        let a = new_nonce();
+        let b = new_nonce();
+        assert_ne!(a, b);
+        assert!(a.starts_with("__AGERUS_END_") && a.ends_with("__"));
+        assert_eq!(a.len(), NONCE.len());
+    }
+
+    #[test]
+    fn marker_on_its_own_line() {
+        assert_eq!(split_end_marker(&format!("{}:0", NONCE), NONCE, true), ("", Some(Some(0))));
+        assert_eq!(split_end_marker(&format!("{}:127", NONCE), NONCE, true), ("", Some(Some(127))));
+        assert_eq!(split_end_marker(NONCE, NONCE, false), ("", Some(None)));
+    }
+
+    #[test]
+    fn marker_after_output_without_a_newline() {
+        // `printf foo` leaves the marker mid-line
+        let line = format!("foo{}:0", NONCE);
+        assert_eq!(split_end_marker(&line, NONCE, true), ("foo", Some(Some(0))));
+        let line = format!("no newline on stderr{}", NONCE);
+        assert_eq!(split_end_marker(&line, NONCE, false), ("no newline on stderr", Some(None)));
+    }
+
+    #[test]
+    fn nonce_inside_output_is_not_a_marker() {
+        for line in [
+            format!("grep found {}:0 in a log", NONCE),
+            format!("{}:", NONCE),
+            format!("{}:12a", NONCE),
+            format!("{}:-1", NONCE),
+            format!("{} ", NONCE),
+        ] {
+            assert_eq!(split_end_marker(&line, NONCE, true), (line.as_str(), None), "{}", line);
+        }
+        // The stdout form on stderr, and the stderr form on stdout
+        let stdout_marker = format!("{}:0", NONCE);
+        assert_eq!(split_end_marker(&stdout_marker, NONCE, false).1, None);
+        assert_eq!(split_end_marker(NONCE, NONCE, true).1, None);
+    }
+
+    #[test]
+    fn stderr_marker_with_trailing_text_is_not_a_marker() {
+        let line = format!("{} and then some", NONCE);
+        assert_eq!(split_end_marker(&line, NONCE, false), (line.as_str(), None));
+    }
+
+    #[test]
+    fn foreign_and_old_nonces_are_not_markers() {
+        let old = "__AGERUS_END_fedcba9876543210__";
+        assert_eq!(split_end_marker(&format!("{}:0", old), NONCE, true).1, None);
+        assert_eq!(split_end_marker(old, NONCE, false).1, None);
+        // The last occurrence decides: an old marker earlier on the line doesn't hide ours
+        let line = format!("{}:1 then {}:2", NONCE, NONCE);
+        assert_eq!(split_end_marker(&line, NONCE, true), (format!("{}:1 then ", NONCE).as_str(), Some(Some(2))));
+    }
+
+    /// What a wrapped command printed before each marker, its exit code, and
+    /// the stderr of the command after it.
+    struct Wrapped {
+        stdout: Vec<String>,
+        stderr: Vec<String>,
+        code: Option<i32>,
+        next_stderr: Vec<String>,
+    }
+
+    /// Runs `cmd` wrapped for `NONCE` through a real bash, then another command.
+    fn run_wrapped(cmd: &str) -> Wrapped {
+        let mut child = std::process::Command::new("bash")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        {
+            use std::io::Write;
+            let mut stdin = child.stdin.take().unwrap();
+            stdin.write_all(wrap_command(cmd, NONCE).as_bytes()).unwrap();
+            stdin.write_all(b"echo after-stdout; echo after-stderr >&2\n").unwrap();
+        }
+        let output = child.wait_with_output().unwrap();
+
+        let scan = |bytes: &[u8], is_stdout: bool| {
+            let text = String::from_utf8_lossy(bytes).into_owned();
+            let mut before = Vec::new();
+            let mut code = None;
+            let mut lines = text.lines();
+            for line in lines.by_ref() {
+                let (out, end) = split_end_marker(line, NONCE, is_stdout);
+                if !out.is_empty() {
+                    before.push(out.to_string());
+                }
+                if let Some(end) = end {
+                    code = Some(end);
+                    break;
+                }
+            }
+            let after: Vec<String> = lines.map(str::to_string).collect();
+            let expected = if is_stdout { "after-stdout" } else { "after-stderr" };
+            // Only the real marker ends the command: the next one's output follows it
+            assert_eq!(after.last().map(String::as_str), Some(expected), "{:?}", after);
+            (before, code.expect("no end marker"), after)
+        };
+        let (stdout, code, _) = scan(&output.stdout, true);
+        let (stderr, _, next_stderr) = scan(&output.stderr, false);
+        Wrapped { stdout, stderr, code, next_stderr }
+    }
+
+    #[test]
+    fn wrapped_command_reports_output_and_status() {
+        let run = run_wrapped("printf foo; printf bar >&2; exit_with() { return $1; }; exit_with 3");
+        assert_eq!(run.stdout, ["foo"]);
+        assert_eq!(run.stderr, ["bar"]);
+        assert_eq!(run.code, Some(3));
+        assert_eq!(run.next_stderr, ["after-stderr"]);
+    }
+
+    #[test]
+    fn syntax_errors_fail_fast_and_keep_the_markers() {
+        let run = run_wrapped("echo \"oops");
+        assert!(run.stdout.is_empty());
+        assert!(run.stderr.iter().any(|l| l.contains("unexpected EOF")), "{:?}", run.stderr);
+        assert_eq!(run.code, Some(2));
+        assert_eq!(run.next_stderr, ["after-stderr"]);
+
+        let run = run_wrapped("if true; then echo half");
+        assert_eq!(run.code, Some(2));
+    }
+
+    #[test]
+    fn trailing_comments_and_background_jobs_still_work() {
+        let run = run_wrapped("echo 'a \"quoted\" $HOME'; true # done");
+        assert_eq!(run.stdout, ["a \"quoted\" $HOME"]);
+        assert_eq!(run.code, Some(0));
+
+        let run = run_wrapped("sleep 0 &\necho spawned");
+        assert_eq!(run.stdout, ["spawned"]);
+        assert_eq!(run.code, Some(0));
+    }
+
+    #[test]
+    fn set_x_traces_do_not_end_the_command_early() {
+        let run = run_wrapped("set -x; echo hi; false");
+        assert_eq!(run.stdout, ["hi"]);
+        // One level deeper than a plain command: it runs inside eval
+        assert_eq!(run.stderr, ["++ echo hi", "++ false"]);
+        assert_eq!(run.code, Some(1));
+        // Tracing is back on for the next command
+        assert_eq!(run.next_stderr, ["+ echo after-stdout", "+ echo after-stderr", "after-stderr"]);
     }
-    Ok(Some(line))
 }
@@ -0,0 +1,32 @@
+use super::theme::*;
+use crate::app::App;
+use crate::config::ToolApproval;
+use ratatui::{
+    layout::{Alignment, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+
+/// Shown across the top for as long as there is no sandbox.
+pub fn draw(f: &mut Frame, app: &App, area: Rect) {
+    let approval = match app.config.tool_approval() {
+        ToolApproval::Off => "tool calls run without asking",
+        ToolApproval::Changes => "commands and file changes ask first",
+        ToolApproval::All => "every tool call asks first",
+    };
+    let text = format!(
+        " ⚠ NO SANDBOX: the agent runs commands directly on this machine, as you ({}) ",
+        approval
+    );
+    let banner = Paragraph::new(Line::from(Span::styled(
+        text,
+        Style::default()
+            .fg(FG_PRIMARY)
+            .add_modifier(Modifier::BOLD),
+    )))
+    .alignment(Alignment::Center)
+    .style(Style::default().bg(ACCENT_RED));
+    f.render_widget(banner, area);
+}
@@ -47,6 +47,32 @@ pub fn draw(f: &mut Frame, app: &App, area: Rect) {
                     Style::default().fg(FG_SECONDARY),
                 )));
             }
+            MessageRole::Tool => {
+                let name = msg.tool_name.as_deref().unwrap_or("tool");
+                let failed = msg.command.as_ref().is_some_and(|c| !c.succeeded());
+                let summary = if let Some(command) = &msg.command {
+                    format!("{} ({} bytes of output)", command.summary(), msg.content.len())
+                } else if msg.content.len() > 200 || msg.content.contains('\n') {
+                    if name == "run_command" {
+                        format!("Output ({} bytes) sent to terminal.", msg.content.len())
+                    } else {
+                        // The full result went to the model; show what it starts with
+                        let first_line = msg.content.lines().next().unwrap_or_default();
+                        let mut head: String = first_line.chars().take(80).collect();
+                        if head.len() < first_line.len() {
+                            head.push('…');
+                        }
+                        format!("{} ({} bytes)", head, msg.content.len())
+                    }
+                } else {
+                    msg.content.clone()
+                };
+                let (mark, color) = if failed { ("✗", Color::Red) } else { ("✓", ACCENT_BLUE) };
+                lines.push(Line::from(vec![
+                    Span::styled(format!("  {} {} ", mark, name), Style::default().fg(color)),
+                    Span::styled(summary, Style::default().fg(FG_SECONDARY)),
+                ]));
+            }
             MessageRole::Thinking => {
                 // Pulse effect logic:
                 // Only pulse if the app is currently processing AND this is the last message.
@@ -129,6 +155,17 @@ pub fn draw(f: &mut Frame, app: &App, area: Rect) {
                     let rendered = render_markdown(&msg.content, max_width, base_style);
                     lines.extend(rendered);
                 }
+
+                for call in &msg.tool_calls {
+                    let mut args = call.arguments.to_string();
+                    if args.chars().count() > 60 {
+                        args = format!("{}…", args.chars().take(60).collect::<String>());
+                    }
+                    lines.push(Line::from(Span::styled(
+                        format!("  🛠️ {}({})", call.name, args),
+                        Style::default().fg(FG_SECONDARY),
+                    )));
+                }
             }
         }
         lines.push(Line::from("")); // Spacing
@@ -1,5 +1,5 @@
 use super::theme::*;
-use crate::app::App;
+use crate::app::{App, AppMode};
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Style, Stylize}, // <--- Added Stylize
@@ -32,7 +32,19 @@ pub fn draw(f: &mut Frame, app: &App, area: Rect) {
     });
 
     // If input is empty, show placeholder
-    let text = if app.input_buffer.is_empty() {
+    let text = if let Some(pending) = app.pending_approval.as_ref().filter(|_| !app.term_focused) {
+        Line::from(Span::styled(
+            format!("Allow {}? y to allow, n to decline.", pending.call.name),
+            Style::default().fg(ACCENT_ORANGE),
+        ))
+    } else if app.mode == AppMode::Terminal {
+        let hint = if app.term_focused {
+            "Typing goes to the terminal. Ctrl+] to release."
+        } else {
+            "Enter to focus the terminal, Tab for chat, PgUp/PgDn to scroll, [ ] for agent shells."
+        };
+        Line::from(Span::styled(hint, Style::default().fg(FG_SECONDARY)))
+    } else if app.input_buffer.is_empty() {
         Line::from(Span::styled(
             "Ask Agerus anything...",
             Style::default().fg(FG_SECONDARY),
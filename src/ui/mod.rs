@@ -1,3 +1,4 @@
+pub mod banner;
 pub mod chat;
 pub mod input;
 pub mod model_selector;
@@ -7,6 +8,7 @@ pub mod terminal;
 pub mod theme;
 
 use crate::app::{App, AppMode};
+use crate::config::SandboxMode;
 use ratatui::{
     layout::{Constraint, Direction, Layout},
     style::Stylize, // <--- Added this import
@@ -21,19 +23,25 @@ pub fn draw(f: &mut Frame, app: &App) {
     // 1. Main Background
     f.render_widget(Block::default().bg(BG_MAIN), area);
 
-    // 2. Vertical Layout: [ Content (Flex), Input (4), Status (1) ]
+    // 2. Vertical Layout: [ Banner (1, no sandbox only), Content (Flex), Input (4), Status (1) ]
+    let banner_height = if app.config.sandbox == SandboxMode::None { 1 } else { 0 };
     let vertical = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
+            Constraint::Length(banner_height),
             Constraint::Min(1),    // Chat or Terminal or Splash
             Constraint::Length(4), // Input Bar (Height 4 for padding)
             Constraint::Length(1), // Status Footer
         ])
         .split(area);
 
-    let content_area = vertical[0];
-    let input_area = vertical[1];
-    let status_area = vertical[2];
+    let content_area = vertical[1];
+    let input_area = vertical[2];
+    let status_area = vertical[3];
+
+    if banner_height > 0 {
+        banner::draw(f, app, vertical[0]);
+    }
 
     // 3. Render Content Area
     if app.messages.len() <= 1 && app.mode == AppMode::Chat {
@@ -1,5 +1,7 @@
 use super::theme::*;
 use crate::app::{App, AppMode};
+use crate::config::{NetworkMode, SandboxMode};
+use crate::processes::ProcessStatus;
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
@@ -28,6 +30,13 @@ pub fn draw(f: &mut Frame, app: &App, area: Rect) {
         " "
     };
 
+    // Open network in the usual colour; any restriction stands out
+    let policy_color = if app.config.container.network == NetworkMode::Bridge {
+        FG_SECONDARY
+    } else {
+        ACCENT_ORANGE
+    };
+
     let left_text = vec![
         Span::styled(
             format!(" agerus v0.1.0 "),
@@ -44,13 +53,39 @@ pub fn draw(f: &mut Frame, app: &App, area: Rect) {
                 .bg(ACCENT_BLUE)
                 .add_modifier(Modifier::BOLD),
         ),
+        if app.config.sandbox == SandboxMode::None {
+            Span::styled(
+                " NO SANDBOX ",
+                Style::default()
+                    .fg(FG_PRIMARY)
+                    .bg(ACCENT_RED)
+                    .add_modifier(Modifier::BOLD),
+            )
+        } else {
+            Span::styled(
+                format!(" {} ", app.config.container.policy_summary()),
+                Style::default().fg(policy_color),
+            )
+        },
     ];
 
-    let right_text = vec![
-        Span::styled(format!(" {} ", spinner), Style::default().fg(ACCENT_ORANGE)),
-        Span::styled(" tab: switch view ", Style::default().fg(FG_SECONDARY)),
+    let running = app
+        .processes
+        .iter()
+        .filter(|p| p.status == ProcessStatus::Running)
+        .count();
+
+    let mut right_text = vec![Span::styled(format!(" {} ", spinner), Style::default().fg(ACCENT_ORANGE))];
+    if running > 0 {
+        right_text.push(Span::styled(format!(" ● {} bg ", running), Style::default().fg(Color::Green)));
+    }
+    right_text.extend([
+        Span::styled(
+            if app.terminal_focused() { " ctrl+]: release " } else { " tab: switch view " },
+            Style::default().fg(FG_SECONDARY),
+        ),
         Span::styled(" ctrl+p: model ", Style::default().fg(FG_SECONDARY)),
-    ];
+    ]);
 
     let layout = Layout::default()
         .direction(Direction::Horizontal)
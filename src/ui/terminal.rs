@@ -1,28 +1,177 @@
 use super::theme::*;
 use crate::app::App;
+use crate::processes::ProcessStatus;
 use ratatui::{
-    layout::Rect,
-    style::Style,
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Position, Rect},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, List, ListItem, Padding},
+    widgets::{Block, Borders, List, ListItem, Padding, Paragraph},
     Frame,
 };
 
+/// Height of the agent's command log under the interactive terminal
+const AGENT_PANE_HEIGHT: u16 = 8;
+/// Width of the background process list beside the agent log
+const PROCESS_PANE_WIDTH: u16 = 32;
+
 pub fn draw(f: &mut Frame, app: &App, area: Rect) {
-    let items: Vec<ListItem> = app
-        .terminal_lines
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(AGENT_PANE_HEIGHT)])
+        .split(area);
+
+    let term_area = Block::default()
+        .padding(Padding::new(1, 1, 0, 0))
+        .inner(sections[0]);
+    app.term_size.set((term_area.height, term_area.width));
+    draw_pty(f, app, term_area);
+
+    if app.processes.is_empty() {
+        draw_agent_log(f, app, sections[1]);
+    } else {
+        let bottom = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(10), Constraint::Length(PROCESS_PANE_WIDTH)])
+            .split(sections[1]);
+        draw_agent_log(f, app, bottom[0]);
+        draw_processes(f, app, bottom[1]);
+    }
+}
+
+fn draw_pty(f: &mut Frame, app: &App, area: Rect) {
+    let Some(parser) = app.pty.as_ref().and_then(|pty| pty.parser()) else {
+        let hint = Paragraph::new(Line::from(Span::styled(
+            "Press Enter to open a shell in the sandbox.",
+            Style::default().fg(FG_SECONDARY),
+        )));
+        f.render_widget(hint, area);
+        return;
+    };
+    let screen = parser.screen();
+
+    render_screen(screen, area, f.buffer_mut());
+
+    if app.term_focused && !screen.hide_cursor() && screen.scrollback() == 0 {
+        let (row, col) = screen.cursor_position();
+        if row < area.height && col < area.width {
+            f.set_cursor_position(Position::new(area.x + col, area.y + row));
+        }
+    }
+}
+
+/// Copies the emulator's cells into the frame buffer.
+fn render_screen(screen: &vt100::Screen, area: Rect, buf: &mut Buffer) {
+    let (rows, cols) = screen.size();
+    for row in 0..rows.min(area.height) {
+        for col in 0..cols.min(area.width) {
+            let Some(cell) = screen.cell(row, col) else {
+                continue;
+            };
+            if cell.is_wide_continuation() {
+                continue;
+            }
+
+            let mut fg = convert_color(cell.fgcolor(), FG_PRIMARY);
+            let mut bg = convert_color(cell.bgcolor(), BG_MAIN);
+            if cell.inverse() {
+                std::mem::swap(&mut fg, &mut bg);
+            }
+            let mut modifier = Modifier::empty();
+            if cell.bold() {
+                modifier |= Modifier::BOLD;
+            }
+            if cell.italic() {
+                modifier |= Modifier::ITALIC;
+            }
+            if cell.underline() {
+                modifier |= Modifier::UNDERLINED;
+            }
+
+            let contents = cell.contents();
+            let symbol = if contents.is_empty() { " " } else { contents.as_str() };
+            if let Some(target) = buf.cell_mut(Position::new(area.x + col, area.y + row)) {
+                target
+                    .set_symbol(symbol)
+                    .set_style(Style::default().fg(fg).bg(bg).add_modifier(modifier));
+            }
+        }
+    }
+}
+
+fn convert_color(color: vt100::Color, default: Color) -> Color {
+    match color {
+        vt100::Color::Default => default,
+        vt100::Color::Idx(i) => Color::Indexed(i),
+        vt100::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
+    }
+}
+
+/// What the agent ran through `run_command`, one tab per shell session.
+fn draw_agent_log(f: &mut Frame, app: &App, area: Rect) {
+    let Some(log) = app.shell_logs.get(app.active_log) else {
+        return;
+    };
+    let items: Vec<ListItem> = log
+        .lines
         .iter()
-        .map(|l| ListItem::new(Line::from(Span::styled(l, Style::default().fg(FG_PRIMARY)))))
+        .map(|l| ListItem::new(Line::from(Span::styled(l, Style::default().fg(FG_SECONDARY)))))
         .collect();
 
-    // Using a mutable borrow of app state for scrolling is standard pattern in ratatui
-    // We clone the state here because draw takes &App (immutable), but List needs internal mutability for state
-    // In a real app we might pass &mut App or keep state separate, but cloning ListState is cheap.
-    let mut state = app.term_scroll.clone();
+    // Cloning ListState is cheap and keeps draw taking &App
+    let mut state = log.scroll.clone();
+
+    let mut title = vec![Span::styled(" agent shell ", Style::default().fg(ACCENT_BLUE))];
+    for (i, l) in app.shell_logs.iter().enumerate() {
+        let style = if i == app.active_log {
+            Style::default().fg(FG_PRIMARY).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(FG_SECONDARY)
+        };
+        title.push(Span::styled(format!("[{}]", l.name), style));
+        title.push(Span::raw(" "));
+    }
+    if app.shell_logs.len() > 1 {
+        title.push(Span::styled("( [ / ] to switch ) ", Style::default().fg(FG_SECONDARY)));
+    }
+
+    let block = Block::default()
+        .borders(Borders::TOP)
+        .border_style(Style::default().fg(FG_SECONDARY))
+        .title(Line::from(title))
+        .padding(Padding::new(1, 1, 0, 0));
+    f.render_stateful_widget(List::new(items).block(block), area, &mut state);
+}
+
+/// Background processes the agent started, with their state.
+fn draw_processes(f: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .processes
+        .iter()
+        .map(|p| {
+            let (icon, color, state) = match &p.status {
+                ProcessStatus::Running => {
+                    ("●", Color::Green, format!("{}s", p.started.elapsed().as_secs()))
+                }
+                ProcessStatus::Exited(Some(0)) => ("○", FG_SECONDARY, "exit 0".into()),
+                ProcessStatus::Exited(Some(code)) => ("✗", Color::Red, format!("exit {}", code)),
+                ProcessStatus::Exited(None) => ("✗", Color::Red, "exited".into()),
+                ProcessStatus::Killed => ("○", FG_SECONDARY, "killed".into()),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} ", icon), Style::default().fg(color)),
+                Span::styled(p.name.clone(), Style::default().fg(FG_PRIMARY)),
+                Span::styled(format!(" {} ", state), Style::default().fg(FG_SECONDARY)),
+                // Clipped by the pane; enough to tell similar names apart
+                Span::styled(p.command.clone(), Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
 
-    f.render_stateful_widget(
-        List::new(items).block(Block::default().padding(Padding::new(1, 1, 1, 1))),
-        area,
-        &mut state,
-    );
+    let block = Block::default()
+        .borders(Borders::TOP | Borders::LEFT)
+        .border_style(Style::default().fg(FG_SECONDARY))
+        .title(Span::styled(" background ", Style::default().fg(ACCENT_BLUE)))
+        .padding(Padding::new(1, 0, 0, 0));
+    f.render_widget(List::new(items).block(block), area);
 }
@@ -7,5 +7,6 @@ pub const FG_PRIMARY: Color = Color::Rgb(220, 220, 220); // Off-white
 pub const FG_SECONDARY: Color = Color::Rgb(100, 100, 100); // Dimmed text
 pub const ACCENT_ORANGE: Color = Color::Rgb(255, 158, 100); // Cursor / Highlight
 pub const ACCENT_BLUE: Color = Color::Rgb(122, 162, 247); // Mode indicators
+pub const ACCENT_RED: Color = Color::Rgb(220, 50, 47); // No-sandbox warning
 
 pub const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];